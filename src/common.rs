@@ -34,6 +34,9 @@ pub struct TreeConfig {
     pub cdc_max_size: usize,
     /// Values larger than this will be chunked using CDC. Smaller values are inlined.
     pub max_inline_value_size: usize,
+    /// Number of sibling nodes (and chunked value pieces) a cursor requests concurrently
+    /// ahead of its position. Useful for stores with real latency; 0 disables read-ahead.
+    pub read_ahead: usize,
 }
 
 impl Default for TreeConfig {
@@ -55,6 +58,7 @@ impl Default for TreeConfig {
             // Might set this lower than cdc_min_size, or equal to avg, depends on strategy.
             // Let's start relatively low. Consider average cost of storing hash vs inline data.
             max_inline_value_size: 1024, // 1 KiB threshold for chunking
+            read_ahead: 0,
         }
     }
 }
\ No newline at end of file
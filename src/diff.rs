@@ -1,245 +1,1077 @@
 // Prolly Tree Diffing Module
 //
 //! Computes differences between two versions of a Prolly Tree.
+//!
+//! The diff is produced lazily by a walker that keeps a stack of pending subtree comparisons
+//! in key order and only descends where hashes differ. `diff_stream` exposes it as an async
+//! stream that callers can stop at any point; `diff_trees` collects the whole stream.
 
-use async_recursion::async_recursion;
-use futures::future::try_join_all; 
+use futures::future::try_join_all;
+use futures::stream::{self, Stream, TryStreamExt};
+use serde::{Serialize, Deserialize};
 use log::{debug, trace, warn, error};
-use std::collections::VecDeque; // Could be useful for iteration helpers
+use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::sync::Arc;
 
 use crate::common::{Hash, Key, Value, TreeConfig}; // Need config potentially for value loading?
 use crate::error::{Result, ProllyError};
-use crate::node::definition::{Node, ValueRepr};
+pub use crate::error::DiffConflict;
+use crate::node::definition::{Node, LeafEntry, ValueRepr};
+use crate::node::cache::NodeCache;
 use crate::store::ChunkStore;
+use crate::store::chunk_store::check_batch_len;
+use crate::store::patch_format::{Patch, PatchRecord, PatchValue};
+
+/// The kind of change a `DiffEntry` describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    /// The key only exists in the 'right' tree.
+    Added,
+    /// The key only exists in the 'left' tree.
+    Removed,
+    /// The key exists in both trees with different values.
+    Modified,
+}
 
 /// Represents a single difference between two tree versions.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DiffEntry {
     pub key: Key,
-    /// Value in the 'left' (or 'from') tree. None if added.
+    pub kind: DiffKind,
+    /// Value in the 'left' (or 'from') tree. None if added, or if values were not requested.
     pub left_value: Option<Value>,
-    /// Value in the 'right' (or 'to') tree. None if deleted.
+    /// Value in the 'right' (or 'to') tree. None if deleted, or if values were not requested.
     pub right_value: Option<Value>,
+    /// Digest of the 'left' value (see `ValueRepr::digest`). Only set in `DiffMode::Digests`.
+    pub left_digest: Option<Hash>,
+    /// Digest of the 'right' value. Only set in `DiffMode::Digests`.
+    pub right_digest: Option<Hash>,
 }
 
 impl DiffEntry {
     fn addition(key: Key, right_value: Value) -> Self {
-        Self { key, left_value: None, right_value: Some(right_value) }
+        Self { key, kind: DiffKind::Added, left_value: None, right_value: Some(right_value), left_digest: None, right_digest: None }
     }
     fn deletion(key: Key, left_value: Value) -> Self {
-        Self { key, left_value: Some(left_value), right_value: None }
+        Self { key, kind: DiffKind::Removed, left_value: Some(left_value), right_value: None, left_digest: None, right_digest: None }
     }
     fn modification(key: Key, left_value: Value, right_value: Value) -> Self {
-        Self { key, left_value: Some(left_value), right_value: Some(right_value) }
+        Self { key, kind: DiffKind::Modified, left_value: Some(left_value), right_value: Some(right_value), left_digest: None, right_digest: None }
+    }
+}
+
+/// How `ProllyTree::apply_diff` treats entries whose old value does not match the tree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApplyMode {
+    /// Check every entry's `left_value` against the current value first. If any differ,
+    /// nothing is applied and the call fails with `ProllyError::DiffConflict`.
+    #[default]
+    Strict,
+    /// Write every entry's right side regardless of the current value.
+    Force,
+}
+
+/// What a diff reports for each changed key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffMode {
+    /// Keys with both values loaded. Chunked values are reconstructed from the store.
+    #[default]
+    Values,
+    /// Keys and change kinds only.
+    Keys,
+    /// Keys, change kinds and value digests.
+    Digests,
+}
+
+/// Options for streaming diffs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct DiffOptions {
+    /// What to report for each changed key. Outside `DiffMode::Values`, no value chunks are
+    /// read and equal keys are compared by value representation (inline bytes or chunk
+    /// hashes), so the same bytes stored inline on one side and chunked on the other are
+    /// reported as modified.
+    pub mode: DiffMode,
+    /// Maximum number of node or value fetches issued at once. This is also how many
+    /// changes are materialized ahead of the consumer. Values below 1 are treated as 1.
+    pub concurrency: usize,
+    /// Only report changes to keys greater than or equal to this key. Subtrees entirely
+    /// before it are not loaded.
+    pub start: Option<Key>,
+    /// Only report changes to keys strictly less than this key. Subtrees entirely at or
+    /// after it are not loaded.
+    pub end: Option<Key>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions { mode: DiffMode::Values, concurrency: 8, start: None, end: None }
     }
 }
 
+/// Counts of the changes between two trees, as returned by `diff_summary`.
+///
+/// Keys are compared by value representation, as in `DiffMode::Keys`, so the same bytes
+/// stored inline on one side and chunked on the other count as modified.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffSummary {
+    pub added: u64,
+    pub removed: u64,
+    pub modified: u64,
+    /// Total size of the values of added keys plus the new values of modified keys.
+    /// Only computed when `DiffSummaryOptions::include_bytes` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_added: Option<u64>,
+    /// Total size of the values of removed keys plus the old values of modified keys.
+    /// Only computed when `DiffSummaryOptions::include_bytes` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_removed: Option<u64>,
+}
+
+/// Options for `diff_summary_with_options`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct DiffSummaryOptions {
+    /// As in `DiffOptions`.
+    pub concurrency: usize,
+    /// Only count keys greater than or equal to this key.
+    pub start: Option<Key>,
+    /// Only count keys strictly less than this key.
+    pub end: Option<Key>,
+    /// Also total the sizes of the changed values. This visits every changed leaf (and
+    /// reads single-chunk values) instead of counting added or removed subtrees from
+    /// their item counts.
+    pub include_bytes: bool,
+}
+
+impl Default for DiffSummaryOptions {
+    fn default() -> Self {
+        DiffSummaryOptions { concurrency: DiffOptions::default().concurrency, start: None, end: None, include_bytes: false }
+    }
+}
+
+/// A subtree referenced by its parent, described without loading it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubtreeRef {
+    pub hash: Hash,
+    /// Height of the subtree; 0 for a leaf node.
+    pub level: u8,
+    /// Number of keys in the subtree.
+    pub num_items: u64,
+}
+
+/// A key range in which two trees differ, as returned by `changed_ranges`.
+///
+/// The range holds the keys `k` with `low < k <= high`, the bounds implied by the boundary
+/// keys of the parents of the subtrees it lists. Outside of the reported ranges, both trees
+/// hold the same entries; inside, they usually differ, but may hold the same entries split
+/// into nodes differently.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedRange {
+    /// Exclusive lower bound (unbounded if `None`).
+    pub low: Option<Key>,
+    /// Inclusive upper bound (unbounded if `None`).
+    pub high: Option<Key>,
+    /// The left tree's subtrees covering the range, in key order. Empty if the range only
+    /// holds keys of the right tree.
+    pub left: Vec<SubtreeRef>,
+    /// The right tree's subtrees covering the range, in key order. Empty if the range only
+    /// holds keys of the left tree.
+    pub right: Vec<SubtreeRef>,
+}
+
 /// Computes the differences between two Prolly Trees represented by their root hashes.
-/// 
-/// Requires shared access to a `ChunkStore` that contains the nodes for *both* trees.
+///
+/// Requires shared access to a `ChunkStore` that contains the nodes for *both* trees; see
+/// `diff_trees_across_stores` for trees held in different stores.
 pub async fn diff_trees<S: ChunkStore>(
     left_root_hash: Option<Hash>,
     right_root_hash: Option<Hash>,
     store: Arc<S>, // Use Arc for shared ownership across async calls
     config: TreeConfig, // Pass config for value reconstruction if needed
 ) -> Result<Vec<DiffEntry>> {
-    recursive_diff(left_root_hash, right_root_hash, store, config).await
+    diff_trees_with_options(left_root_hash, right_root_hash, store, config, Arc::new(NodeCache::default()), DiffOptions::default()).await
 }
 
-#[async_recursion] // Use macro to handle async recursion boxing
-async fn recursive_diff<S: ChunkStore>(
-    left_hash: Option<Hash>,
-    right_hash: Option<Hash>,
+/// Same as `diff_trees`, but loads nodes through the given decoded-node cache
+/// (typically the one owned by the `ProllyTree` the roots belong to) and applies `options`.
+pub async fn diff_trees_with_options<S: ChunkStore>(
+    left_root_hash: Option<Hash>,
+    right_root_hash: Option<Hash>,
     store: Arc<S>,
-    config: TreeConfig, // Cloned or passed down
+    config: TreeConfig,
+    node_cache: Arc<NodeCache>,
+    options: DiffOptions,
 ) -> Result<Vec<DiffEntry>> {
-    
-    trace!("recursive_diff called with left={:?}, right={:?}", left_hash, right_hash);
-
-    // --- Base Cases ---
-    if left_hash == right_hash {
-        // Hashes match (or both are None), subtrees are identical.
-        return Ok(Vec::new());
-    }
-
-    if left_hash.is_none() {
-        // Left side doesn't exist, everything on right is an addition.
-        debug!("Diff: Left side None, generating additions for right hash {:?}", right_hash);
-        return generate_diffs_for_subtree(right_hash.unwrap(), &store, &config, true).await;
-    }
-
-    if right_hash.is_none() {
-        // Right side doesn't exist, everything on left is a deletion.
-        debug!("Diff: Right side None, generating deletions for left hash {:?}", left_hash);
-        return generate_diffs_for_subtree(left_hash.unwrap(), &store, &config, false).await;
-    }
-
-    // --- Recursive Step: Hashes differ and both exist ---
-    let h1 = left_hash.unwrap();
-    let h2 = right_hash.unwrap();
-
-    // Load both nodes
-    // TODO: Optimize - load nodes concurrently? e.g., using try_join!
-    let node1 = load_node_for_diff(&h1, &store).await?;
-    let node2 = load_node_for_diff(&h2, &store).await?;
-
-    let mut diffs = Vec::new();
-
-    match (node1, node2) {
-        // --- Case 1: Both are Leaf Nodes ---
-        (Node::Leaf { entries: entries1, .. }, Node::Leaf { entries: entries2, .. }) => {
-            debug!("Diff: Comparing leaves {:?} and {:?}", h1, h2);
-            // Perform a sorted merge diff on the leaf entries
-            let mut iter1 = entries1.into_iter().peekable();
-            let mut iter2 = entries2.into_iter().peekable();
-
-            loop {
-                match (iter1.peek(), iter2.peek()) {
-                    (Some(e1), Some(e2)) => {
-                        use std::cmp::Ordering::*;
-                        match e1.key.cmp(&e2.key) {
-                            Less => { // e1.key is smaller, means e1 was deleted
-                                let deleted_entry = iter1.next().unwrap();
-                                let val = load_value_repr(&deleted_entry.value, &store, &config).await?;
-                                diffs.push(DiffEntry::deletion(deleted_entry.key, val));
-                            }
-                            Greater => { // e2.key is smaller, means e2 was added
-                                let added_entry = iter2.next().unwrap();
-                                let val = load_value_repr(&added_entry.value, &store, &config).await?;
-                                diffs.push(DiffEntry::addition(added_entry.key, val));
-                            }
-                            Equal => { // Keys match, check values
-                                let entry1 = iter1.next().unwrap();
-                                let entry2 = iter2.next().unwrap();
-                                let val1 = load_value_repr(&entry1.value, &store, &config).await?;
-                                let val2 = load_value_repr(&entry2.value, &store, &config).await?;
-                                if val1 != val2 { // Values differ, modification
-                                    diffs.push(DiffEntry::modification(entry1.key, val1, val2));
-                                }
-                                // If values are equal, no diff entry needed.
-                            }
-                        }
-                    }
-                    (Some(_), None) => { // Remaining entries in iter1 are deletions
-                        let deleted_entry = iter1.next().unwrap();
-                        let val = load_value_repr(&deleted_entry.value, &store, &config).await?;
-                        diffs.push(DiffEntry::deletion(deleted_entry.key, val));
-                    }
-                    (None, Some(_)) => { // Remaining entries in iter2 are additions
-                        let added_entry = iter2.next().unwrap();
-                        let val = load_value_repr(&added_entry.value, &store, &config).await?;
-                        diffs.push(DiffEntry::addition(added_entry.key, val));
-                    }
-                    (None, None) => break, // Both iterators exhausted
+    diff_stream_with_options(left_root_hash, right_root_hash, store, config, node_cache, options)
+        .try_collect()
+        .await
+}
+
+/// Computes the differences between two trees for keys in `[start, end)`, only descending
+/// into subtrees whose key ranges intersect the interval. A `None` bound is unbounded.
+pub async fn diff_range<S: ChunkStore>(
+    left_root_hash: Option<Hash>,
+    right_root_hash: Option<Hash>,
+    store: Arc<S>,
+    config: TreeConfig,
+    start: Option<Key>,
+    end: Option<Key>,
+) -> Result<Vec<DiffEntry>> {
+    let options = DiffOptions { start, end, ..DiffOptions::default() };
+    diff_trees_with_options(left_root_hash, right_root_hash, store, config, Arc::new(NodeCache::default()), options).await
+}
+
+/// Returns the smallest key whose value differs between the two trees, or `None` if they
+/// hold the same entries. Only nodes along differing hashes up to that key are loaded.
+pub async fn first_difference<S: ChunkStore>(
+    left_root_hash: Option<Hash>,
+    right_root_hash: Option<Hash>,
+    store: Arc<S>,
+    config: TreeConfig,
+) -> Result<Option<Key>> {
+    first_difference_in_range(left_root_hash, right_root_hash, store, config, Arc::new(NodeCache::default()), None, None).await
+}
+
+/// Whether the two trees hold the same entries for every key in `[start, end)`. A `None`
+/// bound is unbounded.
+pub async fn range_equal<S: ChunkStore>(
+    left_root_hash: Option<Hash>,
+    right_root_hash: Option<Hash>,
+    store: Arc<S>,
+    config: TreeConfig,
+    start: Option<Key>,
+    end: Option<Key>,
+) -> Result<bool> {
+    first_difference_in_range(left_root_hash, right_root_hash, store, config, Arc::new(NodeCache::default()), start, end)
+        .await
+        .map(|key| key.is_none())
+}
+
+/// Same as `first_difference`, restricted to keys in `[start, end)` and with an explicit
+/// node cache. Values are compared by representation, as in `DiffMode::Keys`.
+pub async fn first_difference_in_range<S: ChunkStore>(
+    left_root_hash: Option<Hash>,
+    right_root_hash: Option<Hash>,
+    store: Arc<S>,
+    config: TreeConfig,
+    node_cache: Arc<NodeCache>,
+    start: Option<Key>,
+    end: Option<Key>,
+) -> Result<Option<Key>> {
+    // One change at a time, without prefetching, so the walk stops at the first divergent key.
+    let options = DiffOptions { mode: DiffMode::Keys, concurrency: 1, start, end };
+    let mut walker = DiffWalker::new(left_root_hash, Arc::clone(&store), right_root_hash, store, config, node_cache, options);
+    Ok(walker.next_changes().await?.into_iter().next().map(|change| change.key))
+}
+
+/// Counts the keys added, removed and modified between two trees without building the
+/// change set. Subtrees present on only one side are counted from the item counts stored
+/// in their parents, without visiting their leaves.
+pub async fn diff_summary<S: ChunkStore>(
+    left_root_hash: Option<Hash>,
+    right_root_hash: Option<Hash>,
+    store: Arc<S>,
+    config: TreeConfig,
+) -> Result<DiffSummary> {
+    diff_summary_with_options(left_root_hash, right_root_hash, store, config, Arc::new(NodeCache::default()), DiffSummaryOptions::default()).await
+}
+
+/// Same as `diff_summary`, with an explicit node cache and options.
+pub async fn diff_summary_with_options<S: ChunkStore>(
+    left_root_hash: Option<Hash>,
+    right_root_hash: Option<Hash>,
+    store: Arc<S>,
+    config: TreeConfig,
+    node_cache: Arc<NodeCache>,
+    options: DiffSummaryOptions,
+) -> Result<DiffSummary> {
+    let include_bytes = options.include_bytes;
+    let diff_options = DiffOptions { mode: DiffMode::Keys, concurrency: options.concurrency, start: options.start, end: options.end };
+    let mut walker = DiffWalker::new(left_root_hash, Arc::clone(&store), right_root_hash, Arc::clone(&store), config, node_cache, diff_options);
+    walker.count_subtrees = !include_bytes;
+    let mut summary = DiffSummary {
+        bytes_added: include_bytes.then_some(0),
+        bytes_removed: include_bytes.then_some(0),
+        ..DiffSummary::default()
+    };
+    loop {
+        let changes = walker.next_changes().await?;
+        if changes.is_empty() {
+            break;
+        }
+        for change in &changes {
+            match (&change.left, &change.right) {
+                (None, _) => summary.added += 1,
+                (_, None) => summary.removed += 1,
+                _ => summary.modified += 1,
+            }
+        }
+        if include_bytes {
+            let sizes = try_join_all(changes.iter().map(|change| async {
+                let (left, right) = tokio::try_join!(
+                    optional_value_size(change.left.as_ref(), &store),
+                    optional_value_size(change.right.as_ref(), &store)
+                )?;
+                Ok::<_, ProllyError>((left, right))
+            }))
+            .await?;
+            for (left, right) in sizes {
+                *summary.bytes_removed.get_or_insert(0) += left;
+                *summary.bytes_added.get_or_insert(0) += right;
+            }
+        }
+    }
+    summary.added += walker.added_in_subtrees;
+    summary.removed += walker.removed_in_subtrees;
+    Ok(summary)
+}
+
+/// Returns the key ranges in which two trees differ, in key order, together with the
+/// subtrees covering them on each side. Leaf nodes are never loaded: differing leaves are
+/// described by the hashes and item counts stored in their parents, and a subtree present on
+/// only one side is reported whole, at whatever height it was found.
+pub async fn changed_ranges<S: ChunkStore>(
+    left_root_hash: Option<Hash>,
+    right_root_hash: Option<Hash>,
+    store: Arc<S>,
+    config: TreeConfig,
+) -> Result<Vec<ChangedRange>> {
+    changed_ranges_with_cache(left_root_hash, right_root_hash, store, config, Arc::new(NodeCache::default())).await
+}
+
+/// Same as `changed_ranges`, loading nodes through the given decoded-node cache.
+pub async fn changed_ranges_with_cache<S: ChunkStore>(
+    left_root_hash: Option<Hash>,
+    right_root_hash: Option<Hash>,
+    store: Arc<S>,
+    config: TreeConfig,
+    node_cache: Arc<NodeCache>,
+) -> Result<Vec<ChangedRange>> {
+    let mut walker = DiffWalker::new(left_root_hash, Arc::clone(&store), right_root_hash, store, config, node_cache, DiffOptions::default());
+    let mut ranges = Vec::new();
+    while let Some(range) = walker.next_changed_range().await? {
+        ranges.push(range);
+    }
+    Ok(ranges)
+}
+
+/// Builds a `Patch` that turns the tree at `base_root_hash` into the tree at `target_root_hash`.
+///
+/// With `embed_values`, every new value is stored in the patch; otherwise chunked values are
+/// referenced by chunk hash and only inline values are embedded, which keeps the patch small
+/// when the receiving store already holds the value chunks.
+pub async fn diff_to_patch<S: ChunkStore>(
+    base_root_hash: Option<Hash>,
+    target_root_hash: Option<Hash>,
+    store: Arc<S>,
+    config: TreeConfig,
+    node_cache: Arc<NodeCache>,
+    embed_values: bool,
+) -> Result<Patch> {
+    let options = DiffOptions { mode: DiffMode::Keys, ..DiffOptions::default() };
+    let mut walker = DiffWalker::new(base_root_hash, Arc::clone(&store), target_root_hash, Arc::clone(&store), config.clone(), node_cache, options);
+    let mut records = Vec::new();
+    loop {
+        let changes = walker.next_changes().await?;
+        if changes.is_empty() {
+            break;
+        }
+        for change in changes {
+            let kind = match (&change.left, &change.right) {
+                (None, _) => DiffKind::Added,
+                (_, None) => DiffKind::Removed,
+                _ => DiffKind::Modified,
+            };
+            let value = match change.right {
+                None => None,
+                Some(ValueRepr::Inline(val)) => Some(PatchValue::Inline(val)),
+                Some(repr) if embed_values => Some(PatchValue::Inline(load_value_repr(&repr, &store, &config).await?)),
+                Some(ValueRepr::Chunked(data_hash)) => Some(PatchValue::ChunkRefs(vec![data_hash])),
+                Some(ValueRepr::ChunkedSequence { chunk_hashes, .. }) => Some(PatchValue::ChunkRefs(chunk_hashes)),
+            };
+            records.push(PatchRecord { key: change.key, kind, value });
+        }
+    }
+    let target_digest = entries_digest(target_root_hash, &store, &walker.node_cache).await?;
+    Ok(Patch { base_root: base_root_hash, target_root: target_root_hash, target_digest, records })
+}
+
+/// Hashes every key of the tree at `root_hash`, in order, together with the digest of its
+/// value (`ValueRepr::digest`). Unlike the root hash, the result does not depend on the
+/// tree's shape: two trees holding the same entries with the same value representations
+/// have the same digest. Every node of the tree is loaded, but no value chunk.
+pub async fn entries_digest<S: ChunkStore + ?Sized>(root_hash: Option<Hash>, store: &Arc<S>, node_cache: &NodeCache) -> Result<Hash> {
+    let mut hasher = blake3::Hasher::new();
+    let mut pending: Vec<Hash> = root_hash.into_iter().collect();
+    while let Some(node_hash) = pending.pop() {
+        match load_node_for_diff(&node_hash, store, node_cache).await?.as_ref() {
+            Node::Leaf { entries, .. } => {
+                for entry in entries {
+                    hasher.update(&(entry.key.len() as u64).to_le_bytes());
+                    hasher.update(&entry.key);
+                    hasher.update(&entry.value.digest());
                 }
             }
+            // Children are pushed in reverse so the leftmost one is popped first.
+            Node::Internal { children, .. } => pending.extend(children.iter().rev().map(|child| child.child_hash)),
         }
+    }
+    Ok(*hasher.finalize().as_bytes())
+}
 
-        // --- Case 2: Both are Internal Nodes ---
-        (Node::Internal { children: children1, level: l1 }, Node::Internal { children: children2, level: l2 }) => {
-             debug!("Diff: Comparing internal nodes {:?} and {:?}", h1, h2);
-             if l1 != l2 {
-                 // Levels mismatch - treat as full add/delete for simplicity
-                 // A more complex diff could try to correlate subtrees despite level change.
-                 warn!("Diff: Internal nodes {:?} and {:?} have different levels ({} vs {}), treating as full add/delete.", h1, h2, l1, l2);
-                 diffs.extend(generate_diffs_for_subtree(h1, &store, &config, false).await?); // false = deletions
-                 diffs.extend(generate_diffs_for_subtree(h2, &store, &config, true).await?); // true = additions
-                 return Ok(diffs);
-             }
-            
-            // Perform sorted merge diff on children entries based on boundary_key
-            let mut iter1 = children1.into_iter().peekable();
-            let mut iter2 = children2.into_iter().peekable();
-            let mut child_diff_futures = Vec::new(); // Collect futures for concurrent execution
-
-            loop {
-                 match (iter1.peek(), iter2.peek()) {
-                    (Some(c1), Some(c2)) => {
-                        use std::cmp::Ordering::*;
-                        match c1.boundary_key.cmp(&c2.boundary_key) {
-                             Less => { // c1 range ends first, unique to left side (deletion)
-                                 let entry1 = iter1.next().unwrap();
-                                 child_diff_futures.push(
-                                     recursive_diff(Some(entry1.child_hash), None, Arc::clone(&store), config.clone())
-                                 );
-                             }
-                             Greater => { // c2 range ends first, unique to right side (addition)
-                                 let entry2 = iter2.next().unwrap();
-                                 child_diff_futures.push(
-                                      recursive_diff(None, Some(entry2.child_hash), Arc::clone(&store), config.clone())
-                                 );
-                             }
-                             Equal => { // Boundary keys match, compare child hashes
-                                 let entry1 = iter1.next().unwrap();
-                                 let entry2 = iter2.next().unwrap();
-                                 // Only recurse if child hashes differ
-                                 if entry1.child_hash != entry2.child_hash {
-                                      child_diff_futures.push(
-                                          recursive_diff(Some(entry1.child_hash), Some(entry2.child_hash), Arc::clone(&store), config.clone())
-                                      );
-                                 }
-                                 // If child hashes match, the subtrees are identical, do nothing.
-                             }
-                        }
+/// Streams the differences between two trees in key order.
+///
+/// Nodes and values are only loaded as the stream is polled, so a caller that needs the
+/// first N changes can stop after N items without the rest of the diff being computed.
+pub fn diff_stream<S: ChunkStore>(
+    left_root_hash: Option<Hash>,
+    right_root_hash: Option<Hash>,
+    store: Arc<S>,
+    config: TreeConfig,
+) -> impl Stream<Item = Result<DiffEntry>> + Send {
+    diff_stream_with_options(left_root_hash, right_root_hash, store, config, Arc::new(NodeCache::default()), DiffOptions::default())
+}
+
+/// Same as `diff_stream`, with an explicit node cache and options.
+pub fn diff_stream_with_options<S: ChunkStore>(
+    left_root_hash: Option<Hash>,
+    right_root_hash: Option<Hash>,
+    store: Arc<S>,
+    config: TreeConfig,
+    node_cache: Arc<NodeCache>,
+    options: DiffOptions,
+) -> impl Stream<Item = Result<DiffEntry>> + Send {
+    diff_stream_across_stores(left_root_hash, Arc::clone(&store), right_root_hash, store, config, node_cache, options)
+}
+
+/// Computes the differences between a tree in `left_store` and a tree in `right_store`,
+/// for example a local tree and one loaded from a file or another replica, without copying
+/// chunks between the stores. Subtrees with equal hashes on both sides are still skipped,
+/// since content addressing makes them identical whichever store holds them.
+pub async fn diff_trees_across_stores<L: ChunkStore + ?Sized, R: ChunkStore + ?Sized>(
+    left_root_hash: Option<Hash>,
+    left_store: Arc<L>,
+    right_root_hash: Option<Hash>,
+    right_store: Arc<R>,
+    config: TreeConfig,
+    options: DiffOptions,
+) -> Result<Vec<DiffEntry>> {
+    diff_stream_across_stores(left_root_hash, left_store, right_root_hash, right_store, config, Arc::new(NodeCache::default()), options)
+        .try_collect()
+        .await
+}
+
+/// Streaming form of `diff_trees_across_stores`, with an explicit node cache. Nodes are
+/// cached by hash, so one cache can serve both stores.
+pub fn diff_stream_across_stores<L: ChunkStore + ?Sized, R: ChunkStore + ?Sized>(
+    left_root_hash: Option<Hash>,
+    left_store: Arc<L>,
+    right_root_hash: Option<Hash>,
+    right_store: Arc<R>,
+    config: TreeConfig,
+    node_cache: Arc<NodeCache>,
+    options: DiffOptions,
+) -> impl Stream<Item = Result<DiffEntry>> + Send {
+    let walker = DiffWalker::new(left_root_hash, left_store, right_root_hash, right_store, config, node_cache, options);
+    stream::try_unfold(walker, |mut walker| async move {
+        Ok(walker.next_entry().await?.map(|entry| (entry, walker)))
+    })
+}
+
+/// A change found by the walk, before its values are loaded.
+struct PendingChange {
+    key: Key,
+    left: Option<ValueRepr>,
+    right: Option<ValueRepr>,
+}
+
+/// A subtree that has not been loaded yet, with the key range its parent assigns to it.
+struct PendingSubtree {
+    hash: Hash,
+    /// Height of the subtree, or `None` for a root whose height is only known once loaded.
+    level: Option<u8>,
+    /// Every key in the subtree is greater than this bound (unbounded if `None`).
+    low: Option<Key>,
+    /// Every key in the subtree is less than or equal to this bound (unbounded if `None`).
+    high: Option<Key>,
+    /// Number of keys in the subtree, or `None` for a root.
+    num_items: Option<u64>,
+}
+
+impl PendingSubtree {
+    /// Whether the subtree may hold keys inside `range`.
+    fn intersects(&self, range: &KeyRange) -> bool {
+        let before_start = matches!((&self.high, &range.start), (Some(high), Some(start)) if high < start);
+        let after_end = matches!((&self.low, &range.end), (Some(low), Some(end)) if low >= end);
+        !before_start && !after_end
+    }
+
+    /// Whether every key in `self` sorts before every key in `other`.
+    fn precedes(&self, other: &PendingSubtree) -> bool {
+        matches!((&self.high, &other.low), (Some(high), Some(low)) if high <= low)
+    }
+
+    /// Whether `key` sorts before every key in the subtree.
+    fn is_after(&self, key: &Key) -> bool {
+        self.low.as_ref().is_some_and(|low| key <= low)
+    }
+
+    /// Whether `key` sorts after every key in the subtree.
+    fn is_before(&self, key: &Key) -> bool {
+        self.high.as_ref().is_some_and(|high| high < key)
+    }
+
+    /// Whether every key the subtree may hold lies inside `range`.
+    fn is_within(&self, range: &KeyRange) -> bool {
+        let after_start = range.start.as_ref().is_none_or(|start| self.low.as_ref().is_some_and(|low| low >= start));
+        let before_end = range.end.as_ref().is_none_or(|end| self.high.as_ref().is_some_and(|high| high < end));
+        after_start && before_end
+    }
+
+    /// Orders subtrees by height, treating a root of unknown height as the tallest.
+    fn height_rank(&self) -> u16 {
+        self.level.map_or(u16::MAX, u16::from)
+    }
+}
+
+/// The half-open key interval `[start, end)` a diff is restricted to.
+#[derive(Clone, Default)]
+struct KeyRange {
+    start: Option<Key>,
+    end: Option<Key>,
+}
+
+impl KeyRange {
+    fn contains(&self, key: &Key) -> bool {
+        self.start.as_ref().is_none_or(|start| key >= start) && self.end.as_ref().is_none_or(|end| key < end)
+    }
+}
+
+enum FrontierItem {
+    Subtree(PendingSubtree),
+    Entry(LeafEntry),
+}
+
+/// One side of the walk: the parts of a tree not compared yet, in key order. Items are
+/// stacked in reverse, so the last one covers the smallest keys. Items outside `range` are
+/// dropped as nodes are expanded.
+struct Frontier {
+    items: Vec<FrontierItem>,
+    range: KeyRange,
+}
+
+impl Frontier {
+    fn new(root_hash: Option<Hash>, range: KeyRange) -> Self {
+        let root = root_hash.map(|hash| FrontierItem::Subtree(PendingSubtree { hash, level: None, low: None, high: None, num_items: None }));
+        Frontier { items: root.into_iter().collect(), range }
+    }
+
+    fn pop_subtree(&mut self) -> PendingSubtree {
+        match self.items.pop() {
+            Some(FrontierItem::Subtree(subtree)) => subtree,
+            _ => unreachable!("diff walker expanded an item that is not a subtree"),
+        }
+    }
+
+    fn pop_entry(&mut self) -> LeafEntry {
+        match self.items.pop() {
+            Some(FrontierItem::Entry(entry)) => entry,
+            _ => unreachable!("diff walker consumed an item that is not an entry"),
+        }
+    }
+
+    /// The subtree at the front, for walks that never expand leaf nodes.
+    fn front_subtree(&self) -> Option<&PendingSubtree> {
+        self.items.last().map(|item| match item {
+            FrontierItem::Subtree(subtree) => subtree,
+            FrontierItem::Entry(_) => unreachable!("leaf entries in a walk that does not expand leaves"),
+        })
+    }
+
+    /// Replaces a loaded subtree by its children, or by its entries if it is a leaf. Fails for
+    /// an internal node at level 0, which can only come from corrupt or forged data.
+    fn push_node(&mut self, subtree: PendingSubtree, node: &Node) -> Result<()> {
+        match node {
+            Node::Leaf { entries, .. } => {
+                let range = &self.range;
+                self.items.extend(entries.iter().rev().filter(|entry| range.contains(&entry.key)).cloned().map(FrontierItem::Entry));
+            }
+            Node::Internal { children, level } => {
+                let child_level = level.checked_sub(1).ok_or_else(|| ProllyError::InvalidOperation(
+                    format!("Internal node {:?} is at level 0", subtree.hash),
+                ))?;
+                // A child covers (previous boundary, own boundary]; the last child inherits the
+                // parent's upper bound, since boundary keys are separators.
+                let last_idx = children.len().saturating_sub(1);
+                let mut low = subtree.low;
+                let mut expanded = Vec::with_capacity(children.len());
+                for (idx, child) in children.iter().enumerate() {
+                    let high = if idx == last_idx { subtree.high.clone() } else { Some(child.boundary_key.clone()) };
+                    let child_subtree = PendingSubtree {
+                        hash: child.child_hash,
+                        level: Some(child_level),
+                        low,
+                        high,
+                        num_items: Some(child.num_items_subtree),
+                    };
+                    low = Some(child.boundary_key.clone());
+                    if child_subtree.intersects(&self.range) {
+                        expanded.push(FrontierItem::Subtree(child_subtree));
                     }
-                    (Some(_), None) => { // Remaining children in iter1 are deletions
-                         let entry1 = iter1.next().unwrap();
-                         child_diff_futures.push(
-                             recursive_diff(Some(entry1.child_hash), None, Arc::clone(&store), config.clone())
-                         );
+                }
+                self.items.extend(expanded.into_iter().rev());
+            }
+        }
+        Ok(())
+    }
+
+    /// Hashes of the subtrees among the next `window` items.
+    fn upcoming_subtrees(&self, window: usize) -> impl Iterator<Item = &Hash> {
+        self.items.iter().rev().take(window).filter_map(|item| match item {
+            FrontierItem::Subtree(subtree) => Some(&subtree.hash),
+            FrontierItem::Entry(_) => None,
+        })
+    }
+}
+
+/// The next move of the walk, decided from the items at the front of both sides.
+enum Step {
+    TakeLeft,
+    TakeRight,
+    TakeBoth,
+    SkipBoth,
+    ExpandLeft,
+    ExpandRight,
+    ExpandBoth,
+    /// The left subtree has no counterpart on the right: count its keys as removed.
+    CountLeft,
+    /// The right subtree has no counterpart on the left: count its keys as added.
+    CountRight,
+}
+
+/// Incremental state of a diff between two roots.
+///
+/// Both trees are walked as a sorted merge of their frontiers. Subtrees at the front of both
+/// sides with equal hashes are skipped without being loaded; otherwise the taller one is
+/// expanded, using the key ranges implied by boundary keys to compare entries and subtrees of
+/// different heights. This keeps the change set minimal even when the two trees have
+/// different heights or split their keys differently.
+struct DiffWalker<L: ChunkStore + ?Sized, R: ChunkStore + ?Sized> {
+    /// Store holding the left tree's nodes and values.
+    left_store: Arc<L>,
+    /// Store holding the right tree's nodes and values; often the same store as `left_store`.
+    right_store: Arc<R>,
+    config: TreeConfig,
+    node_cache: Arc<NodeCache>,
+    mode: DiffMode,
+    concurrency: usize,
+    left: Frontier,
+    right: Frontier,
+    /// Changes whose values are loaded, in key order.
+    ready: VecDeque<DiffEntry>,
+    /// When set, subtrees found only on one side are counted from their item counts
+    /// (`CountLeft` / `CountRight`) instead of being expanded into changes.
+    count_subtrees: bool,
+    /// Keys counted as removed from whole subtrees, without being reported as changes.
+    removed_in_subtrees: u64,
+    /// Keys counted as added from whole subtrees, without being reported as changes.
+    added_in_subtrees: u64,
+}
+
+impl<L: ChunkStore + ?Sized, R: ChunkStore + ?Sized> DiffWalker<L, R> {
+    fn new(
+        left_root_hash: Option<Hash>,
+        left_store: Arc<L>,
+        right_root_hash: Option<Hash>,
+        right_store: Arc<R>,
+        config: TreeConfig,
+        node_cache: Arc<NodeCache>,
+        options: DiffOptions,
+    ) -> Self {
+        let range = KeyRange { start: options.start, end: options.end };
+        DiffWalker {
+            left_store,
+            right_store,
+            config,
+            node_cache,
+            mode: options.mode,
+            concurrency: options.concurrency.max(1),
+            left: Frontier::new(left_root_hash, range.clone()),
+            right: Frontier::new(right_root_hash, range),
+            ready: VecDeque::new(),
+            count_subtrees: false,
+            removed_in_subtrees: 0,
+            added_in_subtrees: 0,
+        }
+    }
+
+    async fn next_entry(&mut self) -> Result<Option<DiffEntry>> {
+        loop {
+            if let Some(entry) = self.ready.pop_front() {
+                return Ok(Some(entry));
+            }
+            let changes = self.next_changes().await?;
+            if changes.is_empty() {
+                return Ok(None);
+            }
+            if self.mode != DiffMode::Values {
+                let mode = self.mode;
+                self.ready.extend(changes.into_iter().map(|change| describe_change(change, mode)));
+                continue;
+            }
+            let (left_store, right_store, config) = (&self.left_store, &self.right_store, &self.config);
+            let entries = try_join_all(changes.into_iter().map(|change| materialize_change(change, left_store, right_store, config))).await?;
+            self.ready.extend(entries.into_iter().flatten());
+        }
+    }
+
+    /// Decides the next move from the items at the front of both sides; `None` once both are exhausted.
+    fn next_step(&self) -> Option<Step> {
+        use FrontierItem::{Entry, Subtree};
+        let step = match (self.left.items.last(), self.right.items.last()) {
+            (None, None) => return None,
+            (Some(Entry(_)), None) => Step::TakeLeft,
+            (Some(Subtree(t1)), None) => if self.is_countable(t1) { Step::CountLeft } else { Step::ExpandLeft },
+            (None, Some(Entry(_))) => Step::TakeRight,
+            (None, Some(Subtree(t2))) => if self.is_countable(t2) { Step::CountRight } else { Step::ExpandRight },
+            (Some(Entry(e1)), Some(Entry(e2))) => match e1.key.cmp(&e2.key) {
+                Ordering::Less => Step::TakeLeft,
+                Ordering::Greater => Step::TakeRight,
+                Ordering::Equal => Step::TakeBoth,
+            },
+            (Some(Entry(e1)), Some(Subtree(t2))) => {
+                if t2.is_after(&e1.key) {
+                    Step::TakeLeft
+                } else if t2.is_before(&e1.key) && self.is_countable(t2) {
+                    Step::CountRight
+                } else {
+                    Step::ExpandRight
+                }
+            }
+            (Some(Subtree(t1)), Some(Entry(e2))) => {
+                if t1.is_after(&e2.key) {
+                    Step::TakeRight
+                } else if t1.is_before(&e2.key) && self.is_countable(t1) {
+                    Step::CountLeft
+                } else {
+                    Step::ExpandLeft
+                }
+            }
+            (Some(Subtree(t1)), Some(Subtree(t2))) => {
+                if t1.hash == t2.hash {
+                    Step::SkipBoth
+                } else if t1.precedes(t2) {
+                    if self.is_countable(t1) { Step::CountLeft } else { Step::ExpandLeft }
+                } else if t2.precedes(t1) {
+                    if self.is_countable(t2) { Step::CountRight } else { Step::ExpandRight }
+                } else {
+                    match t1.height_rank().cmp(&t2.height_rank()) {
+                        Ordering::Greater => Step::ExpandLeft,
+                        Ordering::Less => Step::ExpandRight,
+                        Ordering::Equal => Step::ExpandBoth,
                     }
-                    (None, Some(_)) => { // Remaining children in iter2 are additions
-                         let entry2 = iter2.next().unwrap();
-                         child_diff_futures.push(
-                              recursive_diff(None, Some(entry2.child_hash), Arc::clone(&store), config.clone())
-                         );
+                }
+            }
+        };
+        Some(step)
+    }
+
+    /// Whether a subtree with no counterpart on the other side can be counted without
+    /// being loaded. Everything consumed so far on the other side sorts before the items
+    /// still pending on this side, so such a subtree is wholly added or removed.
+    fn is_countable(&self, subtree: &PendingSubtree) -> bool {
+        self.count_subtrees && subtree.num_items.is_some() && subtree.is_within(&self.left.range)
+    }
+
+    /// Walks until up to `concurrency` changes are found, in key order.
+    async fn next_changes(&mut self) -> Result<Vec<PendingChange>> {
+        let mut changes = Vec::new();
+        while changes.len() < self.concurrency {
+            let Some(step) = self.next_step() else { break };
+            match step {
+                Step::TakeLeft => { // Only on the left: deleted
+                    let entry = self.left.pop_entry();
+                    changes.push(PendingChange { key: entry.key, left: Some(entry.value), right: None });
+                }
+                Step::TakeRight => { // Only on the right: added
+                    let entry = self.right.pop_entry();
+                    changes.push(PendingChange { key: entry.key, left: None, right: Some(entry.value) });
+                }
+                Step::TakeBoth => {
+                    let entry1 = self.left.pop_entry();
+                    let entry2 = self.right.pop_entry();
+                    // Identical representations (including chunk hashes) mean identical values.
+                    if entry1.value != entry2.value {
+                        changes.push(PendingChange { key: entry1.key, left: Some(entry1.value), right: Some(entry2.value) });
                     }
-                    (None, None) => break, // Both iterators exhausted
-                 }
+                }
+                Step::SkipBoth => {
+                    // Equal hashes at the same position: identical subtrees.
+                    self.left.items.pop();
+                    self.right.items.pop();
+                }
+                Step::CountLeft => {
+                    self.removed_in_subtrees += self.left.pop_subtree().num_items.unwrap_or(0);
+                }
+                Step::CountRight => {
+                    self.added_in_subtrees += self.right.pop_subtree().num_items.unwrap_or(0);
+                }
+                Step::ExpandLeft => {
+                    self.prefetch().await;
+                    let subtree = self.left.pop_subtree();
+                    let node = load_node_for_diff(&subtree.hash, &self.left_store, &self.node_cache).await?;
+                    self.left.push_node(subtree, &node)?;
+                }
+                Step::ExpandRight => {
+                    self.prefetch().await;
+                    let subtree = self.right.pop_subtree();
+                    let node = load_node_for_diff(&subtree.hash, &self.right_store, &self.node_cache).await?;
+                    self.right.push_node(subtree, &node)?;
+                }
+                Step::ExpandBoth => {
+                    self.prefetch().await;
+                    let (subtree1, subtree2) = (self.left.pop_subtree(), self.right.pop_subtree());
+                    trace!("Diff: expanding {:?} and {:?}", subtree1.hash, subtree2.hash);
+                    let (node1, node2) = tokio::try_join!(
+                        load_node_for_diff(&subtree1.hash, &self.left_store, &self.node_cache),
+                        load_node_for_diff(&subtree2.hash, &self.right_store, &self.node_cache)
+                    )?;
+                    self.left.push_node(subtree1, &node1)?;
+                    self.right.push_node(subtree2, &node2)?;
+                }
             }
-            
-            // Execute all recursive calls concurrently and collect results
-             let results: Vec<Vec<DiffEntry>> = try_join_all(child_diff_futures).await?;
-             for result_vec in results {
-                 diffs.extend(result_vec);
-             }
         }
+        Ok(changes)
+    }
+
+    /// Walks to the next key range in which the trees differ, without loading leaf nodes.
+    ///
+    /// Differing subtrees are expanded as in `next_changes` until both fronts are leaves.
+    /// Leaves that overlap are then gathered into one range, growing it while the next item
+    /// on either side still starts inside it, since leaf boundaries need not line up between
+    /// the trees.
+    async fn next_changed_range(&mut self) -> Result<Option<ChangedRange>> {
+        let mut group: Option<ChangedRange> = None;
+        loop {
+            let (t1, t2) = (self.left.front_subtree(), self.right.front_subtree());
+            // Roots are loaded first so every subtree has a known height and item count.
+            if t1.is_some_and(|t| t.level.is_none()) {
+                self.expand_for_ranges(true).await?;
+                continue;
+            }
+            if t2.is_some_and(|t| t.level.is_none()) {
+                self.expand_for_ranges(false).await?;
+                continue;
+            }
+            if let (Some(t1), Some(t2)) = (t1, t2) && t1.hash == t2.hash {
+                self.left.items.pop();
+                self.right.items.pop();
+                continue;
+            }
+
+            if let Some(range) = group.as_mut() {
+                let starts_inside = |t: &PendingSubtree| match (&t.low, &range.high) {
+                    (Some(low), Some(high)) => low < high,
+                    _ => true,
+                };
+                let (left_inside, right_inside) = (t1.is_some_and(starts_inside), t2.is_some_and(starts_inside));
+                if left_inside && t1.is_some_and(|t| t.level != Some(0)) {
+                    self.expand_for_ranges(true).await?;
+                } else if right_inside && t2.is_some_and(|t| t.level != Some(0)) {
+                    self.expand_for_ranges(false).await?;
+                } else if left_inside {
+                    let subtree = self.left.pop_subtree();
+                    Self::extend_range(range, &subtree);
+                    range.left.push(Self::subtree_ref(&subtree));
+                } else if right_inside {
+                    let subtree = self.right.pop_subtree();
+                    Self::extend_range(range, &subtree);
+                    range.right.push(Self::subtree_ref(&subtree));
+                } else {
+                    return Ok(group);
+                }
+                continue;
+            }
 
-        // --- Case 3: One Leaf, One Internal ---
-        // Indicates significant structural change. Treat as delete left, add right.
-        (Node::Leaf { .. }, Node::Internal { .. }) | (Node::Internal { .. }, Node::Leaf { .. }) => {
-            warn!("Diff: Node types mismatch ({:?} vs {:?}), treating as full add/delete.", h1, h2);
-            // Run concurrently?
-             let (deletions, additions) = tokio::try_join!(
-                 generate_diffs_for_subtree(h1, &store, &config, false), // false = deletions
-                 generate_diffs_for_subtree(h2, &store, &config, true) // true = additions
-             )?;
-             diffs.extend(deletions);
-             diffs.extend(additions);
+            let (t1, t2) = match (t1, t2) {
+                (None, None) => return Ok(None),
+                (Some(_), None) => return Ok(Some(self.take_one_sided(true))),
+                (None, Some(_)) => return Ok(Some(self.take_one_sided(false))),
+                (Some(t1), Some(t2)) => (t1, t2),
+            };
+            if t1.precedes(t2) {
+                return Ok(Some(self.take_one_sided(true)));
+            }
+            if t2.precedes(t1) {
+                return Ok(Some(self.take_one_sided(false)));
+            }
+            match t1.height_rank().cmp(&t2.height_rank()) {
+                Ordering::Greater => self.expand_for_ranges(true).await?,
+                Ordering::Less => self.expand_for_ranges(false).await?,
+                Ordering::Equal if t1.level == Some(0) => {
+                    let (subtree1, subtree2) = (self.left.pop_subtree(), self.right.pop_subtree());
+                    let mut range = ChangedRange { low: subtree1.low.clone(), high: subtree1.high.clone(), left: vec![Self::subtree_ref(&subtree1)], right: vec![Self::subtree_ref(&subtree2)] };
+                    Self::extend_range(&mut range, &subtree2);
+                    group = Some(range);
+                }
+                Ordering::Equal => {
+                    self.expand_for_ranges(true).await?;
+                    self.expand_for_ranges(false).await?;
+                }
+            }
         }
     }
 
-    Ok(diffs)
+    /// Loads the subtree at the front of one side and replaces it by its children. A root
+    /// that turns out to be a leaf is pushed back as a leaf-level subtree instead.
+    async fn expand_for_ranges(&mut self, left: bool) -> Result<()> {
+        let subtree = if left { self.left.pop_subtree() } else { self.right.pop_subtree() };
+        let node = if left {
+            load_node_for_diff(&subtree.hash, &self.left_store, &self.node_cache).await?
+        } else {
+            load_node_for_diff(&subtree.hash, &self.right_store, &self.node_cache).await?
+        };
+        let frontier = if left { &mut self.left } else { &mut self.right };
+        match node.as_ref() {
+            Node::Leaf { entries, .. } => {
+                let num_items = Some(entries.len() as u64);
+                frontier.items.push(FrontierItem::Subtree(PendingSubtree { level: Some(0), num_items, ..subtree }));
+            }
+            node => frontier.push_node(subtree, node)?,
+        }
+        Ok(())
+    }
+
+    /// Reports the subtree at the front of one side, which has no counterpart on the other.
+    fn take_one_sided(&mut self, left: bool) -> ChangedRange {
+        let subtree = if left { self.left.pop_subtree() } else { self.right.pop_subtree() };
+        let subtree_ref = Self::subtree_ref(&subtree);
+        let (left_refs, right_refs) = if left { (vec![subtree_ref], Vec::new()) } else { (Vec::new(), vec![subtree_ref]) };
+        ChangedRange { low: subtree.low, high: subtree.high, left: left_refs, right: right_refs }
+    }
+
+    /// Widens `range` to cover `subtree`.
+    fn extend_range(range: &mut ChangedRange, subtree: &PendingSubtree) {
+        if range.low.is_some() && subtree.low.as_ref().is_none_or(|low| Some(low) < range.low.as_ref()) {
+            range.low = subtree.low.clone();
+        }
+        if range.high.is_some() && subtree.high.as_ref().is_none_or(|high| Some(high) > range.high.as_ref()) {
+            range.high = subtree.high.clone();
+        }
+    }
+
+    fn subtree_ref(subtree: &PendingSubtree) -> SubtreeRef {
+        SubtreeRef { hash: subtree.hash, level: subtree.level.unwrap_or(0), num_items: subtree.num_items.unwrap_or(0) }
+    }
+
+    /// Fetches up to `concurrency` subtrees at the front of both sides into the node cache,
+    /// with one `get_many` call per side, so the following expansions do not wait on the
+    /// store one by one.
+    async fn prefetch(&self) {
+        // When counting, the upcoming subtrees are often counted without ever being loaded.
+        if self.node_cache.config().max_nodes == 0 || self.count_subtrees {
+            return;
+        }
+        let left_upcoming: Vec<Hash> = self.left.upcoming_subtrees(self.concurrency).copied().collect();
+        let right_upcoming: Vec<Hash> = self.right.upcoming_subtrees(self.concurrency).copied().collect();
+        // Subtrees present on both sides are likely to be skipped rather than loaded.
+        let wanted = |hash: &&Hash, other_side: &[Hash]| !other_side.contains(hash) && !self.node_cache.contains(hash);
+        let mut left_fetches: Vec<Hash> = left_upcoming.iter().filter(|hash| wanted(hash, &right_upcoming)).copied().collect();
+        left_fetches.truncate(self.concurrency);
+        let mut right_fetches: Vec<Hash> = right_upcoming.iter().filter(|hash| wanted(hash, &left_upcoming)).copied().collect();
+        right_fetches.truncate(self.concurrency - left_fetches.len());
+        if left_fetches.len() + right_fetches.len() < 2 {
+            // A single fetch gains nothing over loading it when it is visited.
+            return;
+        }
+        debug!("Diff: prefetching {} nodes", left_fetches.len() + right_fetches.len());
+        // Failures are ignored here; they resurface when the walk reaches the node.
+        let _ = futures::join!(
+            self.node_cache.load_many(self.left_store.as_ref(), &left_fetches),
+            self.node_cache.load_many(self.right_store.as_ref(), &right_fetches)
+        );
+    }
 }
 
+/// Loads the values of a pending change. Returns `None` if both sides turn out to hold the
+/// same bytes under different representations.
+async fn materialize_change<L: ChunkStore + ?Sized, R: ChunkStore + ?Sized>(
+    change: PendingChange,
+    left_store: &Arc<L>,
+    right_store: &Arc<R>,
+    config: &TreeConfig,
+) -> Result<Option<DiffEntry>> {
+    let (left_value, right_value) = tokio::try_join!(
+        load_optional_value_repr(change.left.as_ref(), left_store, config),
+        load_optional_value_repr(change.right.as_ref(), right_store, config)
+    )?;
+    Ok(match (left_value, right_value) {
+        (None, Some(val)) => Some(DiffEntry::addition(change.key, val)),
+        (Some(val), None) => Some(DiffEntry::deletion(change.key, val)),
+        (Some(val1), Some(val2)) if val1 != val2 => Some(DiffEntry::modification(change.key, val1, val2)),
+        _ => None,
+    })
+}
+
+/// Builds the entry for a pending change without loading its values.
+fn describe_change(change: PendingChange, mode: DiffMode) -> DiffEntry {
+    let kind = match (&change.left, &change.right) {
+        (None, _) => DiffKind::Added,
+        (_, None) => DiffKind::Removed,
+        _ => DiffKind::Modified,
+    };
+    let with_digests = mode == DiffMode::Digests;
+    DiffEntry {
+        key: change.key,
+        kind,
+        left_value: None,
+        right_value: None,
+        left_digest: change.left.as_ref().filter(|_| with_digests).map(ValueRepr::digest),
+        right_digest: change.right.as_ref().filter(|_| with_digests).map(ValueRepr::digest),
+    }
+}
+
+async fn load_optional_value_repr<S: ChunkStore + ?Sized>(value_repr: Option<&ValueRepr>, store: &Arc<S>, config: &TreeConfig) -> Result<Option<Value>> {
+    match value_repr {
+        Some(repr) => load_value_repr(repr, store, config).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Size in bytes of the value behind `value_repr` (0 if absent). Only single-chunk values
+/// need a read, since chunk sequences record their total size.
+async fn optional_value_size<S: ChunkStore + ?Sized>(value_repr: Option<&ValueRepr>, store: &Arc<S>) -> Result<u64> {
+    match value_repr {
+        None => Ok(0),
+        Some(ValueRepr::Inline(val)) => Ok(val.len() as u64),
+        Some(ValueRepr::ChunkedSequence { total_size, .. }) => Ok(*total_size),
+        Some(ValueRepr::Chunked(data_hash)) => store.get(data_hash).await?
+            .map(|bytes| bytes.len() as u64)
+            .ok_or(ProllyError::ChunkNotFound(*data_hash)),
+    }
+}
 
 /// Helper function to load a node required for diffing.
-async fn load_node_for_diff<S: ChunkStore>(hash: &Hash, store: &Arc<S>) -> Result<Node> {
-    let bytes = store.get(hash).await?
-        .ok_or_else(|| {
-            error!("Diff failed: Chunk not found for hash {:?}", hash); // Log error if chunk is missing
-            ProllyError::ChunkNotFound(*hash)
-        })?;
-    Node::decode(&bytes).map_err(|e| {
-         error!("Diff failed: Failed to decode node for hash {:?}: {}", hash, e);
+async fn load_node_for_diff<S: ChunkStore + ?Sized>(hash: &Hash, store: &Arc<S>, node_cache: &NodeCache) -> Result<Arc<Node>> {
+    node_cache.load(store.as_ref(), hash).await.map_err(|e| {
+         error!("Diff failed: Failed to load node for hash {:?}: {}", hash, e);
          e
     })
 }
 
 
 /// Helper function to reconstruct a Value from its ValueRepr, loading chunks if needed.
-async fn load_value_repr<S: ChunkStore>(
+async fn load_value_repr<S: ChunkStore + ?Sized>(
     value_repr: &ValueRepr,
     store: &Arc<S>,
     _config: &TreeConfig, // Config might be needed later if value limits apply
@@ -252,9 +1084,9 @@ async fn load_value_repr<S: ChunkStore>(
         }
         ValueRepr::ChunkedSequence { chunk_hashes, total_size } => {
             let mut reconstructed_value = Vec::with_capacity(*total_size as usize);
-            for chunk_hash in chunk_hashes {
-                let chunk_bytes = store.get(chunk_hash).await?
-                    .ok_or_else(|| ProllyError::ChunkNotFound(*chunk_hash))?;
+            let chunks = check_batch_len("get_many", chunk_hashes.len(), store.get_many(chunk_hashes).await?)?;
+            for (chunk_hash, chunk_bytes) in chunk_hashes.iter().zip(chunks) {
+                let chunk_bytes = chunk_bytes.ok_or(ProllyError::ChunkNotFound(*chunk_hash))?;
                 reconstructed_value.extend_from_slice(&chunk_bytes);
             }
             if reconstructed_value.len() as u64 != *total_size {
@@ -265,40 +1097,3 @@ async fn load_value_repr<S: ChunkStore>(
         }
     }
 }
-
-
-/// Helper function to generate Add or Delete diff entries for an entire subtree.
-/// `is_addition`: true generates Additions, false generates Deletions.
-async fn generate_diffs_for_subtree<S: ChunkStore>(
-    root_hash: Hash,
-    store: &Arc<S>,
-    config: &TreeConfig,
-    is_addition: bool,
-) -> Result<Vec<DiffEntry>> {
-    let mut diffs = Vec::new();
-    let mut queue: VecDeque<Hash> = VecDeque::new(); // Use VecDeque for BFS traversal
-    queue.push_back(root_hash);
-
-    while let Some(hash) = queue.pop_front() {
-        let node = load_node_for_diff(&hash, store).await?;
-        match node {
-            Node::Leaf { entries, .. } => {
-                for entry in entries {
-                    // Must load value potentially from chunks
-                    let value = load_value_repr(&entry.value, store, config).await?; 
-                    if is_addition {
-                        diffs.push(DiffEntry::addition(entry.key, value));
-                    } else {
-                        diffs.push(DiffEntry::deletion(entry.key, value));
-                    }
-                }
-            }
-            Node::Internal { children, .. } => {
-                for child_entry in children {
-                    queue.push_back(child_entry.child_hash);
-                }
-            }
-        }
-    }
-    Ok(diffs)
-}
\ No newline at end of file
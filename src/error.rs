@@ -1,6 +1,7 @@
 // prolly-rust/src/error.rs
 
-use crate::common::Hash;
+use crate::common::{Hash, Key, Value};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Custom error type for the Prolly Tree library.
@@ -58,11 +59,28 @@ pub enum ProllyError {
 
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
+
+    #[error("Encryption error: {0}")]
+    EncryptionError(String),
+
+    #[error("Diff does not apply: {} conflicting key(s)", conflicts.len())]
+    DiffConflict { conflicts: Vec<DiffConflict> },
     // Add more specific error types as needed
 
     
 }
 
+/// An entry that `ProllyTree::apply_diff` could not apply in `ApplyMode::Strict` because the
+/// tree's current value differs from the entry's `left_value`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffConflict {
+    pub key: Key,
+    /// The value the entry expected (`None` for an addition).
+    pub expected: Option<Value>,
+    /// The value currently in the tree (`None` if the key is absent).
+    pub actual: Option<Value>,
+}
+
 /// Result type alias for Prolly Tree operations.
 pub type Result<T> = std::result::Result<T, ProllyError>;
 
@@ -1,13 +1,21 @@
 // packages/prolly-rust/src/gc.rs
 
-use std::collections::{HashSet, VecDeque};
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
 use log::trace; // Optional: for logging GC progress
+use serde::Serialize;
 
 use crate::common::Hash;
 use crate::error::{Result, ProllyError};
 use crate::node::definition::{Node, ValueRepr};
-use crate::store::ChunkStore;
+use crate::store::{ChunkStore, HashPager};
+use crate::store::chunk_store::check_batch_len;
+
+/// Number of hashes requested per `ChunkStore::hashes_page` call while sweeping.
+const SWEEP_PAGE_SIZE: usize = 1024;
+
+/// Maximum number of chunks fetched per `ChunkStore::get_many` call while marking.
+const MARK_BATCH_SIZE: usize = 256;
 
 pub struct GarbageCollector<S: ChunkStore> {
     store: Arc<S>,
@@ -26,132 +34,583 @@ impl<S: ChunkStore> GarbageCollector<S> {
     /// * `live_root_hashes`: A slice of `Hash` representing all currently active
     ///   root nodes. All chunks reachable from these roots will be preserved.
     ///
+    /// Chunks put after the collection starts are never deleted, even if they were already
+    /// in the store, and neither are chunks put by writes still in progress when it starts
+    /// (see `ChunkStore::begin_write`). The store must track write generations (see
+    /// `ChunkStore::write_generation`) and page through its hashes (`ChunkStore::hashes_page`),
+    /// so that only the live chunks and one page of hashes are held in memory.
+    ///
     /// # Returns
     /// `Ok(usize)` with the number of chunks collected (deleted), or an error.
     pub async fn collect(&self, live_root_hashes: &[Hash]) -> Result<usize> {
         trace!("Starting garbage collection. Live roots: {:?}", live_root_hashes);
 
-        let all_store_hashes = self.store.all_hashes().await?;
-        if all_store_hashes.is_empty() {
-            trace!("GC: Store is empty, nothing to collect.");
-            return Ok(0);
+        let generation = write_barrier(self.store.as_ref()).await?;
+        let live_chunks_set = self.mark(live_root_hashes).await?;
+
+        // Sweep phase
+        let mut collected_count = 0;
+        let mut pager = HashPager::new();
+        while let Some(dead_chunks_vec) = self.next_dead_page(&mut pager, &live_chunks_set).await? {
+            if !dead_chunks_vec.is_empty() {
+                trace!("GC: Sweeping {} dead chunks.", dead_chunks_vec.len());
+                collected_count += self.store.delete_batch_unless_put_since(&dead_chunks_vec, generation).await?;
+            }
         }
-        let all_store_hashes_set: HashSet<Hash> = all_store_hashes.into_iter().collect();
-        trace!("GC: Found {} total chunks in store before GC.", all_store_hashes_set.len());
+        Ok(collected_count)
+    }
 
+    /// Deletes `dead_chunks`, sparing those put since `generation` when there is one, and
+    /// returns the number deleted.
+    async fn sweep(&self, dead_chunks: &[Hash], generation: Option<u64>) -> Result<usize> {
+        match generation {
+            Some(generation) => self.store.delete_batch_unless_put_since(dead_chunks, generation).await,
+            None => {
+                self.store.delete_batch(dead_chunks).await?;
+                Ok(dead_chunks.len())
+            }
+        }
+    }
+
+    /// Returns the dead chunks of the next page of store hashes: those not in `live_chunks`.
+    /// `None` once the store is exhausted.
+    async fn next_dead_page(&self, pager: &mut HashPager, live_chunks: &HashSet<Hash>) -> Result<Option<Vec<Hash>>> {
+        let Some(page) = pager.next_page(self.store.as_ref(), SWEEP_PAGE_SIZE).await? else {
+            return Ok(None);
+        };
+        Ok(Some(page.into_iter().filter(|hash| !live_chunks.contains(hash)).collect()))
+    }
 
+    /// Mark phase: returns the chunks reachable from `live_root_hashes`. Roots and
+    /// references missing from the store are skipped.
+    pub(crate) async fn mark(&self, live_root_hashes: &[Hash]) -> Result<HashSet<Hash>> {
         let mut live_chunks_set = HashSet::new();
-        let mut queue = VecDeque::new();
-
-        // Initialize queue with live root hashes that are actually in the store
-        for root_hash in live_root_hashes {
-            if all_store_hashes_set.contains(root_hash) {
-                queue.push_back(*root_hash);
-            } else {
-                // This root_hash is not in the store, so it can't be a starting point for live chunks.
-                // It might be a hash of an empty tree (None) or an old, already GC'd root.
-                trace!("GC: Live root hash {:?} not found in store, skipping for marking.", root_hash);
+        let mut queue: VecDeque<Hash> = live_root_hashes.iter().copied().collect();
+        trace!("GC: Initializing mark phase with {} root(s) in queue.", queue.len());
+
+        while !queue.is_empty() {
+            let batch = next_mark_batch(&mut queue, &live_chunks_set, MARK_BATCH_SIZE);
+            let chunks = self.store.get_many(&batch).await.map_err(|e| {
+                ProllyError::StorageError(format!("GC: Error getting {} chunks from store: {}", batch.len(), e))
+            })?;
+            let chunks = check_batch_len("get_many", batch.len(), chunks)?;
+            for (hash_to_process, bytes) in batch.into_iter().zip(chunks) {
+                let Some(bytes) = bytes else {
+                    // A root of an empty or already collected tree, or a reference to a
+                    // chunk that was never stored; nothing to keep.
+                    trace!("GC: Chunk {:?} not found in store, skipping for marking.", hash_to_process);
+                    continue;
+                };
+                live_chunks_set.insert(hash_to_process);
+                trace!("GC: Marked chunk {:?} as live.", hash_to_process);
+
+                // Attempt to decode as a Node to find further references. If decode fails, it's
+                // a data chunk, with no further references to follow.
+                match Node::decode(&bytes) {
+                    Ok(node) => queue.extend(node_references(node)),
+                    Err(_) => {
+                        trace!("GC: Chunk {:?} is a data chunk (or failed to decode as node).", hash_to_process);
+                    }
+                }
             }
         }
-        
-        if queue.is_empty() && !live_root_hashes.is_empty() && live_root_hashes.iter().any(|h| all_store_hashes_set.contains(h) ) {
-            // This case should not happen if live_root_hashes contains valid, existing roots.
-            // If queue is empty but there were valid live roots, it's an issue.
-            // However, if all live_root_hashes were not in the store, queue would be empty, and that's fine (everything collected).
+        trace!("GC: Mark phase complete. {} chunks marked as live.", live_chunks_set.len());
+        Ok(live_chunks_set)
+    }
+
+    /// Dry run of `collect`: finds the chunks a collection from `live_root_hashes` would
+    /// delete, with their sizes and kinds, without deleting anything. Has the same
+    /// requirements on the store as `collect`.
+    pub async fn plan(&self, live_root_hashes: &[Hash]) -> Result<GcPlan> {
+        let generation = write_barrier(self.store.as_ref()).await?;
+        let mut plan = GcPlan { live_roots: live_root_hashes.to_vec(), generation: Some(generation), ..GcPlan::default() };
+        let live_chunks_set = self.mark(live_root_hashes).await?;
+        plan.live_chunks = live_chunks_set.len() as u64;
+
+        let mut dead_chunk_bytes = Vec::new();
+        let mut referenced_as_values = HashSet::new();
+        let mut pager = HashPager::new();
+        while let Some(dead_page) = self.next_dead_page(&mut pager, &live_chunks_set).await? {
+            let chunks = check_batch_len("get_many", dead_page.len(), self.store.get_many(&dead_page).await?)?;
+            for (store_hash, bytes) in dead_page.into_iter().zip(chunks) {
+                let Some(bytes) = bytes else { continue };
+                let decoded = Node::decode(&bytes).ok();
+                // Value chunks referenced by dead leaves are classified by that reference rather
+                // than by whether their bytes happen to decode as a node.
+                if let Some(Node::Leaf { entries, .. }) = &decoded {
+                    for entry in entries {
+                        match &entry.value {
+                            ValueRepr::Chunked(data_hash) => { referenced_as_values.insert(*data_hash); }
+                            ValueRepr::ChunkedSequence { chunk_hashes, .. } => referenced_as_values.extend(chunk_hashes.iter().copied()),
+                            ValueRepr::Inline(_) => {}
+                        }
+                    }
+                }
+                dead_chunk_bytes.push((store_hash, bytes.len() as u64, decoded.is_some()));
+            }
+        }
+        for (hash, size, decodes_as_node) in dead_chunk_bytes {
+            let kind = if decodes_as_node && !referenced_as_values.contains(&hash) { ChunkKind::Node } else { ChunkKind::Value };
+            match kind {
+                ChunkKind::Node => { plan.node_chunks += 1; plan.node_bytes += size; }
+                ChunkKind::Value => { plan.value_chunks += 1; plan.value_bytes += size; }
+            }
+            plan.dead_chunks.push(DeadChunk { hash, size, kind });
         }
+        plan.dead_chunks.sort_by_key(|chunk| chunk.hash);
+        trace!("GC: Plan found {} dead chunks ({} bytes).", plan.dead_chunks.len(), plan.reclaimable_bytes());
+        Ok(plan)
+    }
 
+    /// Deletes the dead chunks listed in `plan`, except those put since the plan was made.
+    /// The caller has to make sure that no root outside `plan.live_roots` has become live in
+    /// the meantime without its chunks being put again, e.g. by checking out an old root.
+    pub async fn collect_with(&self, plan: &GcPlan) -> Result<usize> {
+        let dead_chunks_vec: Vec<Hash> = plan.dead_chunks.iter().map(|chunk| chunk.hash).collect();
+        if dead_chunks_vec.is_empty() {
+            return Ok(0);
+        }
+        trace!("GC: Sweeping {} planned dead chunks.", dead_chunks_vec.len());
+        self.sweep(&dead_chunks_vec, plan.generation).await
+    }
+}
 
-        trace!("GC: Initializing mark phase with {} valid root(s) in queue.", queue.len());
+/// Whether a chunk holds an encoded node or (part of) a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkKind {
+    Node,
+    Value,
+}
+
+/// A chunk a planned collection would delete.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadChunk {
+    pub hash: Hash,
+    /// Size of the chunk in bytes.
+    pub size: u64,
+    pub kind: ChunkKind,
+}
+
+/// The outcome of a garbage collection dry run (`GarbageCollector::plan`), which
+/// `GarbageCollector::collect_with` can then execute.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcPlan {
+    /// The roots the plan was marked from.
+    pub live_roots: Vec<Hash>,
+    /// The store's write generation when the plan was made; chunks put since then are
+    /// spared by `collect_with`.
+    pub generation: Option<u64>,
+    /// Number of chunks reachable from `live_roots`.
+    pub live_chunks: u64,
+    /// The chunks to delete, sorted by hash.
+    pub dead_chunks: Vec<DeadChunk>,
+    pub node_chunks: u64,
+    pub node_bytes: u64,
+    pub value_chunks: u64,
+    pub value_bytes: u64,
+}
+
+impl GcPlan {
+    /// Total size of the chunks the plan would delete.
+    pub fn reclaimable_bytes(&self) -> u64 {
+        self.node_bytes + self.value_bytes
+    }
+}
+
+/// The phase an `IncrementalGc` is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GcPhase {
+    Mark,
+    Sweep,
+    Done,
+}
+
+/// How far an `IncrementalGc` has got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GcProgress {
+    pub phase: GcPhase,
+    /// Chunks found reachable so far.
+    pub marked_chunks: u64,
+    /// Chunks queued for marking.
+    pub pending_chunks: u64,
+    /// Chunks the sweep has examined.
+    pub swept_chunks: u64,
+    /// Chunks the sweep will examine in total; 0 until the sweep starts. Estimated from the
+    /// store's chunk count when the sweep starts, since chunks can be added or removed while
+    /// it runs, and exact once the collection is done.
+    pub sweep_total: u64,
+    /// Chunks deleted so far.
+    pub collected_chunks: u64,
+}
+
+/// A mark-and-sweep collection split into steps of bounded work, so that it can be run a
+/// little at a time between other operations on the store.
+///
+/// Chunks put after the collection starts are never deleted, so the store must track write
+/// generations. Roots that become live while the collection runs (other than by writes,
+/// whose new chunks are protected anyway) must be passed to `add_roots`. The step that
+/// starts the sweep also calls `ChunkStore::chunk_count`, which should be cheap.
+#[derive(Debug)]
+pub struct IncrementalGc<S: ChunkStore + ?Sized> {
+    store: Arc<S>,
+    generation: u64,
+    phase: GcPhase,
+    live_chunks: HashSet<Hash>,
+    mark_queue: VecDeque<Hash>,
+    sweep_pager: HashPager,
+    /// Hashes of the current page the sweep has not examined yet.
+    sweep_buffer: VecDeque<Hash>,
+    /// The store's chunk count when the sweep started.
+    sweep_estimate: Option<u64>,
+    swept_chunks: u64,
+    collected_chunks: u64,
+}
+
+impl<S: ChunkStore + ?Sized> IncrementalGc<S> {
+    /// Starts a collection keeping the chunks reachable from `live_root_hashes`. Fails if
+    /// the store does not track write generations.
+    pub async fn start(store: Arc<S>, live_root_hashes: &[Hash]) -> Result<Self> {
+        let generation = write_barrier(store.as_ref()).await?;
+        let mut gc = IncrementalGc {
+            store,
+            generation,
+            phase: GcPhase::Mark,
+            live_chunks: HashSet::new(),
+            mark_queue: VecDeque::new(),
+            sweep_pager: HashPager::new(),
+            sweep_buffer: VecDeque::new(),
+            sweep_estimate: None,
+            swept_chunks: 0,
+            collected_chunks: 0,
+        };
+        gc.add_roots(live_root_hashes);
+        Ok(gc)
+    }
 
-        // Mark phase
-        while let Some(hash_to_process) = queue.pop_front() {
-            if live_chunks_set.contains(&hash_to_process) {
-                continue; // Already processed
+    /// Keeps the chunks reachable from `roots` as well. Unmarked roots added during the sweep
+    /// send the collection back to marking; the sweep then resumes where it stopped. Since
+    /// the unmarked chunks of such roots are marked first, a collection whose roots keep
+    /// changing only makes progress if each step's budget exceeds the chunks written between
+    /// steps.
+    pub fn add_roots(&mut self, roots: &[Hash]) {
+        if self.phase == GcPhase::Done {
+            return;
+        }
+        for root in roots {
+            if !self.live_chunks.contains(root) {
+                self.mark_queue.push_back(*root);
+                self.phase = GcPhase::Mark;
             }
+        }
+    }
 
-            // Check if the hash to process actually exists in the store before attempting to get it.
-            // This check is somewhat redundant due to `all_store_hashes_set` usage but good for safety.
-            if !all_store_hashes_set.contains(&hash_to_process) {
-                trace!("GC: Hash {:?} from queue not found in store hashes set. Skipping.", hash_to_process);
-                continue;
+    /// Processes up to `max_chunks` chunks (at least one) of marking or sweeping and
+    /// returns the progress made so far.
+    pub async fn step(&mut self, max_chunks: usize) -> Result<GcProgress> {
+        let mut budget = max_chunks.max(1);
+        while budget > 0 && self.phase != GcPhase::Done {
+            match self.phase {
+                GcPhase::Mark => budget -= self.mark_step(budget).await?,
+                GcPhase::Sweep => budget -= self.sweep_step(budget).await?,
+                GcPhase::Done => {}
             }
+        }
+        Ok(self.progress())
+    }
 
-            live_chunks_set.insert(hash_to_process);
-            trace!("GC: Marked chunk {:?} as live.", hash_to_process);
-
-            match self.store.get(&hash_to_process).await {
-                Ok(Some(bytes)) => {
-                    // Attempt to decode as a Node to find further references
-                    if let Ok(node) = Node::decode(&bytes) {
-                        match node {
-                            Node::Leaf { entries, .. } => {
-                                for entry in entries {
-                                    match &entry.value {
-                                        ValueRepr::Chunked(data_hash) => {
-                                            if all_store_hashes_set.contains(data_hash) && !live_chunks_set.contains(data_hash) {
-                                                queue.push_back(*data_hash);
-                                            }
-                                        }
-                                        ValueRepr::ChunkedSequence { chunk_hashes, .. } => {
-                                            for data_hash in chunk_hashes {
-                                                if all_store_hashes_set.contains(data_hash) && !live_chunks_set.contains(data_hash) {
-                                                    queue.push_back(*data_hash);
-                                                }
-                                            }
-                                        }
-                                        ValueRepr::Inline(_) => {}
-                                    }
-                                }
-                            }
-                            Node::Internal { children, .. } => {
-                                for child_entry in children {
-                                    if all_store_hashes_set.contains(&child_entry.child_hash) && !live_chunks_set.contains(&child_entry.child_hash) {
-                                        queue.push_back(child_entry.child_hash);
-                                    }
-                                }
-                            }
-                        }
-                    } else {
-                        // If decode fails, it's a data chunk. It's already marked live.
-                        // No further references to follow from a raw data chunk.
-                        trace!("GC: Chunk {:?} is a data chunk (or failed to decode as node).", hash_to_process);
+    pub fn progress(&self) -> GcProgress {
+        GcProgress {
+            phase: self.phase,
+            marked_chunks: self.live_chunks.len() as u64,
+            pending_chunks: self.mark_queue.len() as u64,
+            swept_chunks: self.swept_chunks,
+            sweep_total: self.sweep_total(),
+            collected_chunks: self.collected_chunks,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.phase == GcPhase::Done
+    }
+
+    fn sweep_total(&self) -> u64 {
+        match self.phase {
+            GcPhase::Done => self.swept_chunks,
+            _ => self.sweep_estimate.map_or(0, |estimate| estimate.max(self.swept_chunks)),
+        }
+    }
+
+    /// Marks up to `budget` queued chunks and returns the work done. Chunks already marked
+    /// cost nothing, so re-marking a root that mostly shares chunks with marked ones is cheap.
+    /// Like `collect`, references to chunks missing from the store are ignored.
+    async fn mark_step(&mut self, budget: usize) -> Result<usize> {
+        let mut work = 0;
+        while work < budget {
+            if self.mark_queue.is_empty() {
+                self.start_sweep().await?;
+                return Ok(work.max(1));
+            }
+            let batch = next_mark_batch(&mut self.mark_queue, &self.live_chunks, (budget - work).min(MARK_BATCH_SIZE));
+            if batch.is_empty() {
+                continue;
+            }
+            work += batch.len();
+            let chunks = check_batch_len("get_many", batch.len(), self.store.get_many(&batch).await?)?;
+            for (hash, bytes) in batch.into_iter().zip(chunks) {
+                let Some(bytes) = bytes else { continue };
+                self.live_chunks.insert(hash);
+                // Chunks that do not decode as nodes are value chunks, with no references.
+                if let Ok(node) = Node::decode(&bytes) {
+                    for reference in node_references(node) {
+                        self.queue_unmarked(reference);
                     }
                 }
-                Ok(None) => {
-                    // This should ideally not happen if `all_store_hashes_set` is accurate and
-                    // chunks aren't deleted concurrently during GC.
-                    return Err(ProllyError::InternalError(format!(
-                        "GC: Chunk {:?} was expected in store but not found during get.",
-                        hash_to_process
-                    )));
-                }
-                Err(e) => {
-                    // Handle error from store.get if necessary
-                    return Err(ProllyError::StorageError(format!(
-                        "GC: Error getting chunk {:?} from store: {}",
-                        hash_to_process, e
-                    )));
-                }
             }
         }
-        trace!("GC: Mark phase complete. {} chunks marked as live.", live_chunks_set.len());
+        Ok(work)
+    }
 
-        // Sweep phase
-        let mut dead_chunks_vec = Vec::new();
-        for store_hash in all_store_hashes_set { // Iterate over original set of all hashes
-            if !live_chunks_set.contains(&store_hash) {
-                dead_chunks_vec.push(store_hash);
-            }
+    fn queue_unmarked(&mut self, hash: Hash) {
+        if !self.live_chunks.contains(&hash) {
+            self.mark_queue.push_back(hash);
         }
+    }
 
-        let collected_count = dead_chunks_vec.len();
-        if !dead_chunks_vec.is_empty() {
-            trace!("GC: Sweeping {} dead chunks.", collected_count);
-            self.store.delete_batch(&dead_chunks_vec).await?;
-        } else {
-            trace!("GC: No dead chunks to sweep.");
+    async fn start_sweep(&mut self) -> Result<()> {
+        trace!("GC: Incremental mark complete. {} chunks marked as live.", self.live_chunks.len());
+        self.phase = GcPhase::Sweep;
+        // A sweep resumed after marking added roots keeps its first estimate.
+        if self.sweep_estimate.is_none() {
+            self.sweep_estimate = Some(self.store.chunk_count().await?);
         }
+        Ok(())
+    }
 
-        Ok(collected_count)
+    /// Sweeps up to `budget` chunks of the store and returns the work done. Pages are no
+    /// larger than the budget, so a step reads no more hashes from the store than it sweeps.
+    async fn sweep_step(&mut self, budget: usize) -> Result<usize> {
+        if self.sweep_buffer.is_empty()
+            && let Some(page) = self.sweep_pager.next_page(self.store.as_ref(), budget.min(SWEEP_PAGE_SIZE)).await?
+        {
+            self.sweep_buffer.extend(page);
+        }
+        let work = budget.min(self.sweep_buffer.len());
+        let dead_chunks: Vec<Hash> = self.sweep_buffer
+            .drain(..work)
+            .filter(|hash| !self.live_chunks.contains(hash))
+            .collect();
+        if !dead_chunks.is_empty() {
+            self.collected_chunks += self.store.delete_batch_unless_put_since(&dead_chunks, self.generation).await? as u64;
+        }
+        self.swept_chunks += work as u64;
+        if self.sweep_buffer.is_empty() && self.sweep_pager.is_done() {
+            trace!("GC: Incremental sweep complete. {} chunks collected.", self.collected_chunks);
+            self.phase = GcPhase::Done;
+        }
+        Ok(work.max(1))
+    }
+}
+
+/// Counted pins on roots that garbage collection must keep alive, such as the base root of
+/// a write in progress. Clones share the same pins.
+#[derive(Debug, Clone, Default)]
+pub struct RootPins {
+    counts: Arc<Mutex<HashMap<Hash, usize>>>,
+}
+
+impl RootPins {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a lease on `root`. Each `pin` must be matched by an `unpin`.
+    pub fn pin(&self, root: Hash) {
+        *self.lock_counts().entry(root).or_insert(0) += 1;
     }
-}
\ No newline at end of file
+
+    /// Releases a lease taken by `pin`. Fails if `root` is not pinned.
+    pub fn unpin(&self, root: &Hash) -> Result<()> {
+        let mut counts = self.lock_counts();
+        match counts.get_mut(root) {
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => { counts.remove(root); }
+            None => return Err(ProllyError::InvalidOperation(format!("Root {:?} is not pinned", root))),
+        }
+        Ok(())
+    }
+
+    /// The roots with at least one lease.
+    pub fn pinned_roots(&self) -> Vec<Hash> {
+        self.lock_counts().keys().copied().collect()
+    }
+
+    fn lock_counts(&self) -> std::sync::MutexGuard<'_, HashMap<Hash, usize>> {
+        // The map stays consistent even if a holder panicked.
+        self.counts.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Starts a write barrier on `store` for a collection, failing if the store does not track
+/// write generations: without one, chunks put while marking would look dead to the sweep.
+async fn write_barrier<S: ChunkStore + ?Sized>(store: &S) -> Result<u64> {
+    store.write_generation().await?.ok_or_else(|| ProllyError::InvalidOperation(
+        "Garbage collection needs a store that tracks write generations".to_string(),
+    ))
+}
+
+/// Pops up to `limit` distinct queued chunks that are not in `marked` yet. Chunks already
+/// marked are dropped from the queue without counting towards the limit.
+fn next_mark_batch(queue: &mut VecDeque<Hash>, marked: &HashSet<Hash>, limit: usize) -> Vec<Hash> {
+    let mut batch = Vec::new();
+    let mut in_batch = HashSet::new();
+    while batch.len() < limit {
+        let Some(hash) = queue.pop_front() else { break };
+        if !marked.contains(&hash) && in_batch.insert(hash) {
+            batch.push(hash);
+        }
+    }
+    batch
+}
+
+/// The chunks a node references: its children, or the value chunks of its entries.
+fn node_references(node: Node) -> Vec<Hash> {
+    match node {
+        Node::Leaf { entries, .. } => entries
+            .into_iter()
+            .flat_map(|entry| match entry.value {
+                ValueRepr::Chunked(data_hash) => vec![data_hash],
+                ValueRepr::ChunkedSequence { chunk_hashes, .. } => chunk_hashes,
+                ValueRepr::Inline(_) => Vec::new(),
+            })
+            .collect(),
+        Node::Internal { children, .. } => children.iter().map(|child| child.child_hash).collect(),
+    }
+}
+
+/// Copies the chunks reachable from `roots` from `source` into `destination` and returns it.
+/// Unlike `GarbageCollector`, this needs no `all_hashes` or `delete_batch` support from the
+/// source, which is left untouched: dropping it afterwards releases the garbage along with
+/// any capacity its maps kept after earlier deletions. Every chunk reachable from `roots`
+/// must be in `source`, and `destination` must store chunks under the same hashes.
+pub async fn compact_into<S: ChunkStore + ?Sized, D: ChunkStore>(source: &S, destination: D, roots: &[Hash]) -> Result<D> {
+    let live_chunks = reachable_chunks(source, roots, None).await?;
+    trace!("Compaction: copying {} live chunks.", live_chunks.len());
+    for hash in live_chunks {
+        let bytes = source.get(&hash).await?.ok_or(ProllyError::ChunkNotFound(hash))?;
+        let stored_hash = destination.put(bytes).await?;
+        if stored_hash != hash {
+            return Err(ProllyError::ChecksumMismatch {
+                context: format!("Compacted chunk {:?} was stored under hash {:?}", hash, stored_hash),
+            });
+        }
+    }
+    Ok(destination)
+}
+
+/// Returns every chunk reachable from `roots` (nodes and the value chunks their leaves
+/// reference), not descending into any chunk in `stop_at`, which is also left out of the
+/// result. Unlike the collector's mark phase, chunks are told apart by where they are
+/// referenced from rather than by trying to decode them, and a missing chunk is an error.
+pub async fn reachable_chunks<S: ChunkStore + ?Sized>(
+    store: &S,
+    roots: &[Hash],
+    stop_at: Option<&HashSet<Hash>>,
+) -> Result<HashSet<Hash>> {
+    let is_stop = |hash: &Hash| stop_at.is_some_and(|stop| stop.contains(hash));
+    let mut reachable = HashSet::new();
+    // (hash, whether the chunk is a node whose references must be followed)
+    let mut queue: VecDeque<(Hash, bool)> = roots.iter().filter(|hash| !is_stop(hash)).map(|hash| (*hash, true)).collect();
+    while let Some((hash, is_node)) = queue.pop_front() {
+        if !reachable.insert(hash) {
+            continue;
+        }
+        if !is_node {
+            if !store.exists(&hash).await? {
+                return Err(ProllyError::ChunkNotFound(hash));
+            }
+            continue;
+        }
+        let bytes = store.get(&hash).await?.ok_or(ProllyError::ChunkNotFound(hash))?;
+        match Node::decode(&bytes)? {
+            Node::Internal { children, .. } => {
+                queue.extend(children.iter().map(|child| (child.child_hash, true)).filter(|(child_hash, _)| !is_stop(child_hash)));
+            }
+            Node::Leaf { entries, .. } => {
+                for entry in &entries {
+                    let data_hashes: &[Hash] = match &entry.value {
+                        ValueRepr::Inline(_) => &[],
+                        ValueRepr::Chunked(data_hash) => std::slice::from_ref(data_hash),
+                        ValueRepr::ChunkedSequence { chunk_hashes, .. } => chunk_hashes,
+                    };
+                    queue.extend(data_hashes.iter().filter(|data_hash| !is_stop(data_hash)).map(|data_hash| (*data_hash, false)));
+                }
+            }
+        }
+    }
+    trace!("Reachability: {} chunks reachable from {} root(s)", reachable.len(), roots.len());
+    Ok(reachable)
+}
+
+/// Returns the chunks reachable from `target_root` that a store holding `base_root` is
+/// missing, walking both trees together one level at a time, highest first. A node found at
+/// the same level on both sides is shared along with its subtree and walked on neither, so
+/// only the parts of the base that differ from the target are read. A chunk the walk reaches
+/// on the target side only may still be somewhere under a shared subtree of the base, so the
+/// result can hold a few chunks the base has, but never misses one it lacks.
+pub async fn delta_chunks<S: ChunkStore + ?Sized>(
+    store: &S,
+    base_root: Option<Hash>,
+    target_root: Option<Hash>,
+) -> Result<HashSet<Hash>> {
+    let mut delta = HashSet::new();
+    if base_root == target_root {
+        return Ok(delta);
+    }
+    // The nodes still to walk on each side, by level.
+    let mut base_levels: BTreeMap<u8, HashSet<Hash>> = BTreeMap::new();
+    let mut target_levels: BTreeMap<u8, HashSet<Hash>> = BTreeMap::new();
+    for (root, levels) in [(base_root, &mut base_levels), (target_root, &mut target_levels)] {
+        if let Some(root) = root {
+            let level = load_node(store, &root).await?.level();
+            levels.entry(level).or_default().insert(root);
+        }
+    }
+    let mut base_values = HashSet::new();
+    let mut target_values = HashSet::new();
+    while let Some(level) = base_levels.keys().chain(target_levels.keys()).max().copied() {
+        let base_here = base_levels.remove(&level).unwrap_or_default();
+        let target_here = target_levels.remove(&level).unwrap_or_default();
+        for (hashes, levels, values) in [
+            (target_here.difference(&base_here), &mut target_levels, &mut target_values),
+            (base_here.difference(&target_here), &mut base_levels, &mut base_values),
+        ] {
+            for hash in hashes {
+                match load_node(store, hash).await? {
+                    Node::Internal { children, .. } => {
+                        let child_level = level.checked_sub(1).ok_or_else(|| ProllyError::InvalidOperation(
+                            format!("Internal node {:?} is at level 0", hash),
+                        ))?;
+                        levels.entry(child_level).or_default().extend(children.iter().map(|child| child.child_hash));
+                    }
+                    leaf => values.extend(node_references(leaf)),
+                }
+            }
+        }
+        delta.extend(target_here.difference(&base_here).copied());
+    }
+    for hash in target_values.difference(&base_values) {
+        if !store.exists(hash).await? {
+            return Err(ProllyError::ChunkNotFound(*hash));
+        }
+        delta.insert(*hash);
+    }
+    trace!("Delta: {} chunks reachable from the target and not from the base", delta.len());
+    Ok(delta)
+}
+
+async fn load_node<S: ChunkStore + ?Sized>(store: &S, hash: &Hash) -> Result<Node> {
+    let bytes = store.get(hash).await?.ok_or(ProllyError::ChunkNotFound(*hash))?;
+    Node::decode(&bytes)
+}
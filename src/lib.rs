@@ -4,6 +4,8 @@
 
 use std::sync::Arc;
 use std::cell::RefCell;
+use std::pin::Pin;
+use futures::stream::{Stream, StreamExt};
 use wasm_bindgen::prelude::*;
 use js_sys::{Promise, Uint8Array as JsUint8Array, Map as JsMap, Object, Reflect, Array as JsArray, Function as JsFunction};
 
@@ -18,10 +20,13 @@ pub mod chunk;
 pub mod tree;
 pub mod diff;
 pub mod gc;
+pub mod sync;
 pub mod wasm_bridge;
 
-use crate::store::file_io_v2::{write_prly_tree_v2, read_prly_tree_v2};
+use crate::store::file_io_v2::{write_prly_tree_v2, read_prly_tree_v2, write_prly_delta_v2, read_prly_delta_v2};
+use crate::store::patch_format::{write_patch_v1, read_patch_v1};
 use crate::store::ChunkStore;
+use crate::node::cache::{NodeCache, NodeCacheConfig};
 
 // Corrected use statements
 use crate::tree::types as core_tree_types; // For core ScanArgs and ScanPage
@@ -32,12 +37,212 @@ use crate::store::InMemoryStore;
 use crate::common::{TreeConfig, Key, Value, Hash};
 use crate::error::ProllyError;
 use crate::diff::DiffEntry as CoreDiffEntry; // Alias to avoid conflict if DiffEntry is also defined in TS section
+use crate::diff::{ApplyMode, ChangedRange, DiffKind, DiffOptions, DiffSummaryOptions};
+use crate::sync::SyncOptions;
+use crate::gc::{ChunkKind, DeadChunk, GcPlan};
 
 // Helper to convert ProllyError to JsValue for Promise rejections
 fn prolly_error_to_jsvalue(err: ProllyError) -> JsValue {
     JsValue::from_str(&format!("ProllyError: {}", err))
 }
 
+// Helper to convert a core DiffEntry into a plain JS `DiffEntry` object
+fn diff_entry_to_js(entry: &CoreDiffEntry) -> JsValue {
+    let obj = Object::new();
+    let kind = match entry.kind { DiffKind::Added => "added", DiffKind::Removed => "removed", DiffKind::Modified => "modified" };
+    Reflect::set(&obj, &"key".into(), &JsUint8Array::from(entry.key.as_slice()).into()).unwrap_or_default();
+    Reflect::set(&obj, &"kind".into(), &kind.into()).unwrap_or_default();
+    if let Some(ref lv)=entry.left_value { Reflect::set(&obj, &"leftValue".into(), &JsUint8Array::from(lv.as_slice()).into()).unwrap_or_default(); }
+    if let Some(ref rv)=entry.right_value { Reflect::set(&obj, &"rightValue".into(), &JsUint8Array::from(rv.as_slice()).into()).unwrap_or_default(); }
+    if let Some(ref ld)=entry.left_digest { Reflect::set(&obj, &"leftDigest".into(), &JsUint8Array::from(&ld[..]).into()).unwrap_or_default(); }
+    if let Some(ref rd)=entry.right_digest { Reflect::set(&obj, &"rightDigest".into(), &JsUint8Array::from(&rd[..]).into()).unwrap_or_default(); }
+    JsValue::from(obj)
+}
+
+// Helper to convert a core ChangedRange into a plain JS `ChangedRange` object
+fn changed_range_to_js(range: &ChangedRange) -> JsValue {
+    let obj = Object::new();
+    let bound_to_js = |bound: &Option<Vec<u8>>| bound.as_ref().map_or(JsValue::NULL, |k| JsUint8Array::from(k.as_slice()).into());
+    let subtrees_to_js = |subtrees: &[crate::diff::SubtreeRef]| -> JsValue {
+        subtrees.iter().map(|subtree| {
+            let subtree_obj = Object::new();
+            Reflect::set(&subtree_obj, &"hash".into(), &JsUint8Array::from(&subtree.hash[..]).into()).unwrap_or_default();
+            Reflect::set(&subtree_obj, &"level".into(), &JsValue::from(subtree.level)).unwrap_or_default();
+            Reflect::set(&subtree_obj, &"numItems".into(), &JsValue::from(subtree.num_items as f64)).unwrap_or_default();
+            JsValue::from(subtree_obj)
+        }).collect::<JsArray>().into()
+    };
+    Reflect::set(&obj, &"low".into(), &bound_to_js(&range.low)).unwrap_or_default();
+    Reflect::set(&obj, &"high".into(), &bound_to_js(&range.high)).unwrap_or_default();
+    Reflect::set(&obj, &"left".into(), &subtrees_to_js(&range.left)).unwrap_or_default();
+    Reflect::set(&obj, &"right".into(), &subtrees_to_js(&range.right)).unwrap_or_default();
+    JsValue::from(obj)
+}
+
+// Helper to convert a GcPlan into the `GcPlan` object documented in prolly_tree_types.ts.
+fn gc_plan_to_js(plan: &GcPlan) -> JsValue {
+    let obj = Object::new();
+    let live_roots: JsArray = plan.live_roots.iter().map(|h| JsValue::from(JsUint8Array::from(&h[..]))).collect();
+    let dead_chunks: JsArray = plan.dead_chunks.iter().map(|chunk| {
+        let chunk_obj = Object::new();
+        let kind = match chunk.kind { ChunkKind::Node => "node", ChunkKind::Value => "value" };
+        Reflect::set(&chunk_obj, &"hash".into(), &JsUint8Array::from(&chunk.hash[..]).into()).unwrap_or_default();
+        Reflect::set(&chunk_obj, &"size".into(), &JsValue::from(chunk.size as f64)).unwrap_or_default();
+        Reflect::set(&chunk_obj, &"kind".into(), &JsValue::from_str(kind)).unwrap_or_default();
+        JsValue::from(chunk_obj)
+    }).collect();
+    Reflect::set(&obj, &"liveRoots".into(), &live_roots.into()).unwrap_or_default();
+    Reflect::set(&obj, &"generation".into(), &plan.generation.map_or(JsValue::NULL, |g| JsValue::from(g as f64))).unwrap_or_default();
+    Reflect::set(&obj, &"liveChunks".into(), &JsValue::from(plan.live_chunks as f64)).unwrap_or_default();
+    Reflect::set(&obj, &"deadChunks".into(), &dead_chunks.into()).unwrap_or_default();
+    Reflect::set(&obj, &"nodeChunks".into(), &JsValue::from(plan.node_chunks as f64)).unwrap_or_default();
+    Reflect::set(&obj, &"nodeBytes".into(), &JsValue::from(plan.node_bytes as f64)).unwrap_or_default();
+    Reflect::set(&obj, &"valueChunks".into(), &JsValue::from(plan.value_chunks as f64)).unwrap_or_default();
+    Reflect::set(&obj, &"valueBytes".into(), &JsValue::from(plan.value_bytes as f64)).unwrap_or_default();
+    JsValue::from(obj)
+}
+
+// Helper to convert a `GcPlan` object returned by `planGc` back into a GcPlan. The totals
+// are recomputed from `deadChunks`.
+fn gc_plan_from_js(plan_js: &JsValue) -> Result<GcPlan, JsValue> {
+    let field = |obj: &JsValue, name: &str| Reflect::get(obj, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED);
+    let mut plan = GcPlan { live_roots: parse_hash_array(&field(plan_js, "liveRoots"), "executeGcPlan (liveRoots)")?, ..GcPlan::default() };
+    plan.generation = field(plan_js, "generation").as_f64().map(|g| g as u64);
+    plan.live_chunks = field(plan_js, "liveChunks").as_f64().unwrap_or(0.0) as u64;
+    let dead_chunks_js = field(plan_js, "deadChunks");
+    let dead_chunks_array = dead_chunks_js.dyn_ref::<JsArray>()
+        .ok_or_else(|| JsValue::from_str("executeGcPlan expects a plan with a deadChunks array."))?;
+    for i in 0..dead_chunks_array.length() {
+        let chunk_js = dead_chunks_array.get(i);
+        let hash_js = field(&chunk_js, "hash");
+        let hash = match hash_js.dyn_ref::<JsUint8Array>() {
+            Some(js_u8) if js_u8.length() == 32 => { let mut h = [0u8; 32]; js_u8.copy_to(&mut h); h }
+            _ => return Err(JsValue::from_str(&format!("Dead chunk at index {} has no valid hash.", i))),
+        };
+        let size = field(&chunk_js, "size").as_f64().unwrap_or(0.0) as u64;
+        let kind = match field(&chunk_js, "kind").as_string().as_deref() {
+            Some("node") => { plan.node_chunks += 1; plan.node_bytes += size; ChunkKind::Node }
+            Some("value") => { plan.value_chunks += 1; plan.value_bytes += size; ChunkKind::Value }
+            _ => return Err(JsValue::from_str(&format!("Dead chunk at index {} has an invalid kind.", i))),
+        };
+        plan.dead_chunks.push(DeadChunk { hash, size, kind });
+    }
+    Ok(plan)
+}
+
+// Helper to convert a JS `DiffEntry` object back into a core DiffEntry. `kind` is inferred
+// from which values are present when it is missing.
+fn diff_entry_from_js(entry_js: &JsValue, index: u32) -> Result<CoreDiffEntry, JsValue> {
+    let bytes_field = |name: &str| -> Result<Option<Vec<u8>>, JsValue> {
+        let field = Reflect::get(entry_js, &name.into()).unwrap_or(JsValue::UNDEFINED);
+        if field.is_undefined() || field.is_null() { return Ok(None); }
+        field.dyn_ref::<JsUint8Array>().map(|arr| Some(arr.to_vec()))
+            .ok_or_else(|| JsValue::from_str(&format!("Diff entry at index {} has a non-Uint8Array {}.", index, name)))
+    };
+    let key = bytes_field("key")?.ok_or_else(|| JsValue::from_str(&format!("Diff entry at index {} has no key.", index)))?;
+    let left_value = bytes_field("leftValue")?;
+    let right_value = bytes_field("rightValue")?;
+    let kind = match Reflect::get(entry_js, &"kind".into()).ok().and_then(|k| k.as_string()).as_deref() {
+        Some("added") => DiffKind::Added,
+        Some("removed") => DiffKind::Removed,
+        Some("modified") => DiffKind::Modified,
+        Some(other) => return Err(JsValue::from_str(&format!("Diff entry at index {} has an invalid kind: {}.", index, other))),
+        None => match (&left_value, &right_value) {
+            (None, _) => DiffKind::Added,
+            (_, None) => DiffKind::Removed,
+            _ => DiffKind::Modified,
+        },
+    };
+    Ok(CoreDiffEntry { key, kind, left_value, right_value, left_digest: None, right_digest: None })
+}
+
+// Like `prolly_error_to_jsvalue`, but rejects with an Error carrying a `conflicts` array
+// ({ key, expected, actual }) when a strict `applyDiff` finds conflicting keys.
+fn apply_diff_error_to_jsvalue(err: ProllyError) -> JsValue {
+    let ProllyError::DiffConflict { ref conflicts } = err else { return prolly_error_to_jsvalue(err) };
+    let error = js_sys::Error::new(&format!("ProllyError: {}", err));
+    let conflicts_js: JsArray = conflicts.iter().map(|conflict| {
+        let obj = Object::new();
+        let bytes_or_null = |v: &Option<Vec<u8>>| v.as_ref().map_or(JsValue::NULL, |b| JsUint8Array::from(b.as_slice()).into());
+        Reflect::set(&obj, &"key".into(), &JsUint8Array::from(conflict.key.as_slice()).into()).unwrap_or_default();
+        Reflect::set(&obj, &"expected".into(), &bytes_or_null(&conflict.expected)).unwrap_or_default();
+        Reflect::set(&obj, &"actual".into(), &bytes_or_null(&conflict.actual)).unwrap_or_default();
+        JsValue::from(obj)
+    }).collect();
+    Reflect::set(&error, &"conflicts".into(), &conflicts_js).unwrap_or_default();
+    error.into()
+}
+
+// Helper to read the `nodeCacheMaxNodes` / `nodeCacheMaxBytes` limits of a `TreeConfigOptions`
+// object. They size the tree's decoded-node cache and are not part of the persisted TreeConfig,
+// so a missing limit keeps its default and `None` means neither was given.
+fn parse_node_cache_config(options_js: &JsValue) -> Result<Option<NodeCacheConfig>, JsValue> {
+    let limit = |name: &str| -> Result<Option<usize>, JsValue> {
+        let value = Reflect::get(options_js, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED);
+        if value.is_undefined() || value.is_null() {
+            return Ok(None);
+        }
+        match value.as_f64() {
+            Some(n) if n >= 0.0 && n.fract() == 0.0 => Ok(Some(n as usize)),
+            _ => Err(JsValue::from_str(&format!("`{}` must be a non-negative integer.", name))),
+        }
+    };
+    let (max_nodes, max_bytes) = (limit("nodeCacheMaxNodes")?, limit("nodeCacheMaxBytes")?);
+    if max_nodes.is_none() && max_bytes.is_none() {
+        return Ok(None);
+    }
+    let defaults = NodeCacheConfig::default();
+    Ok(Some(NodeCacheConfig {
+        max_nodes: max_nodes.unwrap_or(defaults.max_nodes),
+        max_bytes: max_bytes.unwrap_or(defaults.max_bytes),
+    }))
+}
+
+// Helper to parse an optional `DiffOptions` object
+fn parse_diff_options(options: Option<DiffOptionsJs>) -> Result<DiffOptions, JsValue> {
+    match options {
+        Some(options_js) if !options_js.is_undefined() && !options_js.is_null() => {
+            serde_wasm_bindgen::from_value(options_js.into())
+                .map_err(|e| JsValue::from_str(&format!("Failed to parse DiffOptions: {}", e)))
+        }
+        _ => Ok(DiffOptions::default()),
+    }
+}
+
+// Helper to parse an optional 32-byte root hash argument
+fn parse_optional_root_hash(h_js: Option<JsUint8Array>, name: &str) -> Result<Option<Hash>, JsValue> {
+    match h_js {
+        Some(js_arr) if js_arr.length()==32 => { let mut h=[0u8;32]; js_arr.copy_to(&mut h); Ok(Some(h)) }
+        Some(js_arr) => Err(JsValue::from_str(&format!("Invalid {} length: {}, must be 32 bytes or null.", name, js_arr.length()))),
+        None => Ok(None),
+    }
+}
+
+// Helper to parse a required 32-byte root hash argument
+fn parse_root_hash(h_js: &JsUint8Array, name: &str) -> Result<Hash, JsValue> {
+    if h_js.length() != 32 {
+        return Err(JsValue::from_str(&format!("Invalid {} length: {}, must be 32 bytes.", name, h_js.length())));
+    }
+    let mut h = [0u8; 32];
+    h_js.copy_to(&mut h);
+    Ok(h)
+}
+
+// Helper to parse a JS array of 32-byte Uint8Array hashes
+fn parse_hash_array(hashes_js: &JsValue, fn_name: &str) -> Result<Vec<Hash>, JsValue> {
+    let hashes_array = hashes_js.dyn_ref::<JsArray>()
+        .ok_or_else(|| JsValue::from_str(&format!("{} expects an array.", fn_name)))?;
+    let mut hashes: Vec<Hash> = Vec::with_capacity(hashes_array.length() as usize);
+    for i in 0..hashes_array.length() {
+        match hashes_array.get(i).dyn_ref::<JsUint8Array>() {
+            Some(js_u8) if js_u8.length()==32 => { let mut h=[0u8;32]; js_u8.copy_to(&mut h); hashes.push(h); }
+            Some(js_u8) => return Err(JsValue::from_str(&format!("Hash at index {} invalid length: {}.",i,js_u8.length()))),
+            _ => return Err(JsValue::from_str(&format!("Hash at index {} not Uint8Array.",i))),
+        }
+    }
+    Ok(hashes)
+}
+
 // --- TypeScript Custom Section ---
 // Import the TypeScript definitions from an external file.
 #[wasm_bindgen(typescript_custom_section)]
@@ -49,6 +254,20 @@ extern "C" {
     #[wasm_bindgen(typescript_type = "TreeConfigOptions")]
     pub type TreeConfigOptions;
 
+    #[wasm_bindgen(typescript_type = "DiffOptions")]
+    pub type DiffOptionsJs;
+    #[wasm_bindgen(typescript_type = "DiffSummaryOptions")]
+    pub type DiffSummaryOptionsJs;
+    #[wasm_bindgen(typescript_type = "DiffEntry[]")]
+    pub type DiffEntryArray; // Used for apply_diff's entries parameter
+    #[wasm_bindgen(typescript_type = "ApplyMode")]
+    pub type ApplyModeJs;
+    #[wasm_bindgen(typescript_type = "SyncOptions")]
+    pub type SyncOptionsJs;
+    #[wasm_bindgen(typescript_type = "ExportPatchOptions")]
+    pub type ExportPatchOptions;
+    #[wasm_bindgen(typescript_type = "GcPlan")]
+    pub type GcPlanJs;
     #[wasm_bindgen(typescript_type = "ScanOptions")]
     pub type ScanOptions;
 
@@ -88,10 +307,50 @@ extern "C" {
     pub type PromiseExportChunksFnReturn;
     #[wasm_bindgen(typescript_type = "Promise<DiffRootsFnReturn>")]
     pub type PromiseDiffRootsFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<DiffStreamFnReturn>")]
+    pub type PromiseDiffStreamFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<DiffStreamNextReturn>")]
+    pub type PromiseDiffStreamNextReturn;
+    #[wasm_bindgen(typescript_type = "Promise<DiffStreamNextPageReturn>")]
+    pub type PromiseDiffStreamNextPageReturn;
+    #[wasm_bindgen(typescript_type = "Promise<DiffSummaryFnReturn>")]
+    pub type PromiseDiffSummaryFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<ApplyDiffFnReturn>")]
+    pub type PromiseApplyDiffFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<ExportPatchFnReturn>")]
+    pub type PromiseExportPatchFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<ImportPatchFnReturn>")]
+    pub type PromiseImportPatchFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<FirstDifferenceFnReturn>")]
+    pub type PromiseFirstDifferenceFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<RangeEqualFnReturn>")]
+    pub type PromiseRangeEqualFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<ChangedRangesFnReturn>")]
+    pub type PromiseChangedRangesFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<SyncToFnReturn>")]
+    pub type PromiseSyncToFnReturn;
     #[wasm_bindgen(typescript_type = "Promise<TriggerGcFnReturn>")]
     pub type PromiseTriggerGcFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<PinRootFnReturn>")]
+    pub type PromisePinRootFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<UnpinRootFnReturn>")]
+    pub type PromiseUnpinRootFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<GetPinnedRootsFnReturn>")]
+    pub type PromiseGetPinnedRootsFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<CompactFnReturn>")]
+    pub type PromiseCompactFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<StartIncrementalGcFnReturn>")]
+    pub type PromiseStartIncrementalGcFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<GcStepFnReturn>")]
+    pub type PromiseGcStepFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<PlanGcFnReturn>")]
+    pub type PromisePlanGcFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<ExecuteGcPlanFnReturn>")]
+    pub type PromiseExecuteGcPlanFnReturn;
     #[wasm_bindgen(typescript_type = "Promise<GetTreeConfigFnReturn>")]
     pub type PromiseGetTreeConfigFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<GetNodeCacheStatsFnReturn>")]
+    pub type PromiseGetNodeCacheStatsFnReturn;
 
     #[wasm_bindgen(typescript_type = "Promise<ScanItemsFnReturn>")]
     pub type PromiseScanItemsFnReturn;
@@ -109,6 +368,10 @@ extern "C" {
     pub type PromiseExportTreeToFileFnReturn;
     #[wasm_bindgen(typescript_type = "Promise<LoadTreeFromFileBytesFnReturn>")]
     pub type PromiseLoadTreeFromFileBytesFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<ExportDeltaFnReturn>")]
+    pub type PromiseExportDeltaFnReturn;
+    #[wasm_bindgen(typescript_type = "Promise<ImportDeltaFnReturn>")]
+    pub type PromiseImportDeltaFnReturn;
 
     // Type for the new synchronous method's return value
     #[wasm_bindgen(typescript_type = "GetSyncFnReturn")]
@@ -171,14 +434,64 @@ impl PTreeCursor {
     }
 }
 
+type CoreDiffStream = Pin<Box<dyn Stream<Item = crate::error::Result<CoreDiffEntry>> + Send>>;
+
+/// Lazily computed diff between two roots, consumed entry by entry or page by page.
+#[wasm_bindgen(js_name = "PDiffStream")]
+#[derive(Clone)]
+pub struct PDiffStream {
+    inner: Arc<tokio::sync::Mutex<CoreDiffStream>>,
+}
+
+#[wasm_bindgen]
+impl PDiffStream {
+    #[wasm_bindgen]
+    pub fn next(&self) -> PromiseDiffStreamNextReturn {
+        let stream_clone = Arc::clone(&self.inner);
+        let future = async move {
+            let next_entry = stream_clone.lock().await.next().await.transpose().map_err(prolly_error_to_jsvalue)?;
+            let result_obj = Object::new();
+            Reflect::set(&result_obj, &JsValue::from_str("done"), &JsValue::from_bool(next_entry.is_none()))
+                .map_err(|e| prolly_error_to_jsvalue(ProllyError::JsBindingError(format!("Failed to set 'done': {:?}", e))))?;
+            if let Some(entry) = next_entry {
+                Reflect::set(&result_obj, &JsValue::from_str("value"), &diff_entry_to_js(&entry))
+                    .map_err(|e| prolly_error_to_jsvalue(ProllyError::JsBindingError(format!("Failed to set 'value': {:?}", e))))?;
+            }
+            Ok(JsValue::from(result_obj))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Resolves to up to `limit` further entries; an empty array means the diff is exhausted.
+    #[wasm_bindgen(js_name = nextPage)]
+    pub fn next_page(&self, limit: u32) -> PromiseDiffStreamNextPageReturn {
+        let stream_clone = Arc::clone(&self.inner);
+        let future = async move {
+            let mut stream_guard = stream_clone.lock().await;
+            let page = JsArray::new();
+            while page.length() < limit {
+                match stream_guard.next().await {
+                    Some(Ok(entry)) => { page.push(&diff_entry_to_js(&entry)); }
+                    Some(Err(e)) => return Err(prolly_error_to_jsvalue(e)),
+                    None => break,
+                }
+            }
+            Ok(JsValue::from(page))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+}
+
 #[wasm_bindgen]
 impl PTree {
     #[wasm_bindgen(constructor)]
     pub fn new(options: Option<TreeConfigOptions>) -> Result<PTree, JsValue> {
+        let mut node_cache_config = None;
         let config: TreeConfig = if let Some(options_js) = options {
             if options_js.is_undefined() || options_js.is_null() {
                 TreeConfig::default()
             } else {
+                node_cache_config = parse_node_cache_config(options_js.as_ref())?;
                 serde_wasm_bindgen::from_value(options_js.into())
                     .map_err(|e| JsValue::from_str(&format!("Failed to parse TreeConfigOptions: {}", e)))?
             }
@@ -191,11 +504,19 @@ impl PTree {
         }
 
         let store = Arc::new(InMemoryStore::new());
-        let tree = ProllyTree::new(store, config);
-        Ok(Self {
+        let mut tree = ProllyTree::new(store, config);
+        if let Some(node_cache_config) = node_cache_config {
+            tree.set_node_cache(Arc::new(NodeCache::new(node_cache_config)));
+        }
+        Ok(Self::from_tree(tree))
+    }
+
+    // Wraps a core tree in a PTree with no change listeners.
+    fn from_tree(tree: ProllyTree<InMemoryStore>) -> PTree {
+        PTree {
             inner: Arc::new(tokio::sync::Mutex::new(tree)),
-            listeners: Arc::new(RefCell::new(Vec::new())), 
-        })
+            listeners: Arc::new(RefCell::new(Vec::new())),
+        }
     }
 
     #[wasm_bindgen(js_name = "onChange")]
@@ -255,6 +576,11 @@ impl PTree {
             Err(e) => return Promise::reject(&e),
         };
         
+        let node_cache_config = match tree_config_options.as_ref().map(|options| parse_node_cache_config(options.as_ref())) {
+            Some(Err(e)) => return Promise::reject(&e),
+            Some(Ok(node_cache_config)) => node_cache_config,
+            None => None,
+        };
         let config: TreeConfig = match tree_config_options {
             Some(options_js_val) => {
                 // options_js_val is of type TreeConfigOptions (which is a JsValue facade)
@@ -291,8 +617,11 @@ impl PTree {
             };
 
             tree_result
-                .map(|tree| {
-                    PTree { inner: Arc::new(tokio::sync::Mutex::new(tree)), listeners: Arc::new(RefCell::new(Vec::new())) }.into()
+                .map(|mut tree| {
+                    if let Some(node_cache_config) = node_cache_config {
+                        tree.set_node_cache(Arc::new(NodeCache::new(node_cache_config)));
+                    }
+                    PTree::from_tree(tree).into()
                 })
                 .map_err(prolly_error_to_jsvalue)
         };
@@ -550,50 +879,294 @@ impl PTree {
     }
 
     #[wasm_bindgen(js_name = diffRoots)]
-    pub fn diff_roots( &self, root_h_left_js: Option<JsUint8Array>, root_h_right_js: Option<JsUint8Array>) -> PromiseDiffRootsFnReturn {
-        let parse_hash = |h_js: Option<JsUint8Array>, name: &str| -> Result<Option<Hash>, JsValue> {
-            match h_js {
-                Some(js_arr) if js_arr.length()==32 => { let mut h=[0u8;32]; js_arr.copy_to(&mut h); Ok(Some(h)) }
-                Some(js_arr) => Err(JsValue::from_str(&format!("Invalid {} length: {}, must be 32 bytes or null.", name, js_arr.length()))),
-                None => Ok(None),
-            }
-        };
-        let (h_left, h_right) = match (parse_hash(root_h_left_js,"root_hash_left"), parse_hash(root_h_right_js,"root_hash_right")) {
+    pub fn diff_roots( &self, root_h_left_js: Option<JsUint8Array>, root_h_right_js: Option<JsUint8Array>, options: Option<DiffOptionsJs>) -> PromiseDiffRootsFnReturn {
+        let (h_left, h_right) = match (parse_optional_root_hash(root_h_left_js,"root_hash_left"), parse_optional_root_hash(root_h_right_js,"root_hash_right")) {
             (Ok(l), Ok(r)) => (l,r),
             (Err(e), _) | (_, Err(e)) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
         };
+        let diff_options = match parse_diff_options(options) {
+            Ok(o) => o,
+            Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
         let tree_clone = Arc::clone(&self.inner);
         let future = async move {
-            let config_clone = tree_clone.lock().await.config.clone(); // Clone config
-            let store_clone = Arc::clone(&tree_clone.lock().await.store); // Clone store Arc
-            crate::diff::diff_trees(h_left, h_right, store_clone, config_clone).await // Use CoreDiffEntry
+            let (config_clone, store_clone, cache_clone) = {
+                let tree = tree_clone.lock().await;
+                (tree.config.clone(), Arc::clone(&tree.store), Arc::clone(tree.node_cache()))
+            };
+            crate::diff::diff_trees_with_options(h_left, h_right, store_clone, config_clone, cache_clone, diff_options).await // Use CoreDiffEntry
                 .map(|diff_entries: Vec<CoreDiffEntry>| { 
-                    diff_entries.iter().map(|entry| {
-                        let obj = Object::new();
-                        Reflect::set(&obj, &"key".into(), &JsUint8Array::from(entry.key.as_slice()).into()).unwrap_or_default();
-                        if let Some(ref lv)=entry.left_value { Reflect::set(&obj, &"leftValue".into(), &JsUint8Array::from(lv.as_slice()).into()).unwrap_or_default(); }
-                        if let Some(ref rv)=entry.right_value { Reflect::set(&obj, &"rightValue".into(), &JsUint8Array::from(rv.as_slice()).into()).unwrap_or_default(); }
-                        JsValue::from(obj)
-                    }).collect::<JsArray>().into()
+                    diff_entries.iter().map(diff_entry_to_js).collect::<JsArray>().into()
                 }).map_err(prolly_error_to_jsvalue)
         };
         wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
     }
 
-    #[wasm_bindgen(js_name = triggerGc)]
-    pub fn trigger_gc(&self, live_hashes_js_val: &JsValue) -> PromiseTriggerGcFnReturn {
-        let live_hashes_array = match live_hashes_js_val.dyn_ref::<JsArray>() {
-            Some(arr) => arr,
-            None => return wasm_bindgen::JsValue::from(Promise::reject(&JsValue::from_str("triggerGc expects an array."))).into(),
-        };
-        let mut live_hashes_rust: Vec<Hash> = Vec::new();
-        for i in 0..live_hashes_array.length() {
-            match live_hashes_array.get(i).dyn_ref::<JsUint8Array>() {
-                Some(js_u8) if js_u8.length()==32 => { let mut h=[0u8;32]; js_u8.copy_to(&mut h); live_hashes_rust.push(h); }
-                Some(js_u8) => return wasm_bindgen::JsValue::from(Promise::reject(&JsValue::from_str(&format!("Hash at index {} invalid length: {}.",i,js_u8.length())))).into(),
-                _ => return wasm_bindgen::JsValue::from(Promise::reject(&JsValue::from_str(&format!("Hash at index {} not Uint8Array.",i)))).into(),
+    /// Diffs this tree's current root (left) against another tree's current root (right),
+    /// reading each side from its own store, e.g. a tree loaded with `loadTreeFromFileBytes`.
+    #[wasm_bindgen(js_name = diffTree)]
+    pub fn diff_tree(&self, other: &PTree, options: Option<DiffOptionsJs>) -> PromiseDiffRootsFnReturn {
+        let diff_options = match parse_diff_options(options) {
+            Ok(o) => o,
+            Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let other_clone = Arc::clone(&other.inner);
+        let future = async move {
+            let other_root_and_store = if Arc::ptr_eq(&tree_clone, &other_clone) {
+                None
+            } else {
+                let other_tree = other_clone.lock().await;
+                Some((other_tree.get_root_hash(), Arc::clone(&other_tree.store)))
+            };
+            let tree = tree_clone.lock().await;
+            let (other_root, other_store) = other_root_and_store.unwrap_or_else(|| (tree.get_root_hash(), Arc::clone(&tree.store)));
+            tree.diff_with_store(other_root, other_store, diff_options).await
+                .map(|diff_entries: Vec<CoreDiffEntry>| diff_entries.iter().map(diff_entry_to_js).collect::<JsArray>().into())
+                .map_err(prolly_error_to_jsvalue)
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Copies the chunks of this tree's current root that `target`'s store is missing into it,
+    /// skipping subtrees it already holds. `target` can then `checkout` the root.
+    #[wasm_bindgen(js_name = syncTo)]
+    pub fn sync_to(&self, target: &PTree, options: Option<SyncOptionsJs>) -> PromiseSyncToFnReturn {
+        let sync_options: SyncOptions = match options.map(JsValue::from) {
+            Some(options_js) if !options_js.is_undefined() && !options_js.is_null() => match serde_wasm_bindgen::from_value(options_js) {
+                Ok(o) => o,
+                Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&JsValue::from_str(&format!("Failed to parse SyncOptions: {}", e)))).into(),
+            },
+            _ => SyncOptions::default(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let target_clone = Arc::clone(&target.inner);
+        let future = async move {
+            let target_store = if Arc::ptr_eq(&tree_clone, &target_clone) {
+                None
+            } else {
+                Some(Arc::clone(&target_clone.lock().await.store))
+            };
+            let tree = tree_clone.lock().await;
+            let target_store = target_store.unwrap_or_else(|| Arc::clone(&tree.store));
+            let stats = tree.sync_to(target_store, sync_options).await.map_err(prolly_error_to_jsvalue)?;
+            serde_wasm_bindgen::to_value(&stats)
+                .map_err(|e| JsValue::from_str(&format!("Failed to serialize SyncStats: {}", e)))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Like `diffRoots`, but resolves to a `PDiffStream` that computes the diff lazily.
+    #[wasm_bindgen(js_name = diffStream)]
+    pub fn diff_stream(&self, root_h_left_js: Option<JsUint8Array>, root_h_right_js: Option<JsUint8Array>, options: Option<DiffOptionsJs>) -> PromiseDiffStreamFnReturn {
+        let (h_left, h_right) = match (parse_optional_root_hash(root_h_left_js,"root_hash_left"), parse_optional_root_hash(root_h_right_js,"root_hash_right")) {
+            (Ok(l), Ok(r)) => (l,r),
+            (Err(e), _) | (_, Err(e)) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let diff_options = match parse_diff_options(options) {
+            Ok(o) => o,
+            Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            let tree = tree_clone.lock().await;
+            let stream: CoreDiffStream = Box::pin(crate::diff::diff_stream_with_options(
+                h_left, h_right, Arc::clone(&tree.store), tree.config.clone(), Arc::clone(tree.node_cache()), diff_options,
+            ));
+            Ok(PDiffStream { inner: Arc::new(tokio::sync::Mutex::new(stream)) }.into())
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    // Shared by `firstDifference` and `rangeEqual`: validates the arguments and returns the
+    // future computing the first differing key.
+    fn first_difference_future(
+        &self,
+        root_h_left_js: Option<JsUint8Array>,
+        root_h_right_js: Option<JsUint8Array>,
+        start: Option<JsUint8Array>,
+        end: Option<JsUint8Array>,
+    ) -> Result<impl std::future::Future<Output = Result<Option<Key>, JsValue>> + 'static, JsValue> {
+        let h_left = parse_optional_root_hash(root_h_left_js, "root_hash_left")?;
+        let h_right = parse_optional_root_hash(root_h_right_js, "root_hash_right")?;
+        let (start_key, end_key) = (start.map(|k| k.to_vec()), end.map(|k| k.to_vec()));
+        let tree_clone = Arc::clone(&self.inner);
+        Ok(async move {
+            let (config_clone, store_clone, cache_clone) = {
+                let tree = tree_clone.lock().await;
+                (tree.config.clone(), Arc::clone(&tree.store), Arc::clone(tree.node_cache()))
+            };
+            crate::diff::first_difference_in_range(h_left, h_right, store_clone, config_clone, cache_clone, start_key, end_key)
+                .await
+                .map_err(prolly_error_to_jsvalue)
+        })
+    }
+
+    /// Resolves to the smallest key in `[start, end)` whose value differs between the two roots,
+    /// or null if the range is equal.
+    #[wasm_bindgen(js_name = firstDifference)]
+    pub fn first_difference(&self, root_h_left_js: Option<JsUint8Array>, root_h_right_js: Option<JsUint8Array>, start: Option<JsUint8Array>, end: Option<JsUint8Array>) -> PromiseFirstDifferenceFnReturn {
+        let first_difference = match self.first_difference_future(root_h_left_js, root_h_right_js, start, end) {
+            Ok(f) => f,
+            Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let future = async move {
+            Ok(first_difference.await?.map_or(JsValue::NULL, |k| JsValue::from(JsUint8Array::from(&k[..]))))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Resolves to whether the two roots hold the same entries for every key in `[start, end)`.
+    #[wasm_bindgen(js_name = rangeEqual)]
+    pub fn range_equal(&self, root_h_left_js: Option<JsUint8Array>, root_h_right_js: Option<JsUint8Array>, start: Option<JsUint8Array>, end: Option<JsUint8Array>) -> PromiseRangeEqualFnReturn {
+        let first_difference = match self.first_difference_future(root_h_left_js, root_h_right_js, start, end) {
+            Ok(f) => f,
+            Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let future = async move { Ok(JsValue::from(first_difference.await?.is_none())) };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Resolves to the key ranges in which two roots differ, with the subtrees covering them on
+    /// each side, without loading leaf nodes.
+    #[wasm_bindgen(js_name = changedRanges)]
+    pub fn changed_ranges(&self, root_h_left_js: Option<JsUint8Array>, root_h_right_js: Option<JsUint8Array>) -> PromiseChangedRangesFnReturn {
+        let (h_left, h_right) = match (parse_optional_root_hash(root_h_left_js,"root_hash_left"), parse_optional_root_hash(root_h_right_js,"root_hash_right")) {
+            (Ok(l), Ok(r)) => (l,r),
+            (Err(e), _) | (_, Err(e)) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            let (config_clone, store_clone, cache_clone) = {
+                let tree = tree_clone.lock().await;
+                (tree.config.clone(), Arc::clone(&tree.store), Arc::clone(tree.node_cache()))
+            };
+            crate::diff::changed_ranges_with_cache(h_left, h_right, store_clone, config_clone, cache_clone)
+                .await
+                .map(|ranges| ranges.iter().map(changed_range_to_js).collect::<JsArray>().into())
+                .map_err(prolly_error_to_jsvalue)
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Counts the keys added, removed and modified between two roots without building the diff.
+    #[wasm_bindgen(js_name = diffSummary)]
+    pub fn diff_summary(&self, root_h_left_js: Option<JsUint8Array>, root_h_right_js: Option<JsUint8Array>, options: Option<DiffSummaryOptionsJs>) -> PromiseDiffSummaryFnReturn {
+        let (h_left, h_right) = match (parse_optional_root_hash(root_h_left_js,"root_hash_left"), parse_optional_root_hash(root_h_right_js,"root_hash_right")) {
+            (Ok(l), Ok(r)) => (l,r),
+            (Err(e), _) | (_, Err(e)) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let summary_options: DiffSummaryOptions = match options {
+            Some(options_js) if !options_js.is_undefined() && !options_js.is_null() => {
+                match serde_wasm_bindgen::from_value(options_js.into()) {
+                    Ok(o) => o,
+                    Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&JsValue::from_str(&format!("Failed to parse DiffSummaryOptions: {}", e)))).into(),
+                }
+            }
+            _ => DiffSummaryOptions::default(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            let (config_clone, store_clone, cache_clone) = {
+                let tree = tree_clone.lock().await;
+                (tree.config.clone(), Arc::clone(&tree.store), Arc::clone(tree.node_cache()))
+            };
+            let summary = crate::diff::diff_summary_with_options(h_left, h_right, store_clone, config_clone, cache_clone, summary_options)
+                .await
+                .map_err(prolly_error_to_jsvalue)?;
+            serde_wasm_bindgen::to_value(&summary)
+                .map_err(|e| JsValue::from_str(&format!("Failed to serialize DiffSummary: {}", e)))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Applies diff entries (as returned by `diffRoots` in the default `"values"` mode) to this
+    /// tree. Resolves to whether the root hash changed.
+    #[wasm_bindgen(js_name = applyDiff)]
+    pub fn apply_diff(&self, entries_js: &DiffEntryArray, mode: Option<ApplyModeJs>) -> PromiseApplyDiffFnReturn {
+        let entries_array = match JsValue::from(entries_js).dyn_ref::<JsArray>() {
+            Some(arr) => arr.clone(),
+            None => return wasm_bindgen::JsValue::from(Promise::reject(&JsValue::from_str("applyDiff expects an array."))).into(),
+        };
+        let mut entries: Vec<CoreDiffEntry> = Vec::with_capacity(entries_array.length() as usize);
+        for i in 0..entries_array.length() {
+            match diff_entry_from_js(&entries_array.get(i), i) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
             }
         }
+        let apply_mode: ApplyMode = match mode.map(JsValue::from) {
+            Some(mode_js) if !mode_js.is_undefined() && !mode_js.is_null() => match serde_wasm_bindgen::from_value(mode_js) {
+                Ok(m) => m,
+                Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&JsValue::from_str(&format!("Failed to parse ApplyMode: {}", e)))).into(),
+            },
+            _ => ApplyMode::default(),
+        };
+
+        let tree_clone = self.inner.clone();
+        let listeners_clone = self.listeners.clone();
+        let future = async move {
+            let mut tree = tree_clone.lock().await;
+            let old_hash = tree.get_root_hash();
+            let changed = tree.apply_diff(&entries, apply_mode).await.map_err(apply_diff_error_to_jsvalue)?;
+            if changed {
+                let new_hash = tree.get_root_hash();
+                Self::emit_change(&listeners_clone, old_hash, new_hash, "applyDiff");
+            }
+            Ok(JsValue::from(changed))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Serializes the changes from `baseRoot` to `targetRoot` into a portable patch file.
+    #[wasm_bindgen(js_name = exportPatch)]
+    pub fn export_patch(&self, base_root_js: Option<JsUint8Array>, target_root_js: Option<JsUint8Array>, options: Option<ExportPatchOptions>) -> PromiseExportPatchFnReturn {
+        let (h_base, h_target) = match (parse_optional_root_hash(base_root_js,"base_root"), parse_optional_root_hash(target_root_js,"target_root")) {
+            (Ok(b), Ok(t)) => (b,t),
+            (Err(e), _) | (_, Err(e)) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        // Values are embedded unless explicitly disabled.
+        let embed_values = options
+            .and_then(|o| Reflect::get(&o.into(), &"embedValues".into()).ok())
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            let patch = tree_clone.lock().await.export_patch(h_base, h_target, embed_values).await.map_err(prolly_error_to_jsvalue)?;
+            let patch_bytes = write_patch_v1(&patch).map_err(prolly_error_to_jsvalue)?;
+            Ok(JsValue::from(JsUint8Array::from(&patch_bytes[..])))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Applies a patch produced by `exportPatch`. The tree must be at the patch's base root,
+    /// and the import fails (leaving the tree unchanged) unless the result holds exactly the
+    /// target's entries, as recorded by the digest in the patch header.
+    #[wasm_bindgen(js_name = importPatch)]
+    pub fn import_patch(&self, patch_bytes_js: &JsUint8Array) -> PromiseImportPatchFnReturn {
+        let patch_bytes = patch_bytes_js.to_vec();
+        let tree_clone = self.inner.clone();
+        let listeners_clone = self.listeners.clone();
+        let future = async move {
+            let patch = read_patch_v1(&patch_bytes).map_err(prolly_error_to_jsvalue)?;
+            let mut tree = tree_clone.lock().await;
+            let old_hash = tree.get_root_hash();
+            let changed = tree.import_patch(&patch).await.map_err(prolly_error_to_jsvalue)?;
+            if changed {
+                let new_hash = tree.get_root_hash();
+                Self::emit_change(&listeners_clone, old_hash, new_hash, "importPatch");
+            }
+            Ok(JsValue::from(changed))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    #[wasm_bindgen(js_name = triggerGc)]
+    pub fn trigger_gc(&self, live_hashes_js_val: &JsValue) -> PromiseTriggerGcFnReturn {
+        let live_hashes_rust = match parse_hash_array(live_hashes_js_val, "triggerGc") {
+            Ok(hashes) => hashes,
+            Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
         let tree_clone = Arc::clone(&self.inner);
         let future = async move {
             tree_clone.lock().await.gc(&live_hashes_rust).await
@@ -602,6 +1175,121 @@ impl PTree {
         wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
     }
 
+    #[wasm_bindgen(js_name = pinRoot)]
+    pub fn pin_root(&self, root_hash: JsUint8Array) -> PromisePinRootFnReturn {
+        let root = match parse_root_hash(&root_hash, "root hash") {
+            Ok(root) => root,
+            Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            tree_clone.lock().await.pin_root(root);
+            Ok(JsValue::UNDEFINED)
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    #[wasm_bindgen(js_name = unpinRoot)]
+    pub fn unpin_root(&self, root_hash: JsUint8Array) -> PromiseUnpinRootFnReturn {
+        let root = match parse_root_hash(&root_hash, "root hash") {
+            Ok(root) => root,
+            Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            tree_clone.lock().await.unpin_root(&root)
+                .map(|_| JsValue::UNDEFINED).map_err(prolly_error_to_jsvalue)
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// The roots pinned by `pinRoot` or by loading a file saved with `retainRoots`.
+    #[wasm_bindgen(js_name = getPinnedRoots)]
+    pub fn get_pinned_roots(&self) -> PromiseGetPinnedRootsFnReturn {
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            let pinned_roots: JsArray = tree_clone.lock().await.root_pins().pinned_roots().iter()
+                .map(|h| JsValue::from(JsUint8Array::from(&h[..])))
+                .collect();
+            Ok(pinned_roots.into())
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Copies the chunks reachable from the current root and from `retainRoots` into a new
+    /// tree with its own store, leaving this tree untouched.
+    #[wasm_bindgen(js_name = compact)]
+    pub fn compact(&self, retain_roots_js: Option<JsArray>) -> PromiseCompactFnReturn {
+        let retain_roots = match retain_roots_js {
+            Some(roots_js) => match parse_hash_array(&roots_js.into(), "compact") {
+                Ok(hashes) => hashes,
+                Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+            },
+            None => Vec::new(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            let compacted = tree_clone.lock().await.compact_into(InMemoryStore::new(), &retain_roots).await
+                .map_err(prolly_error_to_jsvalue)?;
+            Ok(PTree::from_tree(compacted).into())
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    #[wasm_bindgen(js_name = startIncrementalGc)]
+    pub fn start_incremental_gc(&self, live_hashes_js_val: &JsValue) -> PromiseStartIncrementalGcFnReturn {
+        let live_hashes_rust = match parse_hash_array(live_hashes_js_val, "startIncrementalGc") {
+            Ok(hashes) => hashes,
+            Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            let progress = tree_clone.lock().await.start_incremental_gc(&live_hashes_rust).await.map_err(prolly_error_to_jsvalue)?;
+            serde_wasm_bindgen::to_value(&progress)
+                .map_err(|e| JsValue::from_str(&format!("Failed to serialize GcProgress: {}", e)))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    #[wasm_bindgen(js_name = gcStep)]
+    pub fn gc_step(&self, max_chunks: u32) -> PromiseGcStepFnReturn {
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            let progress = tree_clone.lock().await.gc_step(max_chunks as usize).await.map_err(prolly_error_to_jsvalue)?;
+            serde_wasm_bindgen::to_value(&progress)
+                .map_err(|e| JsValue::from_str(&format!("Failed to serialize GcProgress: {}", e)))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    #[wasm_bindgen(js_name = planGc)]
+    pub fn plan_gc(&self, live_hashes_js_val: &JsValue) -> PromisePlanGcFnReturn {
+        let live_hashes_rust = match parse_hash_array(live_hashes_js_val, "planGc") {
+            Ok(hashes) => hashes,
+            Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            tree_clone.lock().await.plan_gc(&live_hashes_rust).await
+                .map(|plan| gc_plan_to_js(&plan)).map_err(prolly_error_to_jsvalue)
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    #[wasm_bindgen(js_name = executeGcPlan)]
+    pub fn execute_gc_plan(&self, plan: GcPlanJs) -> PromiseExecuteGcPlanFnReturn {
+        let plan_rust = match gc_plan_from_js(&plan) {
+            Ok(plan) => plan,
+            Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            tree_clone.lock().await.collect_gc_plan(&plan_rust).await
+                .map(|c| JsValue::from_f64(c as f64)).map_err(prolly_error_to_jsvalue)
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
     #[wasm_bindgen(js_name = "getTreeConfig")]
     pub fn get_tree_config(&self) -> PromiseGetTreeConfigFnReturn { 
         let tree_clone = Arc::clone(&self.inner);
@@ -612,6 +1300,16 @@ impl PTree {
         wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
     }
 
+    #[wasm_bindgen(js_name = "getNodeCacheStats")]
+    pub fn get_node_cache_stats(&self) -> PromiseGetNodeCacheStatsFnReturn {
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            serde_wasm_bindgen::to_value(&tree_clone.lock().await.node_cache_stats())
+                .map_err(|e| JsValue::from_str(&format!("Failed to serialize NodeCacheStats: {}", e)))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
     #[wasm_bindgen(js_name = scanItems)]
     pub fn scan_items( &self, options: ScanOptions ) -> PromiseScanItemsFnReturn {         
         let core_scan_args: core_tree_types::ScanArgs = if options.is_undefined() || options.is_null() {
@@ -700,8 +1398,55 @@ impl PTree {
         wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
     }
 
+    /// Serializes the chunks reachable from `toRoot` but not from `fromRoot` into a delta bundle.
+    #[wasm_bindgen(js_name = exportDelta)]
+    pub fn export_delta(&self, from_root_js: Option<JsUint8Array>, to_root_js: Option<JsUint8Array>, description: Option<String>) -> PromiseExportDeltaFnReturn {
+        let (h_from, h_to) = match (parse_optional_root_hash(from_root_js,"from_root"), parse_optional_root_hash(to_root_js,"to_root")) {
+            (Ok(f), Ok(t)) => (f,t),
+            (Err(e), _) | (_, Err(e)) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+        };
+        let tree_clone = Arc::clone(&self.inner);
+        let future = async move {
+            let tree = tree_clone.lock().await;
+            let bundle = tree.export_delta(h_from, h_to).await.map_err(prolly_error_to_jsvalue)?;
+            let file_bytes = write_prly_delta_v2(&bundle, &tree.config, description).map_err(prolly_error_to_jsvalue)?;
+            Ok(JsValue::from(JsUint8Array::from(&file_bytes[..])))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Imports a delta bundle produced by `exportDelta` into a tree whose store holds the
+    /// bundle's base, and checks out the bundle's target root.
+    #[wasm_bindgen(js_name = importDelta)]
+    pub fn import_delta(&self, delta_bytes_js: &JsUint8Array) -> PromiseImportDeltaFnReturn {
+        let delta_bytes = delta_bytes_js.to_vec();
+        let tree_clone = self.inner.clone();
+        let listeners_clone = self.listeners.clone();
+        let future = async move {
+            let (bundle, _tree_config, _description) = read_prly_delta_v2(&delta_bytes).map_err(prolly_error_to_jsvalue)?;
+            let mut tree = tree_clone.lock().await;
+            let old_hash = tree.get_root_hash();
+            let changed = tree.import_delta(&bundle).await.map_err(prolly_error_to_jsvalue)?;
+            if changed {
+                let new_hash = tree.get_root_hash();
+                Self::emit_change(&listeners_clone, old_hash, new_hash, "importDelta");
+            }
+            Ok(JsValue::from(changed))
+        };
+        wasm_bindgen::JsValue::from(wasm_bindgen_futures::future_to_promise(future)).into()
+    }
+
+    /// Saves the chunks reachable from the current root and from `retainRoots` (e.g. older
+    /// versions the application keeps), recording every retained root in the metadata.
     #[wasm_bindgen(js_name = saveTreeToFileBytes)]
-    pub fn save_tree_to_file_bytes(&self, description: Option<String>) -> PromiseExportTreeToFileFnReturn {
+    pub fn save_tree_to_file_bytes(&self, description: Option<String>, retain_roots_js: Option<JsArray>) -> PromiseExportTreeToFileFnReturn {
+        let extra_roots = match retain_roots_js {
+            Some(roots_js) => match parse_hash_array(&roots_js.into(), "saveTreeToFileBytes") {
+                Ok(hashes) => hashes,
+                Err(e) => return wasm_bindgen::JsValue::from(Promise::reject(&e)).into(),
+            },
+            None => Vec::new(),
+        };
         let tree_clone = Arc::clone(&self.inner);
         let future = async move {
             let tree_guard = tree_clone.lock().await;
@@ -709,10 +1454,10 @@ impl PTree {
             let root_hash = tree_guard.get_root_hash();
             let tree_config = tree_guard.config.clone();
             
-            // Line 529 where HashMap was not found
-            let chunks_map_rust: HashMap<Hash, Vec<u8>> = tree_guard.store.get_all_chunks_for_test().await;
+            let (retained_roots, chunks_map_rust): (Vec<Hash>, HashMap<Hash, Vec<u8>>) =
+                tree_guard.live_chunks(&extra_roots).await.map_err(prolly_error_to_jsvalue)?;
 
-            match write_prly_tree_v2(root_hash, &tree_config, &chunks_map_rust, description) {
+            match write_prly_tree_v2(root_hash, &tree_config, &chunks_map_rust, description, retained_roots) {
                 Ok(file_bytes) => Ok(JsValue::from(JsUint8Array::from(&file_bytes[..]))),
                 Err(e) => Err(prolly_error_to_jsvalue(e)),
             }
@@ -724,15 +1469,13 @@ impl PTree {
     pub fn load_tree_from_file_bytes(file_bytes_js: JsUint8Array) -> Promise {
         let file_bytes = file_bytes_js.to_vec();
         let future = async move {
-            let (root_hash_opt, tree_config, chunks, _description) =
+            let (root_hash_opt, tree_config, chunks, _description, retained_roots) =
                 read_prly_tree_v2(&file_bytes).map_err(prolly_error_to_jsvalue)?;
 
             let store = InMemoryStore::new();
-            for (_hash, chunk) in chunks {
-                // Fixed: Use the correct `put` signature.
-                // This is inefficient as it re-hashes, but it's correct per the trait definition.
-                store.put(chunk).await.map_err(prolly_error_to_jsvalue)?;
-            }
+            // Re-hashes every chunk, but it's correct per the trait definition.
+            let chunks = chunks.into_values().collect();
+            store.put_many(chunks).await.map_err(prolly_error_to_jsvalue)?;
             let store_arc = Arc::new(store);
 
             let tree = if let Some(root_hash) = root_hash_opt {
@@ -742,12 +1485,12 @@ impl PTree {
             } else {
                 ProllyTree::new(store_arc, tree_config)
             };
-            
-            Ok(PTree {
-                inner: Arc::new(tokio::sync::Mutex::new(tree)),
-                listeners: Arc::new(RefCell::new(Vec::new())),
+            // The retained roots stay checkoutable until the application unpins them.
+            for root in retained_roots {
+                tree.pin_root(root);
             }
-            .into())
+
+            Ok(PTree::from_tree(tree).into())
         };
         wasm_bindgen_futures::future_to_promise(future)
     }
@@ -0,0 +1,205 @@
+// prolly-rust/src/node/cache.rs
+
+//! A bounded, least-recently-used cache of decoded nodes keyed by their chunk hash.
+//!
+//! Nodes are content-addressed, so a cached entry can never go stale for its hash; the
+//! cache only has to bound its memory use. Limits are enforced both by entry count and by
+//! the encoded size of the cached nodes. Nodes are handed out as `Arc<Node>`, so a hit
+//! costs a reference count rather than a copy; callers that modify a node clone it first.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use serde::{Serialize, Deserialize};
+
+use crate::common::Hash;
+use crate::error::{Result, ProllyError};
+use crate::node::definition::Node;
+use crate::store::ChunkStore;
+use crate::store::chunk_store::check_batch_len;
+
+/// Size limits for a `NodeCache`. A limit of zero disables caching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeCacheConfig {
+    /// Maximum number of decoded nodes kept in the cache.
+    pub max_nodes: usize,
+    /// Maximum total encoded size, in bytes, of the nodes kept in the cache.
+    pub max_bytes: usize,
+}
+
+impl Default for NodeCacheConfig {
+    fn default() -> Self {
+        NodeCacheConfig {
+            max_nodes: 1024,
+            max_bytes: 16 * 1024 * 1024, // 16 MiB
+        }
+    }
+}
+
+/// Counters describing cache effectiveness and current occupancy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+#[derive(Debug)]
+struct CachedNode {
+    node: Arc<Node>,
+    encoded_size: usize,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct NodeCacheInner {
+    entries: HashMap<Hash, CachedNode>,
+    /// Recency order: `last_used` tick -> hash. The first entry is the least recently used.
+    recency: BTreeMap<u64, Hash>,
+    tick: u64,
+    bytes: usize,
+    stats: NodeCacheStats,
+}
+
+impl NodeCacheInner {
+    /// Marks `hash` as most recently used. Returns false if it is not cached.
+    fn touch(&mut self, hash: &Hash) -> bool {
+        self.tick += 1;
+        let tick = self.tick;
+        let Some(entry) = self.entries.get_mut(hash) else { return false };
+        self.recency.remove(&entry.last_used);
+        entry.last_used = tick;
+        self.recency.insert(tick, *hash);
+        true
+    }
+
+    fn evict_to_fit(&mut self, config: &NodeCacheConfig) {
+        while self.entries.len() > config.max_nodes || self.bytes > config.max_bytes {
+            let Some((_, hash)) = self.recency.pop_first() else { break };
+            if let Some(evicted) = self.entries.remove(&hash) {
+                self.bytes -= evicted.encoded_size;
+                self.stats.evictions += 1;
+            }
+        }
+    }
+}
+
+/// A thread-safe LRU cache of decoded nodes, shared by a tree and the cursors and diffs it creates.
+#[derive(Debug)]
+pub struct NodeCache {
+    config: NodeCacheConfig,
+    inner: Mutex<NodeCacheInner>,
+}
+
+impl Default for NodeCache {
+    fn default() -> Self {
+        Self::new(NodeCacheConfig::default())
+    }
+}
+
+impl NodeCache {
+    pub fn new(config: NodeCacheConfig) -> Self {
+        NodeCache { config, inner: Mutex::new(NodeCacheInner::default()) }
+    }
+
+    pub fn config(&self) -> NodeCacheConfig {
+        self.config
+    }
+
+    fn lock(&self) -> MutexGuard<'_, NodeCacheInner> {
+        // The cache holds no invariants worth abandoning on a panic elsewhere.
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the cached node for `hash`, recording a hit or a miss.
+    pub fn get(&self, hash: &Hash) -> Option<Arc<Node>> {
+        let mut inner = self.lock();
+        if inner.touch(hash) {
+            inner.stats.hits += 1;
+            inner.entries.get(hash).map(|entry| Arc::clone(&entry.node))
+        } else {
+            inner.stats.misses += 1;
+            None
+        }
+    }
+
+    /// Whether `hash` is cached. Unlike `get`, this neither counts as a hit or miss nor
+    /// refreshes the entry's recency.
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.lock().entries.contains_key(hash)
+    }
+
+    /// Caches a decoded node. `encoded_size` is the size of its stored chunk and is what
+    /// counts against `max_bytes`. Nodes larger than `max_bytes` are not cached.
+    pub fn insert(&self, hash: Hash, node: Arc<Node>, encoded_size: usize) {
+        if self.config.max_nodes == 0 || encoded_size > self.config.max_bytes {
+            return;
+        }
+        let mut inner = self.lock();
+        if inner.touch(&hash) {
+            return;
+        }
+        let tick = inner.tick;
+        inner.recency.insert(tick, hash);
+        inner.entries.insert(hash, CachedNode { node, encoded_size, last_used: tick });
+        inner.bytes += encoded_size;
+        inner.evict_to_fit(&self.config);
+    }
+
+    /// Drops every cached node. Counters are kept.
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.entries.clear();
+        inner.recency.clear();
+        inner.bytes = 0;
+    }
+
+    pub fn stats(&self) -> NodeCacheStats {
+        let inner = self.lock();
+        NodeCacheStats { entries: inner.entries.len(), bytes: inner.bytes, ..inner.stats }
+    }
+
+    /// Loads and decodes the node for `hash`, serving it from the cache when possible.
+    pub async fn load<S: ChunkStore + ?Sized>(&self, store: &S, hash: &Hash) -> Result<Arc<Node>> {
+        if let Some(node) = self.get(hash) {
+            return Ok(node);
+        }
+        let bytes = store.get(hash).await?
+            .ok_or(ProllyError::ChunkNotFound(*hash))?;
+        let node = Arc::new(Node::decode(&bytes)?);
+        self.insert(*hash, Arc::clone(&node), bytes.len());
+        Ok(node)
+    }
+
+    /// Loads several nodes, fetching the ones not cached with a single `get_many` call.
+    pub async fn load_many<S: ChunkStore + ?Sized>(&self, store: &S, hashes: &[Hash]) -> Result<Vec<Arc<Node>>> {
+        let mut nodes: Vec<Option<Arc<Node>>> = hashes.iter().map(|hash| self.get(hash)).collect();
+        let missing: Vec<Hash> = hashes.iter().zip(&nodes).filter(|(_, node)| node.is_none()).map(|(hash, _)| *hash).collect();
+        if !missing.is_empty() {
+            let fetched = check_batch_len("get_many", missing.len(), store.get_many(&missing).await?)?;
+            for ((hash, bytes), node) in missing.iter().zip(fetched).zip(nodes.iter_mut().filter(|node| node.is_none())) {
+                let bytes = bytes.ok_or(ProllyError::ChunkNotFound(*hash))?;
+                let decoded = Arc::new(Node::decode(&bytes)?);
+                self.insert(*hash, Arc::clone(&decoded), bytes.len());
+                *node = Some(decoded);
+            }
+        }
+        Ok(nodes.into_iter().flatten().collect())
+    }
+
+    /// Synchronous variant of `load`, for stores that support synchronous reads.
+    pub fn load_sync<S: ChunkStore + ?Sized>(&self, store: &S, hash: &Hash) -> Result<Arc<Node>> {
+        if let Some(node) = self.get(hash) {
+            return Ok(node);
+        }
+        let bytes = store.get_sync(hash)?
+            .ok_or(ProllyError::ChunkNotFound(*hash))?;
+        let node = Arc::new(Node::decode(&bytes)?);
+        self.insert(*hash, Arc::clone(&node), bytes.len());
+        Ok(node)
+    }
+}
@@ -1,9 +1,15 @@
 // prolly-rust/src/node/definition.rs
 
 use serde::{Serialize, Deserialize};
+use bincode::Options;
 use crate::common::{Hash, Key, Value, TreeConfig}; // TreeConfig for FANOUT access
 use crate::error::{Result, ProllyError};
 
+/// Leading byte of the prefix-compressed node layout written by `Node::encode`.
+/// Legacy nodes are plain bincode, which starts with the little-endian `u32` variant
+/// tag of `Node` (first byte 0 or 1), so the two layouts can never be confused.
+const PREFIX_COMPRESSED_NODE_TAG: u8 = 0xC1;
+
 /// Represents a value stored in a leaf node.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum ValueRepr {
@@ -20,6 +26,28 @@ pub enum ValueRepr {
     },
 }
 
+impl ValueRepr {
+    /// A digest identifying this value without loading any chunk payloads: the BLAKE3 hash
+    /// of an inline value, the chunk hash of a single-chunk value, or a BLAKE3 hash over the
+    /// chunk hashes of a chunk sequence. Equal digests imply equal values. In a store that
+    /// addresses chunks by their BLAKE3 hash, an inline value and a single-chunk value with
+    /// the same bytes have the same digest, while a chunk sequence matches neither.
+    pub fn digest(&self) -> Hash {
+        match self {
+            ValueRepr::Inline(val) => *blake3::hash(val).as_bytes(),
+            ValueRepr::Chunked(data_hash) => *data_hash,
+            ValueRepr::ChunkedSequence { chunk_hashes, total_size } => {
+                let mut hasher = blake3::Hasher::new();
+                for chunk_hash in chunk_hashes {
+                    hasher.update(chunk_hash);
+                }
+                hasher.update(&total_size.to_le_bytes());
+                *hasher.finalize().as_bytes()
+            }
+        }
+    }
+}
+
 /// An entry in a leaf node, mapping a key to a value representation.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct LeafEntry {
@@ -28,21 +56,57 @@ pub struct LeafEntry {
 }
 
 /// An entry in an internal node, pointing to a child node.
-/// The `boundary_key` acts as a separator. All keys in the `child_node`
-/// are less than or equal to this `boundary_key`.
-/// The last child in an internal node might not need a boundary_key or use a conceptual max_key.
+/// The `boundary_key` acts as a separator: every key in the child subtree is less than
+/// or equal to it, and (except for the last entry of a node) every key in the right
+/// sibling's subtree is strictly greater than it.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct InternalEntry {
-    /// The key that delimits the upper bound of keys in the child_node.
-    /// For the "leftmost" pointer in some B-tree variants, this might be omitted
-    /// or handled specially, but Prolly Trees often have keys for all pointers.
-    /// A common strategy: this key is the *largest key* in the subtree pointed to by `child_hash`.
+    /// Upper bound of the keys in the subtree pointed to by `child_hash`.
+    /// For all but the last entry this is the shortest byte string that separates the
+    /// child from its right sibling (see `shortest_separator`), so it need not be a key
+    /// that exists in the tree. The last entry of a node carries the boundary reported
+    /// by its child, which is at least the subtree's largest key.
     pub boundary_key: Key,
     pub child_hash: Hash,
     pub num_items_subtree: u64, 
     // pub total_size_subtree: u64, // Optional: for size-based balancing
 }
 
+/// Returns the shortest byte string `s` with `left <= s < right`, used as the boundary key
+/// between two adjacent subtrees whose largest and smallest keys are `left` and `right`.
+/// Falls back to `left` itself when nothing shorter exists (or `left >= right`).
+pub fn shortest_separator(left: &[u8], right: &[u8]) -> Key {
+    if left >= right {
+        return left.to_vec();
+    }
+    let shared = left.iter().zip(right.iter()).take_while(|(a, b)| a == b).count();
+    if shared == left.len() {
+        // `left` is a prefix of `right`; no shorter string can be >= `left`.
+        return left.to_vec();
+    }
+    // Here left[shared] < right[shared].
+    if left[shared] + 1 < right[shared] {
+        let mut separator = left[..shared].to_vec();
+        separator.push(left[shared] + 1);
+        return separator;
+    }
+    if right.len() > shared + 1 {
+        // A proper prefix of `right` is smaller than it, and larger than `left`
+        // because it already differs at `shared`.
+        return right[..=shared].to_vec();
+    }
+    // `right` ends at the differing byte, which is exactly one more than left's:
+    // keep left's byte and bump the first following byte that can be incremented.
+    for idx in (shared + 1)..left.len() {
+        if left[idx] != u8::MAX {
+            let mut separator = left[..idx].to_vec();
+            separator.push(left[idx] + 1);
+            return separator;
+        }
+    }
+    left.to_vec()
+}
+
 /// Represents a node in the Prolly Tree.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum Node {
@@ -104,14 +168,74 @@ impl Node {
         }
     }
 
-    /// Encodes the node into bytes using bincode.
+    /// Encodes the node into bytes using the prefix-compressed layout.
+    ///
+    /// Each key is stored as the length of the prefix it shares with the previous key
+    /// in the node plus the remaining suffix, and all integers are varint-encoded.
     pub fn encode(&self) -> Result<Vec<u8>> {
-        bincode::serialize(self).map_err(ProllyError::from)
+        let compressed = match self {
+            Node::Leaf { level, entries } => {
+                let mut prev_key: &[u8] = &[];
+                let entries = entries.iter().map(|entry| {
+                    let key = CompressedKey::new(prev_key, &entry.key);
+                    prev_key = &entry.key;
+                    CompressedLeafEntry { key, value: entry.value.clone() }
+                }).collect();
+                CompressedNode::Leaf { level: *level, entries }
+            }
+            Node::Internal { level, children } => {
+                let mut prev_key: &[u8] = &[];
+                let children = children.iter().map(|child| {
+                    let boundary_key = CompressedKey::new(prev_key, &child.boundary_key);
+                    prev_key = &child.boundary_key;
+                    CompressedInternalEntry {
+                        boundary_key,
+                        child_hash: child.child_hash,
+                        num_items_subtree: child.num_items_subtree,
+                    }
+                }).collect();
+                CompressedNode::Internal { level: *level, children }
+            }
+        };
+        let mut bytes = vec![PREFIX_COMPRESSED_NODE_TAG];
+        compact_bincode().serialize_into(&mut bytes, &compressed).map_err(ProllyError::from)?;
+        Ok(bytes)
     }
 
-    /// Decodes a node from bytes using bincode.
+    /// Decodes a node from bytes. Accepts both the prefix-compressed layout and the
+    /// legacy plain-bincode layout, so chunks and files written before compression
+    /// was introduced remain readable.
     pub fn decode(bytes: &[u8]) -> Result<Self> {
-        bincode::deserialize(bytes).map_err(ProllyError::from)
+        match bytes.split_first() {
+            Some((&PREFIX_COMPRESSED_NODE_TAG, body)) => {
+                let compressed: CompressedNode = compact_bincode().deserialize(body).map_err(ProllyError::from)?;
+                match compressed {
+                    CompressedNode::Leaf { level, entries } => {
+                        let mut decoded: Vec<LeafEntry> = Vec::with_capacity(entries.len());
+                        for entry in entries {
+                            let prev_key = decoded.last().map_or(&[][..], |e| e.key.as_slice());
+                            let key = entry.key.expand(prev_key)?;
+                            decoded.push(LeafEntry { key, value: entry.value });
+                        }
+                        Ok(Node::Leaf { level, entries: decoded })
+                    }
+                    CompressedNode::Internal { level, children } => {
+                        let mut decoded: Vec<InternalEntry> = Vec::with_capacity(children.len());
+                        for child in children {
+                            let prev_key = decoded.last().map_or(&[][..], |c| c.boundary_key.as_slice());
+                            let boundary_key = child.boundary_key.expand(prev_key)?;
+                            decoded.push(InternalEntry {
+                                boundary_key,
+                                child_hash: child.child_hash,
+                                num_items_subtree: child.num_items_subtree,
+                            });
+                        }
+                        Ok(Node::Internal { level, children: decoded })
+                    }
+                }
+            }
+            _ => bincode::deserialize(bytes).map_err(ProllyError::from),
+        }
     }
 
     // Utility to get the number of entries/children
@@ -121,4 +245,60 @@ impl Node {
             Node::Internal { children, .. } => children.len(),
         }
     }
-}
\ No newline at end of file
+}
+
+// --- Prefix-compressed wire layout ---
+
+/// Bincode options for the compressed layout: varint integers, little endian.
+fn compact_bincode() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+/// A key stored relative to the previous key in the same node.
+#[derive(Serialize, Deserialize)]
+struct CompressedKey {
+    /// Number of leading bytes shared with the previous key.
+    shared_prefix_len: u32,
+    /// The bytes following the shared prefix.
+    suffix: Vec<u8>,
+}
+
+impl CompressedKey {
+    fn new(prev_key: &[u8], key: &[u8]) -> Self {
+        let shared = prev_key.iter().zip(key.iter()).take_while(|(a, b)| a == b).count();
+        Self { shared_prefix_len: shared as u32, suffix: key[shared..].to_vec() }
+    }
+
+    fn expand(self, prev_key: &[u8]) -> Result<Key> {
+        let shared = self.shared_prefix_len as usize;
+        if shared > prev_key.len() {
+            return Err(ProllyError::NodeDeserialization(format!(
+                "Shared prefix length {} exceeds previous key length {}",
+                shared, prev_key.len()
+            )));
+        }
+        let mut key = Vec::with_capacity(shared + self.suffix.len());
+        key.extend_from_slice(&prev_key[..shared]);
+        key.extend_from_slice(&self.suffix);
+        Ok(key)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CompressedLeafEntry {
+    key: CompressedKey,
+    value: ValueRepr,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CompressedInternalEntry {
+    boundary_key: CompressedKey,
+    child_hash: Hash,
+    num_items_subtree: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+enum CompressedNode {
+    Leaf { level: u8, entries: Vec<CompressedLeafEntry> },
+    Internal { level: u8, children: Vec<CompressedInternalEntry> },
+}
@@ -1,8 +1,10 @@
 // prolly-rust/src/node/mod.rs
 
 pub mod definition;
+pub mod cache;
 
 // Re-export key items for easier access from `crate::node::`
 pub use definition::{Node, LeafEntry, InternalEntry, ValueRepr};
+pub use cache::{NodeCache, NodeCacheConfig, NodeCacheStats};
 // Potentially re-export constants like FANOUT if they remain in definition.rs
 // pub use definition::DEFAULT_FANOUT; // Example if we make FANOUT part of TreeConfig or a default
\ No newline at end of file
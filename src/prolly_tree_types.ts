@@ -10,6 +10,12 @@ export interface TreeConfigOptions {
   cdcAvgSize?: number | null;
   cdcMaxSize?: number | null;
   maxInlineValueSize?: number | null;
+  /** Sibling nodes and value chunks a cursor fetches concurrently ahead of its position. 0 disables read-ahead. */
+  readAhead?: number | null;
+  /** Maximum number of decoded nodes the tree caches (default 1024). 0 disables the cache. Not saved with the tree. */
+  nodeCacheMaxNodes?: number | null;
+  /** Maximum total encoded size, in bytes, of the cached nodes (default 16 MiB). 0 disables the cache. Not saved with the tree. */
+  nodeCacheMaxBytes?: number | null;
 }
 
 /**
@@ -58,8 +64,126 @@ export interface IScanPage {
  */
 export interface DiffEntry {
   key: Uint8Array;
+  kind: DiffKind;
   leftValue?: Uint8Array | null;
   rightValue?: Uint8Array | null;
+  /** Digest of the left value. Only present with `mode: "digests"`. */
+  leftDigest?: Uint8Array;
+  /** Digest of the right value. Only present with `mode: "digests"`. */
+  rightDigest?: Uint8Array;
+}
+
+/**
+ * How `applyDiff` treats entries whose old value does not match the tree: `"strict"`
+ * (default) checks every entry's `leftValue` first and applies nothing on a mismatch,
+ * rejecting with an error whose `conflicts` property lists the `DiffConflict`s; `"force"`
+ * writes every entry's right side unconditionally.
+ */
+export type ApplyMode = "strict" | "force";
+
+/** A key whose current value differs from the `leftValue` a strict `applyDiff` expected. */
+export interface DiffConflict {
+  key: Uint8Array;
+  /** The expected value, or null for an addition. */
+  expected: Uint8Array | null;
+  /** The current value, or null if the key is absent. */
+  actual: Uint8Array | null;
+}
+
+/** Options for `exportPatch`. */
+export interface ExportPatchOptions {
+  /**
+   * Embed every new value in the patch (default `true`). When `false`, chunked values are
+   * stored as chunk hash references, and the importing tree's store must already hold
+   * those chunks.
+   */
+  embedValues?: boolean;
+}
+
+/** Options for `syncTo`. */
+export interface SyncOptions {
+  /** Maximum number of chunk hashes or chunks per protocol message (default 256). */
+  batchSize?: number;
+}
+
+/** What a `syncTo` call transferred. */
+export interface SyncStats {
+  /** Chunks the target was missing, all of which were copied. */
+  chunksSent: number;
+  /** Total size of the copied chunks. */
+  bytesSent: number;
+  /** Offered chunks the target already held; the subtrees below them were skipped. */
+  chunksSkipped: number;
+  /** Number of protocol messages exchanged. */
+  roundTrips: number;
+}
+
+/** The kind of change a `DiffEntry` describes. */
+export type DiffKind = "added" | "removed" | "modified";
+
+/**
+ * Options for `diffRoots` and `diffStream`.
+ * Corresponds to the Rust `DiffOptions` struct.
+ */
+export interface DiffOptions {
+  /**
+   * What to report per changed key: `"values"` (default) loads both values, `"keys"` reports
+   * only keys and change kinds, and `"digests"` adds a 32-byte digest of each value. The last
+   * two modes never read chunked value payloads.
+   */
+  mode?: "values" | "keys" | "digests";
+  /** Maximum number of node or value fetches issued at once. */
+  concurrency?: number;
+  /** Only report changes to keys greater than or equal to this key (inclusive). */
+  start?: Uint8Array;
+  /** Only report changes to keys strictly less than this key (exclusive). */
+  end?: Uint8Array;
+}
+
+/**
+ * Options for `diffSummary`.
+ * Corresponds to the Rust `DiffSummaryOptions` struct.
+ */
+export interface DiffSummaryOptions {
+  /** Maximum number of node or value fetches issued at once. */
+  concurrency?: number;
+  /** Only count keys greater than or equal to this key (inclusive). */
+  start?: Uint8Array;
+  /** Only count keys strictly less than this key (exclusive). */
+  end?: Uint8Array;
+  /**
+   * Also compute `bytesAdded` and `bytesRemoved`. This visits every changed key instead of
+   * counting wholly added or removed subtrees from their stored item counts.
+   */
+  includeBytes?: boolean;
+}
+
+/**
+ * Counts of the changes between two roots.
+ * Corresponds to the Rust `DiffSummary` struct.
+ */
+export interface DiffSummary {
+  added: number;
+  removed: number;
+  modified: number;
+  /** Size of added values plus new values of modified keys. Only with `includeBytes`. */
+  bytesAdded?: number;
+  /** Size of removed values plus old values of modified keys. Only with `includeBytes`. */
+  bytesRemoved?: number;
+}
+
+/**
+ * Counters for the tree's decoded-node cache.
+ * This corresponds to the Rust `NodeCacheStats` struct.
+ */
+export interface NodeCacheStats {
+  hits: number;
+  misses: number;
+  evictions: number;
+  /** Number of decoded nodes currently cached. */
+  entries: number;
+  /** Total encoded size, in bytes, of the cached nodes. */
+  bytes: number;
 }
 
 // --- Resolved Promise Return Type Aliases ---
@@ -89,10 +213,114 @@ export type GetRootHashSyncFnReturn = Uint8Array | null;
 export type ExportChunksFnReturn = Map<Uint8Array, Uint8Array>;
 /** The `diffRoots` method resolves to an array of DiffEntry objects. */
 export type DiffRootsFnReturn = DiffEntry[];
+/** The `diffStream` method resolves to a stream that computes the diff as it is read. */
+export type DiffStreamFnReturn = PDiffStream;
+/** The `diffSummary` method resolves to the change counts between two roots. */
+export type DiffSummaryFnReturn = DiffSummary;
+/** The `applyDiff` method resolves to whether the tree's root hash changed. */
+export type ApplyDiffFnReturn = boolean;
+/** The `exportPatch` method resolves to the bytes of a patch file. */
+export type ExportPatchFnReturn = Uint8Array;
+/** The `importPatch` method resolves to whether the tree's root hash changed. */
+export type ImportPatchFnReturn = boolean;
+/** The `syncTo` method resolves to what was transferred. */
+export type SyncToFnReturn = SyncStats;
+/** The `exportDelta` method resolves to the bytes of a delta bundle. */
+export type ExportDeltaFnReturn = Uint8Array;
+/** The `importDelta` method resolves to whether the tree's root hash changed. */
+export type ImportDeltaFnReturn = boolean;
+/** The `firstDifference` method resolves to the first differing key, or null if the range is equal. */
+export type FirstDifferenceFnReturn = Uint8Array | null;
+/** The `rangeEqual` method resolves to whether both roots hold the same entries in the range. */
+export type RangeEqualFnReturn = boolean;
+/** A subtree referenced by its parent, described without loading it. */
+export interface SubtreeRef {
+  hash: Uint8Array;
+  /** Height of the subtree; 0 for a leaf node. */
+  level: number;
+  numItems: number;
+}
+
+/**
+ * A key range `low < key <= high` in which two roots differ (a null bound is unbounded),
+ * with the subtrees covering it on each side. One side is empty when the range only
+ * holds keys of the other root.
+ */
+export interface ChangedRange {
+  low: Uint8Array | null;
+  high: Uint8Array | null;
+  left: SubtreeRef[];
+  right: SubtreeRef[];
+}
+
+/** How far an incremental garbage collection has got. */
+export interface GcProgress {
+  /** The collection is over once the phase is `"done"`. */
+  phase: "mark" | "sweep" | "done";
+  /** Chunks found reachable so far. */
+  markedChunks: number;
+  /** Chunks queued for marking. */
+  pendingChunks: number;
+  /** Chunks the sweep has examined. */
+  sweptChunks: number;
+  /**
+   * Chunks the sweep will examine in total; 0 until the sweep starts. An estimate while
+   * the sweep runs, since writes add chunks, and exact once the phase is "done".
+   */
+  sweepTotal: number;
+  /** Chunks deleted so far. */
+  collectedChunks: number;
+}
+
+/** A chunk that a planned garbage collection would delete. */
+export interface DeadChunk {
+  hash: Uint8Array;
+  /** Size of the chunk in bytes. */
+  size: number;
+  /** Whether the chunk holds a tree node or value data. */
+  kind: "node" | "value";
+}
+
+/** The result of a garbage collection dry run (`planGc`), executable with `executeGcPlan`. */
+export interface GcPlan {
+  /** The roots kept alive, including the tree's root and pinned roots when the plan was made. */
+  liveRoots: Uint8Array[];
+  /** The store's write generation when the plan was made; chunks written since are never deleted. */
+  generation: number | null;
+  /** Number of chunks reachable from `liveRoots`. */
+  liveChunks: number;
+  /** The chunks to delete, sorted by hash. */
+  deadChunks: DeadChunk[];
+  nodeChunks: number;
+  nodeBytes: number;
+  valueChunks: number;
+  valueBytes: number;
+}
+
+/** The `changedRanges` method resolves to the differing ranges in key order. */
+export type ChangedRangesFnReturn = ChangedRange[];
 /** The `triggerGc` method resolves to the number of chunks garbage collected. */
 export type TriggerGcFnReturn = number;
+/** The `pinRoot` method resolves once the root is pinned. */
+export type PinRootFnReturn = void;
+/** The `unpinRoot` method resolves once one pin on the root is released. */
+export type UnpinRootFnReturn = void;
+/** The `getPinnedRoots` method resolves to the roots with at least one pin. */
+export type GetPinnedRootsFnReturn = Uint8Array[];
+/** The `compact` method resolves to a new tree holding only the live chunks. */
+export type CompactFnReturn = PTree;
+/** The `startIncrementalGc` method resolves to the progress of the new collection. */
+export type StartIncrementalGcFnReturn = GcProgress;
+/** The `gcStep` method resolves to the progress of the incremental collection. */
+export type GcStepFnReturn = GcProgress;
+/** The `planGc` method resolves to what a collection would delete. */
+export type PlanGcFnReturn = GcPlan;
+/** The `executeGcPlan` method resolves to the number of chunks deleted. */
+export type ExecuteGcPlanFnReturn = number;
 /** The `getTreeConfig` method resolves to the tree's current configuration. */
 export type GetTreeConfigFnReturn = TreeConfigOptions;
+/** The `getNodeCacheStats` method resolves to the decoded-node cache counters. */
+export type GetNodeCacheStatsFnReturn = NodeCacheStats;
 /** The `scanItems` method resolves to a page of scanned items. */
 export type ScanItemsFnReturn = IScanPage;
 /** The `scanItemsSync` method synchronously returns a page of scanned items. */
@@ -115,7 +343,7 @@ export interface ChangeEvent {
   /** The root hash of the tree *after* the operation. */
   newRootHash: Uint8Array | null;
   /** The type of operation that triggered the change. */
-  type: "insert" | "delete" | "insertBatch" | "checkout";
+  type: "insert" | "delete" | "insertBatch" | "checkout" | "applyDiff" | "importPatch" | "importDelta";
 }
 
 /**
@@ -126,6 +354,16 @@ export type CursorNextReturn =
   | { done: false; value: [Uint8Array, Uint8Array] }
   | { done: true; value?: undefined };
 
+/**
+ * The resolved value of the `PDiffStream.next()` method.
+ * It's an object indicating if the diff is exhausted, and if not, the next change in key order.
+ */
+export type DiffStreamNextReturn =
+  | { done: false; value: DiffEntry }
+  | { done: true; value?: undefined };
+/** The `PDiffStream.nextPage` method resolves to the next changes; an empty array once the diff is exhausted. */
+export type DiffStreamNextPageReturn = DiffEntry[];
+
 // --- Added for Hierarchy Scan ---
 export interface HierarchyScanOptions {
   startKey?: Uint8Array;
@@ -157,6 +395,7 @@ export type HierarchyItem =
       type: "InternalEntry";
       parentHash: Uint8Array;
       entryIndex: number;
+      /** Upper bound for keys in the child subtree. Shortened to a separator, so it may not be a stored key. */
       boundaryKey: Uint8Array;
       childHash: Uint8Array;
       numItemsSubtree: number;
@@ -24,18 +24,95 @@ pub trait ChunkStore: Send + Sync + std::fmt::Debug + 'static {
         self.get(hash).await.map(|opt| opt.is_some())
     }
 
+    /// Retrieves several chunks at once, returning one entry per hash in the same order
+    /// (`None` for chunks that are not found). Stores with per-call overhead should override
+    /// the default, which calls `get` once per hash.
+    async fn get_many(&self, hashes: &[Hash]) -> Result<Vec<Option<Vec<u8>>>> {
+        let mut chunks = Vec::with_capacity(hashes.len());
+        for hash in hashes {
+            chunks.push(self.get(hash).await?);
+        }
+        Ok(chunks)
+    }
+
+    /// Stores several chunks at once and returns their hashes in the same order.
+    /// The default implementation calls `put` once per chunk.
+    async fn put_many(&self, chunks: Vec<Vec<u8>>) -> Result<Vec<Hash>> {
+        let mut hashes = Vec::with_capacity(chunks.len());
+        for bytes in chunks {
+            hashes.push(self.put(bytes).await?);
+        }
+        Ok(hashes)
+    }
+
+    /// Checks several hashes at once, returning one flag per hash in the same order.
+    /// The default implementation calls `exists` once per hash.
+    async fn exists_many(&self, hashes: &[Hash]) -> Result<Vec<bool>> {
+        let mut found = Vec::with_capacity(hashes.len());
+        for hash in hashes {
+            found.push(self.exists(hash).await?);
+        }
+        Ok(found)
+    }
+
     /// Deletes a batch of chunks identified by their hashes.
     /// This is primarily intended for use by a garbage collection process.
     /// The method should succeed even if some of the provided hashes are not found.
     async fn delete_batch(&self, hashes: &[Hash]) -> Result<()>;
 
     /// Retrieves all unique chunk hashes currently present in the store.
-    /// This is used by the garbage collector to know the entire set of potentially collectible chunks.
-    /// Note: Depending on the store's size, this could return a large vector.
-    /// For very large stores, an asynchronous iterator might be more memory-efficient,
-    /// but Vec<Hash> is simpler for now.
+    /// Note: Depending on the store's size, this could return a large vector; the garbage
+    /// collector enumerates the store with `hashes_page` instead.
     async fn all_hashes(&self) -> Result<Vec<Hash>>;
 
+    /// Returns the number of chunks in the store. The default implementation counts
+    /// `all_hashes`; stores that know their size should override it.
+    async fn chunk_count(&self) -> Result<u64> {
+        Ok(self.all_hashes().await?.len() as u64)
+    }
+
+    /// Returns up to `limit` hashes of chunks in the store, in ascending order, starting
+    /// after `after` (or from the smallest hash when `None`). A page shorter than `limit`
+    /// is the last one. Lets callers such as the garbage collector enumerate the store
+    /// without holding every hash in memory, so stores that support collection must
+    /// implement it; the default implementation fails.
+    async fn hashes_page(&self, _after: Option<&Hash>, _limit: usize) -> Result<Vec<Hash>> {
+        Err(ProllyError::InvalidOperation(
+            "This store does not support paging through its hashes.".to_string(),
+        ))
+    }
+
+    /// Starts a garbage collection write barrier and returns its generation, or `None` if the
+    /// store does not track writes, in which case it cannot be garbage collected. Every chunk put after this call, including a put of a
+    /// chunk the store already holds, must count as put since the returned generation, and
+    /// so must every chunk put by a write registered with `begin_write` that has not ended.
+    async fn write_generation(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Registers a write that is about to put chunks and returns its id, or `None` if the
+    /// store does not track writes. Until the matching `end_write`, barriers from
+    /// `write_generation` also protect the chunks the write put before the barrier, which
+    /// nothing marked may reference yet. A write that is never ended only makes later
+    /// collections more conservative.
+    fn begin_write(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Ends a write registered with `begin_write`.
+    fn end_write(&self, _write_id: u64) -> Result<()> {
+        Ok(())
+    }
+
+    /// Deletes the chunks in `hashes` that have not been put since `generation` (a value
+    /// returned by `write_generation`), atomically with respect to concurrent puts.
+    /// Returns the number of chunks deleted.
+    async fn delete_batch_unless_put_since(&self, _hashes: &[Hash], _generation: u64) -> Result<usize> {
+        Err(ProllyError::InvalidOperation(
+            "This store does not track write generations.".to_string(),
+        ))
+    }
+
     /// Synchronously retrieves a chunk by its hash.
     /// This is a non-blocking operation that will fail if the underlying
     /// store cannot be accessed without blocking.
@@ -63,4 +140,51 @@ pub trait ChunkStore: Send + Sync + std::fmt::Debug + 'static {
     // Future considerations:
     // async fn delete(&self, hash: &Hash) -> Result<()>; // Old single delete, now covered by delete_batch
     // async fn flush(&self) -> Result<()>; // If the store buffers writes
-}
\ No newline at end of file
+}
+
+/// Checks that a batch call such as `get_many` returned one result per item, as the trait
+/// requires, so that zipping the results with the items cannot drop or shift any.
+pub(crate) fn check_batch_len<T>(method: &str, items: usize, results: Vec<T>) -> Result<Vec<T>> {
+    if results.len() != items {
+        return Err(ProllyError::StorageError(format!(
+            "{} returned {} results for {} items", method, results.len(), items
+        )));
+    }
+    Ok(results)
+}
+
+/// Walks the hashes of a store page by page with `ChunkStore::hashes_page`.
+#[derive(Debug, Clone, Default)]
+pub struct HashPager {
+    after: Option<Hash>,
+    done: bool,
+}
+
+impl HashPager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the last page has been returned.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Returns the next page of up to `limit` hashes, or `None` once the store is exhausted.
+    /// Chunks deleted or added behind the pager do not affect later pages.
+    pub async fn next_page<S: ChunkStore + ?Sized>(&mut self, store: &S, limit: usize) -> Result<Option<Vec<Hash>>> {
+        if self.done {
+            return Ok(None);
+        }
+        let limit = limit.max(1);
+        let page = store.hashes_page(self.after.as_ref(), limit).await?;
+        self.done = page.len() < limit;
+        match page.last() {
+            Some(last) => {
+                self.after = Some(*last);
+                Ok(Some(page))
+            }
+            None => Ok(None),
+        }
+    }
+}
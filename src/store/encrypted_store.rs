@@ -0,0 +1,383 @@
+// prolly-rust/src/store/encrypted_store.rs
+
+//! A `ChunkStore` wrapper that encrypts every chunk before it reaches the underlying store.
+//!
+//! Chunks are sealed with XChaCha20-Poly1305. The wrapped store only ever sees ciphertext,
+//! and chunks are addressed by the hash of that ciphertext (the hash returned from `put`),
+//! so neither keys nor values leak through the addresses either.
+//!
+//! Operations that copy chunks between stores by hash (`ProllyTree::sync_to`,
+//! `ProllyTree::import_delta` and `compact_into`) check that the receiving store puts each
+//! chunk under the hash it had in the sending one. That only holds between stores that
+//! address chunks the same way: plain content-addressed stores, or convergent
+//! `EncryptedStore`s with the same key. Copying into a random-nonce store, or between a
+//! plain and an encrypted store, is rejected with `ProllyError::ChecksumMismatch`.
+
+use async_recursion::async_recursion;
+use async_trait::async_trait;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key as CipherKey, XChaCha20Poly1305, XNonce};
+use log::trace;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::common::Hash;
+use crate::error::{Result, ProllyError};
+use crate::node::definition::{Node, ValueRepr};
+use super::chunk_store::ChunkStore;
+
+const ENVELOPE_VERSION: u8 = 0x01;
+const MODE_TAG_CONVERGENT: u8 = 0x01;
+const MODE_TAG_RANDOM_NONCE: u8 = 0x02;
+
+const NONCE_SIZE: usize = 24;
+const KEY_SIZE: usize = 32;
+const TAG_SIZE: usize = 16;
+const HEADER_SIZE: usize = 2;
+
+const CONTENT_KDF_CONTEXT: &str = "prolly-rust 2025 encrypted-store content-key";
+const WRAP_KDF_CONTEXT: &str = "prolly-rust 2025 encrypted-store wrap-key";
+const DATA_KDF_CONTEXT: &str = "prolly-rust 2025 encrypted-store data-key";
+
+/// How chunk plaintexts are turned into ciphertexts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionMode {
+    /// Each chunk is encrypted under a key derived from its own content (keyed by the
+    /// master key). Identical plaintexts produce identical ciphertexts, so deduplication
+    /// in the underlying store is preserved.
+    Convergent,
+    /// Each chunk is encrypted under the master key with a fresh random nonce.
+    /// Hides equality between chunks at the cost of deduplication.
+    RandomNonce,
+}
+
+/// Subkeys derived from a 32-byte master key.
+struct KeyMaterial {
+    /// Keys the derivation of per-chunk content keys in convergent mode.
+    content_kdf_key: [u8; KEY_SIZE],
+    /// Encrypts per-chunk content keys in convergent mode.
+    wrap_cipher: XChaCha20Poly1305,
+    /// Encrypts chunk data in random-nonce mode.
+    data_cipher: XChaCha20Poly1305,
+}
+
+impl KeyMaterial {
+    fn derive(master_key: &[u8; KEY_SIZE]) -> Self {
+        let wrap_key = blake3::derive_key(WRAP_KDF_CONTEXT, master_key);
+        let data_key = blake3::derive_key(DATA_KDF_CONTEXT, master_key);
+        Self {
+            content_kdf_key: blake3::derive_key(CONTENT_KDF_CONTEXT, master_key),
+            wrap_cipher: XChaCha20Poly1305::new(CipherKey::from_slice(&wrap_key)),
+            data_cipher: XChaCha20Poly1305::new(CipherKey::from_slice(&data_key)),
+        }
+    }
+
+    /// Encrypts `plaintext` into a self-describing envelope:
+    ///
+    /// * Convergent: `[version | mode | key_nonce(24) | wrapped_content_key(48) | ciphertext+tag]`
+    /// * Random nonce: `[version | mode | nonce(24) | ciphertext+tag]`
+    fn seal(&self, mode: EncryptionMode, plaintext: &[u8]) -> Result<Vec<u8>> {
+        match mode {
+            EncryptionMode::Convergent => {
+                let header = [ENVELOPE_VERSION, MODE_TAG_CONVERGENT];
+                let content_key: [u8; KEY_SIZE] = *blake3::keyed_hash(&self.content_kdf_key, plaintext).as_bytes();
+                // The key nonce is derived from the content key so the whole envelope is deterministic.
+                let key_nonce_full = blake3::keyed_hash(&self.content_kdf_key, &content_key);
+                let key_nonce = XNonce::from_slice(&key_nonce_full.as_bytes()[..NONCE_SIZE]);
+
+                let wrapped_key = self.wrap_cipher
+                    .encrypt(key_nonce, Payload { msg: &content_key, aad: &header })
+                    .map_err(|e| ProllyError::EncryptionError(format!("Failed to wrap content key: {}", e)))?;
+                // Every content key encrypts exactly one plaintext, so a fixed nonce is safe here.
+                let content_cipher = XChaCha20Poly1305::new(CipherKey::from_slice(&content_key));
+                let body = content_cipher
+                    .encrypt(&XNonce::default(), Payload { msg: plaintext, aad: &header })
+                    .map_err(|e| ProllyError::EncryptionError(format!("Failed to encrypt chunk: {}", e)))?;
+
+                let mut envelope = Vec::with_capacity(HEADER_SIZE + NONCE_SIZE + wrapped_key.len() + body.len());
+                envelope.extend_from_slice(&header);
+                envelope.extend_from_slice(key_nonce);
+                envelope.extend_from_slice(&wrapped_key);
+                envelope.extend_from_slice(&body);
+                Ok(envelope)
+            }
+            EncryptionMode::RandomNonce => {
+                let header = [ENVELOPE_VERSION, MODE_TAG_RANDOM_NONCE];
+                let mut nonce_bytes = [0u8; NONCE_SIZE];
+                getrandom::getrandom(&mut nonce_bytes)
+                    .map_err(|e| ProllyError::EncryptionError(format!("Failed to generate nonce: {}", e)))?;
+                let body = self.data_cipher
+                    .encrypt(XNonce::from_slice(&nonce_bytes), Payload { msg: plaintext, aad: &header })
+                    .map_err(|e| ProllyError::EncryptionError(format!("Failed to encrypt chunk: {}", e)))?;
+
+                let mut envelope = Vec::with_capacity(HEADER_SIZE + NONCE_SIZE + body.len());
+                envelope.extend_from_slice(&header);
+                envelope.extend_from_slice(&nonce_bytes);
+                envelope.extend_from_slice(&body);
+                Ok(envelope)
+            }
+        }
+    }
+
+    /// Decrypts an envelope produced by `seal`. The mode is read from the envelope,
+    /// so chunks written in either mode can be read back regardless of the store's current mode.
+    fn open(&self, envelope: &[u8]) -> Result<Vec<u8>> {
+        if envelope.len() < HEADER_SIZE + NONCE_SIZE + TAG_SIZE {
+            return Err(ProllyError::EncryptionError("Encrypted chunk is too short".to_string()));
+        }
+        let header = &envelope[..HEADER_SIZE];
+        if header[0] != ENVELOPE_VERSION {
+            return Err(ProllyError::EncryptionError(format!("Unsupported encrypted chunk version: {}", header[0])));
+        }
+        let nonce = XNonce::from_slice(&envelope[HEADER_SIZE..HEADER_SIZE + NONCE_SIZE]);
+        let rest = &envelope[HEADER_SIZE + NONCE_SIZE..];
+
+        match header[1] {
+            MODE_TAG_CONVERGENT => {
+                let wrapped_len = KEY_SIZE + TAG_SIZE;
+                if rest.len() < wrapped_len + TAG_SIZE {
+                    return Err(ProllyError::EncryptionError("Convergent chunk is too short".to_string()));
+                }
+                let content_key = self.wrap_cipher
+                    .decrypt(nonce, Payload { msg: &rest[..wrapped_len], aad: header })
+                    .map_err(|_| ProllyError::EncryptionError("Failed to unwrap content key (wrong key or tampered chunk)".to_string()))?;
+                let content_cipher = XChaCha20Poly1305::new(CipherKey::from_slice(&content_key));
+                let plaintext = content_cipher
+                    .decrypt(&XNonce::default(), Payload { msg: &rest[wrapped_len..], aad: header })
+                    .map_err(|_| ProllyError::EncryptionError("Failed to decrypt chunk (tampered chunk)".to_string()))?;
+                // The content key must be the one derived from the plaintext, otherwise the
+                // envelope was assembled from mismatched parts.
+                if blake3::keyed_hash(&self.content_kdf_key, &plaintext).as_bytes()[..] != content_key[..] {
+                    return Err(ProllyError::EncryptionError("Convergent content key does not match chunk content".to_string()));
+                }
+                Ok(plaintext)
+            }
+            MODE_TAG_RANDOM_NONCE => self.data_cipher
+                .decrypt(nonce, Payload { msg: rest, aad: header })
+                .map_err(|_| ProllyError::EncryptionError("Failed to decrypt chunk (wrong key or tampered chunk)".to_string())),
+            other => Err(ProllyError::EncryptionError(format!("Unknown encryption mode tag: {}", other))),
+        }
+    }
+}
+
+/// A `ChunkStore` that encrypts chunk bytes with an authenticated cipher before
+/// handing them to an inner store, and decrypts them on the way back out.
+///
+/// Chunk hashes returned from `put` are the inner store's hashes of the *ciphertext*,
+/// and tree nodes reference children by those hashes. Consequently rotating the key
+/// re-encrypts every reachable chunk and yields new root hashes (see `rotate_key`).
+pub struct EncryptedStore<S: ChunkStore> {
+    inner: Arc<S>,
+    mode: EncryptionMode,
+    keys: RwLock<Arc<KeyMaterial>>,
+}
+
+impl<S: ChunkStore> std::fmt::Debug for EncryptedStore<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print key material.
+        f.debug_struct("EncryptedStore")
+            .field("inner", &self.inner)
+            .field("mode", &self.mode)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: ChunkStore> EncryptedStore<S> {
+    /// Wraps `inner`, encrypting all chunks with keys derived from `master_key`.
+    pub fn new(inner: Arc<S>, master_key: [u8; KEY_SIZE], mode: EncryptionMode) -> Self {
+        Self {
+            inner,
+            mode,
+            keys: RwLock::new(Arc::new(KeyMaterial::derive(&master_key))),
+        }
+    }
+
+    /// The wrapped store holding the ciphertext.
+    pub fn inner(&self) -> &Arc<S> {
+        &self.inner
+    }
+
+    pub fn mode(&self) -> EncryptionMode {
+        self.mode
+    }
+
+    fn current_keys(&self) -> Result<Arc<KeyMaterial>> {
+        self.keys.read()
+            .map(|guard| Arc::clone(&guard))
+            .map_err(|_| ProllyError::InternalError("EncryptedStore key lock poisoned".to_string()))
+    }
+
+    /// Re-encrypts every chunk reachable from `live_root_hashes` under `new_master_key`
+    /// and makes the new key current.
+    ///
+    /// Because chunks are addressed by ciphertext hash, every re-encrypted node is rewritten
+    /// with its children's new hashes. Returns the new root hashes in the same order as
+    /// `live_root_hashes`. Chunks encrypted under the old key are left in the inner store
+    /// and become unreadable once the key is swapped; run GC with the returned roots to
+    /// reclaim them. Writers should be paused while rotation runs.
+    pub async fn rotate_key(&self, new_master_key: [u8; KEY_SIZE], live_root_hashes: &[Hash]) -> Result<Vec<Hash>> {
+        let old_keys = self.current_keys()?;
+        let new_keys = Arc::new(KeyMaterial::derive(&new_master_key));
+        let mut remapped: HashMap<Hash, Hash> = HashMap::new();
+        let mut new_roots = Vec::with_capacity(live_root_hashes.len());
+
+        for root_hash in live_root_hashes {
+            let new_root = self.reencrypt_node(*root_hash, &old_keys, &new_keys, &mut remapped).await?;
+            new_roots.push(new_root);
+        }
+        trace!("EncryptedStore: re-encrypted {} chunks during key rotation.", remapped.len());
+
+        let mut guard = self.keys.write()
+            .map_err(|_| ProllyError::InternalError("EncryptedStore key lock poisoned".to_string()))?;
+        *guard = new_keys;
+        Ok(new_roots)
+    }
+
+    #[async_recursion]
+    async fn reencrypt_node(
+        &self,
+        hash: Hash,
+        old_keys: &Arc<KeyMaterial>,
+        new_keys: &Arc<KeyMaterial>,
+        remapped: &mut HashMap<Hash, Hash>,
+    ) -> Result<Hash> {
+        if let Some(new_hash) = remapped.get(&hash) {
+            return Ok(*new_hash);
+        }
+        let envelope = self.inner.get(&hash).await?.ok_or(ProllyError::ChunkNotFound(hash))?;
+        let mut node = Node::decode(&old_keys.open(&envelope)?)?;
+
+        match &mut node {
+            Node::Leaf { entries, .. } => {
+                for entry in entries.iter_mut() {
+                    match &mut entry.value {
+                        ValueRepr::Inline(_) => {}
+                        ValueRepr::Chunked(data_hash) => {
+                            *data_hash = self.reencrypt_value_chunk(*data_hash, old_keys, new_keys, remapped).await?;
+                        }
+                        ValueRepr::ChunkedSequence { chunk_hashes, .. } => {
+                            for data_hash in chunk_hashes.iter_mut() {
+                                *data_hash = self.reencrypt_value_chunk(*data_hash, old_keys, new_keys, remapped).await?;
+                            }
+                        }
+                    }
+                }
+            }
+            Node::Internal { children, .. } => {
+                for child in children.iter_mut() {
+                    child.child_hash = self.reencrypt_node(child.child_hash, old_keys, new_keys, remapped).await?;
+                }
+            }
+        }
+
+        let new_hash = self.inner.put(new_keys.seal(self.mode, &node.encode()?)?).await?;
+        remapped.insert(hash, new_hash);
+        Ok(new_hash)
+    }
+
+    async fn reencrypt_value_chunk(
+        &self,
+        hash: Hash,
+        old_keys: &Arc<KeyMaterial>,
+        new_keys: &Arc<KeyMaterial>,
+        remapped: &mut HashMap<Hash, Hash>,
+    ) -> Result<Hash> {
+        if let Some(new_hash) = remapped.get(&hash) {
+            return Ok(*new_hash);
+        }
+        let envelope = self.inner.get(&hash).await?.ok_or(ProllyError::ChunkNotFound(hash))?;
+        let plaintext = old_keys.open(&envelope)?;
+        let new_hash = self.inner.put(new_keys.seal(self.mode, &plaintext)?).await?;
+        remapped.insert(hash, new_hash);
+        Ok(new_hash)
+    }
+}
+
+#[async_trait]
+impl<S: ChunkStore> ChunkStore for EncryptedStore<S> {
+    async fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>> {
+        match self.inner.get(hash).await? {
+            Some(envelope) => self.current_keys()?.open(&envelope).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn put(&self, bytes: Vec<u8>) -> Result<Hash> {
+        let envelope = self.current_keys()?.seal(self.mode, &bytes)?;
+        self.inner.put(envelope).await
+    }
+
+    async fn exists(&self, hash: &Hash) -> Result<bool> {
+        self.inner.exists(hash).await
+    }
+
+    async fn get_many(&self, hashes: &[Hash]) -> Result<Vec<Option<Vec<u8>>>> {
+        let envelopes = self.inner.get_many(hashes).await?;
+        let keys = self.current_keys()?;
+        envelopes
+            .into_iter()
+            .map(|envelope| envelope.map(|envelope| keys.open(&envelope)).transpose())
+            .collect()
+    }
+
+    async fn put_many(&self, chunks: Vec<Vec<u8>>) -> Result<Vec<Hash>> {
+        let keys = self.current_keys()?;
+        let envelopes = chunks
+            .iter()
+            .map(|bytes| keys.seal(self.mode, bytes))
+            .collect::<Result<Vec<_>>>()?;
+        self.inner.put_many(envelopes).await
+    }
+
+    async fn exists_many(&self, hashes: &[Hash]) -> Result<Vec<bool>> {
+        self.inner.exists_many(hashes).await
+    }
+
+    async fn delete_batch(&self, hashes: &[Hash]) -> Result<()> {
+        self.inner.delete_batch(hashes).await
+    }
+
+    async fn all_hashes(&self) -> Result<Vec<Hash>> {
+        self.inner.all_hashes().await
+    }
+
+    async fn chunk_count(&self) -> Result<u64> {
+        self.inner.chunk_count().await
+    }
+
+    async fn hashes_page(&self, after: Option<&Hash>, limit: usize) -> Result<Vec<Hash>> {
+        self.inner.hashes_page(after, limit).await
+    }
+
+    async fn write_generation(&self) -> Result<Option<u64>> {
+        self.inner.write_generation().await
+    }
+
+    fn begin_write(&self) -> Result<Option<u64>> {
+        self.inner.begin_write()
+    }
+
+    fn end_write(&self, write_id: u64) -> Result<()> {
+        self.inner.end_write(write_id)
+    }
+
+    async fn delete_batch_unless_put_since(&self, hashes: &[Hash], generation: u64) -> Result<usize> {
+        self.inner.delete_batch_unless_put_since(hashes, generation).await
+    }
+
+    fn get_sync(&self, hash: &Hash) -> Result<Option<Vec<u8>>> {
+        match self.inner.get_sync(hash)? {
+            Some(envelope) => self.current_keys()?.open(&envelope).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn put_sync(&self, bytes: Vec<u8>) -> Result<Hash> {
+        let envelope = self.current_keys()?.seal(self.mode, &bytes)?;
+        self.inner.put_sync(envelope)
+    }
+
+    fn delete_batch_sync(&self, hashes: &[Hash]) -> Result<()> {
+        self.inner.delete_batch_sync(hashes)
+    }
+}
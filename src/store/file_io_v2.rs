@@ -1,7 +1,7 @@
 // packages/prolly-rust/src/store/file_io_v2.rs
 use super::format_v2::{
-    FileHeaderV2, ChunkIndexEntryV2, MetadataContentV2, EofBlockV2, ContentBlockEnvelope,
-    FILE_SIGNATURE_V2, FORMAT_VERSION_V2, CHUNK_HASH_SIZE,
+    FileHeaderV2, ChunkIndexEntryV2, MetadataContentV2, DeltaBaseV2, EofBlockV2, ContentBlockEnvelope,
+    FILE_SIGNATURE_V2, DELTA_SIGNATURE_V2, FORMAT_VERSION_V2, CHUNK_HASH_SIZE,
     TAG_CHUNK_INDEX_BLOCK, TAG_METADATA_BLOCK, TAG_EOF_BLOCK,
     calculate_crc32, calculate_blake3_hash
 };
@@ -17,11 +17,63 @@ use serde_json;
 // CONFIGURATION - Set to true to enable overall file checksum
 const ENABLE_OVERALL_FILE_CHECKSUM: bool = true;
 
+/// Chunk bytes by hash, as stored in a PRLY v2 file.
+type ChunkMap = HashMap<[u8; CHUNK_HASH_SIZE], Vec<u8>>;
+
+/// The chunks a store holding `base_root` needs to check out `target_root`, as written to a
+/// delta bundle by `write_prly_delta_v2`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeltaBundleV2 {
+    pub base_root: Option<[u8; CHUNK_HASH_SIZE]>,
+    pub target_root: Option<[u8; CHUNK_HASH_SIZE]>,
+    pub chunks: ChunkMap,
+}
+
 pub fn write_prly_tree_v2(
     root_hash: Option<[u8; CHUNK_HASH_SIZE]>,
     tree_config: &TreeConfig,
     chunks: &HashMap<[u8; CHUNK_HASH_SIZE], Vec<u8>>,
     description: Option<String>,
+    retained_roots: Vec<[u8; CHUNK_HASH_SIZE]>,
+) -> Result<Vec<u8>, ProllyError> {
+    let metadata = MetadataContentV2 {
+        root_hash,
+        tree_config: tree_config.clone(),
+        created_at: Utc::now().to_rfc3339(),
+        description,
+        total_chunk_data_bytes: 0,
+        retained_roots,
+        delta_base: None,
+    };
+    write_prly_file_v2(FILE_SIGNATURE_V2, metadata, chunks)
+}
+
+/// Writes a delta bundle: a PRLY v2 file signed `DELTA_SIGNATURE_V2` holding only the
+/// bundle's chunks, whose metadata records the target root as `root_hash` and the base root
+/// as `delta_base`.
+pub fn write_prly_delta_v2(
+    bundle: &DeltaBundleV2,
+    tree_config: &TreeConfig,
+    description: Option<String>,
+) -> Result<Vec<u8>, ProllyError> {
+    let metadata = MetadataContentV2 {
+        root_hash: bundle.target_root,
+        tree_config: tree_config.clone(),
+        created_at: Utc::now().to_rfc3339(),
+        description,
+        total_chunk_data_bytes: 0,
+        retained_roots: Vec::from_iter(bundle.target_root),
+        delta_base: Some(DeltaBaseV2 { root_hash: bundle.base_root }),
+    };
+    write_prly_file_v2(DELTA_SIGNATURE_V2, metadata, &bundle.chunks)
+}
+
+/// Writes `chunks` and `metadata` (whose `total_chunk_data_bytes` is filled in) in the PRLY v2
+/// layout, under `signature`.
+fn write_prly_file_v2(
+    signature: &[u8; 8],
+    mut metadata_content: MetadataContentV2,
+    chunks: &HashMap<[u8; CHUNK_HASH_SIZE], Vec<u8>>,
 ) -> Result<Vec<u8>, ProllyError> {
     // This buffer will become the final file content.
     // Initialize with space for the header.
@@ -78,13 +130,7 @@ pub fn write_prly_tree_v2(
         buffer_writer.write_all(&chunk_index_content_bytes)?;
 
         // 3. Prepare and Write Metadata Block
-        let metadata_content = MetadataContentV2 {
-            root_hash,
-            tree_config: tree_config.clone(),
-            created_at: Utc::now().to_rfc3339(),
-            description,
-            total_chunk_data_bytes,
-        };
+        metadata_content.total_chunk_data_bytes = total_chunk_data_bytes;
         let metadata_json_bytes = serde_json::to_vec(&metadata_content)
             .map_err(|e| ProllyError::Serialization(e.to_string()))?;
         let metadata_checksum = calculate_crc32(&metadata_json_bytes);
@@ -101,11 +147,14 @@ pub fn write_prly_tree_v2(
         offset_eof_block_begins = buffer_writer.position();
 
         // 4. Write the Finalized Header into the beginning of file_buffer
-        let final_header = FileHeaderV2::new(
-            offset_metadata_block_envelope_start,
-            offset_chunk_index_block_envelope_start,
-            offset_eof_block_begins,
-        );
+        let final_header = FileHeaderV2 {
+            signature: *signature,
+            ..FileHeaderV2::new(
+                offset_metadata_block_envelope_start,
+                offset_chunk_index_block_envelope_start,
+                offset_eof_block_begins,
+            )
+        };
         let original_cursor_pos = buffer_writer.position(); // Should be offset_eof_block_begins
         buffer_writer.seek(SeekFrom::Start(0))?;
         final_header.write_to(&mut buffer_writer)?;
@@ -129,7 +178,7 @@ pub fn write_prly_tree_v2(
     };
 
     // 6. Prepare EOF Block
-    let mut reversed_signature = *FILE_SIGNATURE_V2;
+    let mut reversed_signature = *signature;
     reversed_signature.reverse();
     let eof_block = EofBlockV2 {
         tag: TAG_EOF_BLOCK,
@@ -161,15 +210,47 @@ pub fn write_prly_tree_v2(
 // No changes needed for read_prly_tree_v2 for this specific issue,
 // as its checksum verification logic was likely correct, assuming the
 // written file was correct.
+/// Reads a tree file, returning its root, config, chunks, description and retained roots
+/// (empty for files written before roots were recorded).
 pub fn read_prly_tree_v2(
     file_bytes: &[u8],
-) -> Result<(Option<[u8; CHUNK_HASH_SIZE]>, TreeConfig, HashMap<[u8; CHUNK_HASH_SIZE], Vec<u8>>, Option<String>), ProllyError> {
+) -> Result<(Option<[u8; CHUNK_HASH_SIZE]>, TreeConfig, HashMap<[u8; CHUNK_HASH_SIZE], Vec<u8>>, Option<String>, Vec<[u8; CHUNK_HASH_SIZE]>), ProllyError> {
+    let (metadata_content, chunks_map) = read_prly_file_v2(file_bytes)?;
+    if metadata_content.delta_base.is_some() {
+        return Err(ProllyError::InvalidFileFormat("File is a delta bundle; import it into a tree holding its base instead".into()));
+    }
+    Ok((
+        metadata_content.root_hash,
+        metadata_content.tree_config,
+        chunks_map,
+        metadata_content.description,
+        metadata_content.retained_roots,
+    ))
+}
+
+/// Reads a delta bundle written by `write_prly_delta_v2`. Chunk hashes are not verified here.
+pub fn read_prly_delta_v2(
+    file_bytes: &[u8],
+) -> Result<(DeltaBundleV2, TreeConfig, Option<String>), ProllyError> {
+    let (metadata_content, chunks) = read_prly_file_v2(file_bytes)?;
+    let Some(delta_base) = metadata_content.delta_base else {
+        return Err(ProllyError::InvalidFileFormat("File is a full tree, not a delta bundle".into()));
+    };
+    let bundle = DeltaBundleV2 { base_root: delta_base.root_hash, target_root: metadata_content.root_hash, chunks };
+    Ok((bundle, metadata_content.tree_config, metadata_content.description))
+}
+
+/// Reads and verifies a PRLY v2 file, returning its metadata and chunks. The file is a delta
+/// bundle exactly when it is signed `DELTA_SIGNATURE_V2`, which its metadata must agree with.
+fn read_prly_file_v2(
+    file_bytes: &[u8],
+) -> Result<(MetadataContentV2, ChunkMap), ProllyError> {
     // ... (existing read logic remains the same) ...
     let mut reader = Cursor::new(file_bytes);
 
     let header = FileHeaderV2::read_from(&mut reader)
         .map_err(|e| ProllyError::InvalidFileFormat(format!("Failed to read header: {}", e)))?;
-    if &header.signature != FILE_SIGNATURE_V2 {
+    if &header.signature != FILE_SIGNATURE_V2 && &header.signature != DELTA_SIGNATURE_V2 {
         return Err(ProllyError::InvalidFileFormat("Invalid signature".into()));
     }
     if header.version != FORMAT_VERSION_V2 {
@@ -217,6 +298,9 @@ pub fn read_prly_tree_v2(
     }
     let metadata_content: MetadataContentV2 = serde_json::from_slice(&metadata_json_bytes)
         .map_err(|e| ProllyError::Deserialization(e.to_string()))?;
+    if metadata_content.delta_base.is_some() != (&header.signature == DELTA_SIGNATURE_V2) {
+        return Err(ProllyError::InvalidFileFormat("File signature does not match whether its metadata describes a delta bundle".into()));
+    }
 
 
     let mut chunks_map = HashMap::new();
@@ -243,7 +327,7 @@ pub fn read_prly_tree_v2(
         return Err(ProllyError::InvalidFileFormat("EOF Block tag mismatch.".into()));
     }
 
-    let mut expected_reversed_sig = *FILE_SIGNATURE_V2;
+    let mut expected_reversed_sig = header.signature;
     expected_reversed_sig.reverse();
     if eof_block.signature_repeated != expected_reversed_sig {
         return Err(ProllyError::InvalidFileFormat("EOF block reversed signature mismatch.".into()));
@@ -273,10 +357,5 @@ pub fn read_prly_tree_v2(
     }
 
 
-    Ok((
-        metadata_content.root_hash,
-        metadata_content.tree_config,
-        chunks_map,
-        metadata_content.description,
-    ))
+    Ok((metadata_content, chunks_map))
 }
\ No newline at end of file
@@ -7,6 +7,9 @@ use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
 // --- Constants ---
 pub const FILE_SIGNATURE_V2: &[u8; 8] = b"PRLYTRV2";
+/// Signature of delta bundles. They share the v2 layout, but readers that predate them must
+/// not load one as a full tree, so they are told apart by signature rather than by metadata.
+pub const DELTA_SIGNATURE_V2: &[u8; 8] = b"PRLYDLV2";
 pub const FORMAT_VERSION_V2: u8 = 0x02;
 
 pub const TAG_CHUNK_INDEX_BLOCK: u8 = 0x20;
@@ -121,6 +124,19 @@ pub struct MetadataContentV2 {
     pub created_at: String,
     pub description: Option<String>,
     pub total_chunk_data_bytes: u64,
+    /// Every root whose chunks the file holds, including `root_hash`. Files written before
+    /// roots were recorded leave this empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retained_roots: Vec<[u8; CHUNK_HASH_SIZE]>,
+    /// Set for delta bundles, which only hold the chunks `root_hash` adds to this base.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta_base: Option<DeltaBaseV2>,
+}
+
+/// The base a delta bundle was computed against.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeltaBaseV2 {
+    pub root_hash: Option<[u8; CHUNK_HASH_SIZE]>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -1,8 +1,9 @@
 // prolly-rust/src/store/mem_store.rs
 
 use async_trait::async_trait;
-use std::collections::HashMap;
-use std::sync::Arc; // Using Arc for shared ownership with RwLock
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError}; // Using Arc for shared ownership with RwLock
 use tokio::sync::RwLock;
 
 use crate::common::Hash;
@@ -12,13 +13,46 @@ use super::chunk_store::ChunkStore;
 
 #[derive(Debug, Default)]
 pub struct InMemoryStoreInner {
-    data: HashMap<Hash, Vec<u8>>,
+    /// Chunks ordered by hash, so `hashes_page` can seek to a page directly.
+    data: BTreeMap<Hash, Vec<u8>>,
+    /// The generation each chunk was last put in. Chunks missing here were last put in
+    /// generation 0. Entries are only recorded once a write or collection has started and
+    /// are removed with their chunks, so the map never holds more entries than `data`.
+    put_generations: HashMap<Hash, u64>,
+}
+
+impl InMemoryStoreInner {
+    fn put_chunk(&mut self, hash: Hash, bytes: Vec<u8>, generation: u64) {
+        self.data.entry(hash).or_insert_with(|| bytes);
+        if generation > 0 {
+            self.put_generations.insert(hash, generation);
+        }
+    }
+
+    fn delete_chunks(&mut self, hashes: &[Hash]) {
+        for hash in hashes {
+            self.data.remove(hash);
+            self.put_generations.remove(hash);
+        }
+    }
+}
+
+/// Write generations and the writes in progress, kept apart from the chunks so that
+/// writes can be registered synchronously.
+#[derive(Debug, Default)]
+struct WriteTracking {
+    /// Current write generation, advanced by `write_generation`.
+    generation: u64,
+    next_write_id: u64,
+    /// The generation each write in progress started in, by write id.
+    active_writes: HashMap<u64, u64>,
 }
 
 /// An in-memory `ChunkStore` implementation using `tokio::sync::RwLock`.
 #[derive(Debug, Clone, Default)]
 pub struct InMemoryStore {
     inner: Arc<RwLock<InMemoryStoreInner>>,
+    writes: Arc<Mutex<WriteTracking>>,
 }
 
 impl InMemoryStore {
@@ -38,7 +72,16 @@ impl InMemoryStore {
     /// Helper for tests or specific scenarios to get all chunks (non-async for direct access if needed by caller)
     /// Note: This bypasses the async trait methods for direct inspection.
     pub async fn get_all_chunks_for_test(&self) -> HashMap<Hash, Vec<u8>> {
-        self.inner.read().await.data.clone()
+        self.inner.read().await.data.iter().map(|(hash, bytes)| (*hash, bytes.clone())).collect()
+    }
+
+    fn lock_writes(&self) -> MutexGuard<'_, WriteTracking> {
+        // The tracking stays consistent even if a holder panicked.
+        self.writes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn current_generation(&self) -> u64 {
+        self.lock_writes().generation
     }
 }
 
@@ -56,10 +99,9 @@ impl ChunkStore for InMemoryStore {
         let hash = hash_bytes(&bytes); // Using hash_bytes from crate::chunk
 
         let mut guard = self.inner.write().await;
-        // Using entry API to avoid cloning bytes if already present,
-        // though current ChunkStore::put implies overwriting or assuming content-addressing handles duplicates.
-        // For content-addressable storage, if hash exists, data must be identical.
-        guard.data.entry(hash).or_insert_with(|| bytes);
+        // For content-addressable storage, if hash exists, data must be identical; the put
+        // still counts as a write for any GC barrier in progress.
+        guard.put_chunk(hash, bytes, self.current_generation());
         Ok(hash)
     }
 
@@ -68,24 +110,96 @@ impl ChunkStore for InMemoryStore {
         Ok(guard.data.contains_key(hash))
     }
 
+    async fn get_many(&self, hashes: &[Hash]) -> Result<Vec<Option<Vec<u8>>>> {
+        let guard = self.inner.read().await;
+        Ok(hashes.iter().map(|hash| guard.data.get(hash).cloned()).collect())
+    }
+
+    async fn put_many(&self, chunks: Vec<Vec<u8>>) -> Result<Vec<Hash>> {
+        let hashed: Vec<(Hash, Vec<u8>)> = chunks.into_iter().map(|bytes| (hash_bytes(&bytes), bytes)).collect();
+        let mut guard = self.inner.write().await;
+        let generation = self.current_generation();
+        Ok(hashed
+            .into_iter()
+            .map(|(hash, bytes)| {
+                guard.put_chunk(hash, bytes, generation);
+                hash
+            })
+            .collect())
+    }
+
+    async fn exists_many(&self, hashes: &[Hash]) -> Result<Vec<bool>> {
+        let guard = self.inner.read().await;
+        Ok(hashes.iter().map(|hash| guard.data.contains_key(hash)).collect())
+    }
+
     async fn delete_batch(&self, hashes: &[Hash]) -> Result<()> {
         if hashes.is_empty() {
             return Ok(());
         }
         let mut guard = self.inner.write().await;
-        for hash in hashes {
-            guard.data.remove(hash);
-        }
+        guard.delete_chunks(hashes);
         Ok(())
     }
 
     async fn all_hashes(&self) -> Result<Vec<Hash>> {
         let guard = self.inner.read().await;
-        // Collect all keys (hashes) from the HashMap
+        // Collect all keys (hashes) from the map
         let hashes_vec = guard.data.keys().cloned().collect();
         Ok(hashes_vec)
     }
 
+    async fn chunk_count(&self) -> Result<u64> {
+        Ok(self.inner.read().await.data.len() as u64)
+    }
+
+    async fn hashes_page(&self, after: Option<&Hash>, limit: usize) -> Result<Vec<Hash>> {
+        let guard = self.inner.read().await;
+        let start = after.map_or(Bound::Unbounded, |after| Bound::Excluded(*after));
+        Ok(guard.data.range((start, Bound::Unbounded)).take(limit).map(|(hash, _)| *hash).collect())
+    }
+
+    /// Chunks put by writes that started before this call are protected as well: the
+    /// returned barrier is no newer than the generation the oldest write in progress
+    /// started in.
+    async fn write_generation(&self) -> Result<Option<u64>> {
+        // Puts read the generation under the data lock, so none straddles the barrier.
+        let _data = self.inner.write().await;
+        let mut writes = self.lock_writes();
+        writes.generation += 1;
+        let oldest_write = writes.active_writes.values().min().copied();
+        Ok(Some(oldest_write.map_or(writes.generation, |started| started.min(writes.generation))))
+    }
+
+    async fn delete_batch_unless_put_since(&self, hashes: &[Hash], generation: u64) -> Result<usize> {
+        let mut guard = self.inner.write().await;
+        let deletable: Vec<Hash> = hashes
+            .iter()
+            .filter(|hash| guard.data.contains_key(*hash) && guard.put_generations.get(*hash).copied().unwrap_or(0) < generation)
+            .copied()
+            .collect();
+        guard.delete_chunks(&deletable);
+        Ok(deletable.len())
+    }
+
+    fn begin_write(&self) -> Result<Option<u64>> {
+        let mut writes = self.lock_writes();
+        // Puts are only tracked from generation 1 on, so a write must not start in 0.
+        writes.generation = writes.generation.max(1);
+        let write_id = writes.next_write_id;
+        writes.next_write_id += 1;
+        let generation = writes.generation;
+        writes.active_writes.insert(write_id, generation);
+        Ok(Some(write_id))
+    }
+
+    fn end_write(&self, write_id: u64) -> Result<()> {
+        match self.lock_writes().active_writes.remove(&write_id) {
+            Some(_) => Ok(()),
+            None => Err(ProllyError::InvalidOperation(format!("Write {} is not in progress", write_id))),
+        }
+    }
+
     fn get_sync(&self, hash: &Hash) -> Result<Option<Vec<u8>>> {
         let guard = self.inner.try_read().map_err(|_| {
             ProllyError::StorageError("Failed to acquire synchronous read lock on store. An async write operation is likely in progress.".to_string())
@@ -98,7 +212,7 @@ impl ChunkStore for InMemoryStore {
         let mut guard = self.inner.try_write().map_err(|_| {
             ProllyError::StorageError("Failed to acquire synchronous write lock on store. An async operation is likely in progress.".to_string())
         })?;
-        guard.data.entry(hash).or_insert_with(|| bytes);
+        guard.put_chunk(hash, bytes, self.current_generation());
         Ok(hash)
     }
 
@@ -109,9 +223,7 @@ impl ChunkStore for InMemoryStore {
         let mut guard = self.inner.try_write().map_err(|_| {
             ProllyError::StorageError("Failed to acquire synchronous write lock on store for delete. An async operation is likely in progress.".to_string())
         })?;
-        for hash in hashes {
-            guard.data.remove(hash);
-        }
+        guard.delete_chunks(hashes);
         Ok(())
     }
 }
@@ -129,11 +241,11 @@ mod wasm_specific {
     use wasm_bindgen::{JsCast, JsValue};
 
     impl InMemoryStore {
-        /// Convert a JS `Map<Uint8Array, Uint8Array>` ➜ Rust HashMap, then to InMemoryStore.
+        /// Convert a JS `Map<Uint8Array, Uint8Array>` ➜ Rust BTreeMap, then to InMemoryStore.
         /// This function itself is synchronous as it processes JS objects directly.
         /// The resulting InMemoryStore is async.
         pub fn from_js_map(map: &JsMap) -> std::result::Result<Self, JsValue> { // Returns std::result for JsValue
-            let mut inner_map = HashMap::new();
+            let mut inner_map = BTreeMap::new();
             let entries = js_sys::try_iter(map)
                 .map_err(|_| JsValue::from_str("Input Map is not iterable"))?
                 .ok_or_else(|| JsValue::from_str("Failed to create iterator from Map"))?;
@@ -167,7 +279,8 @@ mod wasm_specific {
             }
 
             Ok(Self {
-                inner: Arc::new(RwLock::new(InMemoryStoreInner { data: inner_map })),
+                inner: Arc::new(RwLock::new(InMemoryStoreInner { data: inner_map, ..Default::default() })),
+                writes: Arc::default(),
             })
         }
     }
@@ -4,8 +4,13 @@ pub mod chunk_store;
 pub mod mem_store;
 pub mod format_v2; 
 pub mod file_io_v2; 
+pub mod encrypted_store;
+pub mod patch_format;
+pub mod ref_counted_store;
 
 // Re-export key items for easier access from `crate::store::`
-pub use chunk_store::ChunkStore;
+pub use chunk_store::{ChunkStore, HashPager};
 pub use mem_store::InMemoryStore;
+pub use encrypted_store::{EncryptedStore, EncryptionMode};
+pub use ref_counted_store::{RefCountedStore, RefCountCheck};
 
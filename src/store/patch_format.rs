@@ -0,0 +1,260 @@
+// packages/prolly-rust/src/store/patch_format.rs
+//
+// Portable patch files: the changes between a base root and a target root, in key order.
+//
+// Layout (all integers big-endian):
+//   Header:  signature "PRLYPTC1" | version u8 | flags u8 (bit 0: base root present,
+//            bit 1: target root present) | base root [32] | target root [32] |
+//            target entries digest [32] | record count u64
+//   Records: ContentBlockEnvelope (tag 0x30, length, CRC32) followed by the records, each
+//            kind u8 | key length u32 | key | value (additions and modifications only)
+//   Value:   0x01 inline | length u64 | bytes
+//            0x02 chunk references | count u32 | chunk hashes [32] each
+//   EOF:     tag 0xFF | BLAKE3 of everything before the EOF tag [32]
+
+use super::format_v2::{
+    ContentBlockEnvelope, CHUNK_HASH_SIZE, BLAKE3_CHECKSUM_SIZE, TAG_EOF_BLOCK,
+    calculate_crc32, calculate_blake3_hash,
+};
+use crate::common::{Hash, Key, Value};
+use crate::diff::DiffKind;
+use crate::error::ProllyError;
+use std::io::{Cursor, Read, Write};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+// --- Constants ---
+pub const PATCH_SIGNATURE_V1: &[u8; 8] = b"PRLYPTC1";
+pub const PATCH_FORMAT_VERSION_V1: u8 = 0x01;
+
+pub const TAG_PATCH_RECORDS_BLOCK: u8 = 0x30;
+
+const FLAG_HAS_BASE_ROOT: u8 = 0x01;
+const FLAG_HAS_TARGET_ROOT: u8 = 0x02;
+
+const KIND_ADDED: u8 = 0x01;
+const KIND_REMOVED: u8 = 0x02;
+const KIND_MODIFIED: u8 = 0x03;
+
+const VALUE_INLINE: u8 = 0x01;
+const VALUE_CHUNK_REFS: u8 = 0x02;
+
+// --- Structures ---
+
+/// The new value of a key in a patch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchValue {
+    /// The value bytes, embedded in the patch.
+    Inline(Value),
+    /// Hashes of the data chunks holding the value, in order. The receiving store must
+    /// already have these chunks.
+    ChunkRefs(Vec<Hash>),
+}
+
+/// One changed key. `value` is the key's value in the target tree, and is `None` exactly
+/// for removals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchRecord {
+    pub key: Key,
+    pub kind: DiffKind,
+    pub value: Option<PatchValue>,
+}
+
+/// The changes that turn the tree at `base_root` into the tree at `target_root`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub base_root: Option<Hash>,
+    pub target_root: Option<Hash>,
+    /// Digest of every key and value in the target tree (see `diff::entries_digest`). It does
+    /// not depend on the tree's shape, so the importer can check its result against it
+    /// without having the target root.
+    pub target_digest: Hash,
+    /// Records sorted by key, with no duplicate keys.
+    pub records: Vec<PatchRecord>,
+}
+
+fn header_size() -> usize {
+    8 + 1 + 1 + CHUNK_HASH_SIZE + CHUNK_HASH_SIZE + CHUNK_HASH_SIZE + 8
+}
+
+fn write_record<W: Write>(writer: &mut W, record: &PatchRecord) -> Result<(), ProllyError> {
+    let kind = match record.kind {
+        DiffKind::Added => KIND_ADDED,
+        DiffKind::Removed => KIND_REMOVED,
+        DiffKind::Modified => KIND_MODIFIED,
+    };
+    writer.write_u8(kind)?;
+    writer.write_u32::<BigEndian>(record.key.len() as u32)?;
+    writer.write_all(&record.key)?;
+    match (record.kind, &record.value) {
+        (DiffKind::Removed, None) => {}
+        (DiffKind::Added | DiffKind::Modified, Some(PatchValue::Inline(value))) => {
+            writer.write_u8(VALUE_INLINE)?;
+            writer.write_u64::<BigEndian>(value.len() as u64)?;
+            writer.write_all(value)?;
+        }
+        (DiffKind::Added | DiffKind::Modified, Some(PatchValue::ChunkRefs(chunk_hashes))) => {
+            writer.write_u8(VALUE_CHUNK_REFS)?;
+            writer.write_u32::<BigEndian>(chunk_hashes.len() as u32)?;
+            for chunk_hash in chunk_hashes {
+                writer.write_all(chunk_hash)?;
+            }
+        }
+        _ => {
+            return Err(ProllyError::Serialization(format!(
+                "Patch record for key {:?} must carry a value exactly when it is not a removal", record.key
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn read_record<R: Read>(reader: &mut R) -> Result<PatchRecord, ProllyError> {
+    let kind = match reader.read_u8()? {
+        KIND_ADDED => DiffKind::Added,
+        KIND_REMOVED => DiffKind::Removed,
+        KIND_MODIFIED => DiffKind::Modified,
+        other => return Err(ProllyError::InvalidFileFormat(format!("Unknown patch record kind: {}", other))),
+    };
+    let key_len = reader.read_u32::<BigEndian>()? as usize;
+    let mut key = vec![0u8; key_len];
+    reader.read_exact(&mut key)?;
+    let value = if kind == DiffKind::Removed {
+        None
+    } else {
+        Some(match reader.read_u8()? {
+            VALUE_INLINE => {
+                let value_len = reader.read_u64::<BigEndian>()? as usize;
+                let mut value = Vec::new();
+                reader.take(value_len as u64).read_to_end(&mut value)?;
+                if value.len() != value_len {
+                    return Err(ProllyError::InvalidFileFormat("Truncated inline value in patch record".into()));
+                }
+                PatchValue::Inline(value)
+            }
+            VALUE_CHUNK_REFS => {
+                let count = reader.read_u32::<BigEndian>()?;
+                let mut chunk_hashes = Vec::new();
+                for _ in 0..count {
+                    let mut chunk_hash = [0u8; CHUNK_HASH_SIZE];
+                    reader.read_exact(&mut chunk_hash)?;
+                    chunk_hashes.push(chunk_hash);
+                }
+                PatchValue::ChunkRefs(chunk_hashes)
+            }
+            other => return Err(ProllyError::InvalidFileFormat(format!("Unknown patch value encoding: {}", other))),
+        })
+    };
+    Ok(PatchRecord { key, kind, value })
+}
+
+/// Serializes `patch` into the patch file layout described at the top of this module.
+pub fn write_patch_v1(patch: &Patch) -> Result<Vec<u8>, ProllyError> {
+    if patch.records.windows(2).any(|pair| pair[0].key >= pair[1].key) {
+        return Err(ProllyError::Serialization("Patch records must be sorted by key without duplicates".into()));
+    }
+
+    let mut records_content = Cursor::new(Vec::new());
+    for record in &patch.records {
+        write_record(&mut records_content, record)?;
+    }
+    let records_content = records_content.into_inner();
+
+    let mut file_buffer = Vec::with_capacity(header_size() + ContentBlockEnvelope::size() + records_content.len() + 1 + BLAKE3_CHECKSUM_SIZE);
+    let mut flags = 0u8;
+    if patch.base_root.is_some() { flags |= FLAG_HAS_BASE_ROOT; }
+    if patch.target_root.is_some() { flags |= FLAG_HAS_TARGET_ROOT; }
+    file_buffer.write_all(PATCH_SIGNATURE_V1)?;
+    file_buffer.write_u8(PATCH_FORMAT_VERSION_V1)?;
+    file_buffer.write_u8(flags)?;
+    file_buffer.write_all(&patch.base_root.unwrap_or([0u8; CHUNK_HASH_SIZE]))?;
+    file_buffer.write_all(&patch.target_root.unwrap_or([0u8; CHUNK_HASH_SIZE]))?;
+    file_buffer.write_all(&patch.target_digest)?;
+    file_buffer.write_u64::<BigEndian>(patch.records.len() as u64)?;
+
+    let records_len = u32::try_from(records_content.len())
+        .map_err(|_| ProllyError::Serialization("Patch records exceed the 4 GiB block limit".into()))?;
+    ContentBlockEnvelope::new(TAG_PATCH_RECORDS_BLOCK, records_len, calculate_crc32(&records_content))
+        .write_to(&mut file_buffer)?;
+    file_buffer.write_all(&records_content)?;
+
+    let overall_checksum = calculate_blake3_hash(&file_buffer);
+    file_buffer.write_u8(TAG_EOF_BLOCK)?;
+    file_buffer.write_all(&overall_checksum)?;
+    Ok(file_buffer)
+}
+
+/// Parses and verifies a patch written by `write_patch_v1`.
+pub fn read_patch_v1(file_bytes: &[u8]) -> Result<Patch, ProllyError> {
+    let eof_block_size = 1 + BLAKE3_CHECKSUM_SIZE;
+    if file_bytes.len() < header_size() + ContentBlockEnvelope::size() + eof_block_size {
+        return Err(ProllyError::InvalidFileFormat("Patch is too short".into()));
+    }
+    let mut reader = Cursor::new(file_bytes);
+
+    let mut signature = [0u8; 8];
+    reader.read_exact(&mut signature)?;
+    if &signature != PATCH_SIGNATURE_V1 {
+        return Err(ProllyError::InvalidFileFormat("Invalid patch signature".into()));
+    }
+    let version = reader.read_u8()?;
+    if version != PATCH_FORMAT_VERSION_V1 {
+        return Err(ProllyError::InvalidFileFormat(format!("Unsupported patch version: {}", version)));
+    }
+
+    let eof_offset = file_bytes.len() - eof_block_size;
+    if file_bytes[eof_offset] != TAG_EOF_BLOCK {
+        return Err(ProllyError::InvalidFileFormat("Patch EOF tag mismatch".into()));
+    }
+    if calculate_blake3_hash(&file_bytes[..eof_offset])[..] != file_bytes[eof_offset + 1..] {
+        return Err(ProllyError::ChecksumMismatch { context: "Overall patch content".into() });
+    }
+
+    let flags = reader.read_u8()?;
+    let mut base_root = [0u8; CHUNK_HASH_SIZE];
+    reader.read_exact(&mut base_root)?;
+    let mut target_root = [0u8; CHUNK_HASH_SIZE];
+    reader.read_exact(&mut target_root)?;
+    let mut target_digest = [0u8; CHUNK_HASH_SIZE];
+    reader.read_exact(&mut target_digest)?;
+    let record_count = reader.read_u64::<BigEndian>()?;
+
+    let envelope = ContentBlockEnvelope::read_from(&mut reader)?;
+    if envelope.tag != TAG_PATCH_RECORDS_BLOCK {
+        return Err(ProllyError::InvalidFileFormat("Patch Records Block tag mismatch".into()));
+    }
+    let records_start = reader.position() as usize;
+    let records_end = records_start + envelope.content_length as usize;
+    if records_end != eof_offset {
+        return Err(ProllyError::InvalidFileFormat("Patch Records Block length does not match the file size".into()));
+    }
+    let records_content = &file_bytes[records_start..records_end];
+    if calculate_crc32(records_content) != envelope.content_checksum {
+        return Err(ProllyError::ChecksumMismatch { context: "Patch Records Block".into() });
+    }
+
+    let mut records_reader = Cursor::new(records_content);
+    let mut records: Vec<PatchRecord> = Vec::new();
+    while (records_reader.position() as usize) < records_content.len() {
+        let record = read_record(&mut records_reader)
+            .map_err(|e| match e {
+                ProllyError::IoError(io_err) => ProllyError::InvalidFileFormat(format!("Truncated patch record: {}", io_err)),
+                other => other,
+            })?;
+        if records.last().is_some_and(|previous| previous.key >= record.key) {
+            return Err(ProllyError::InvalidFileFormat("Patch records are not sorted by key".into()));
+        }
+        records.push(record);
+    }
+    if records.len() as u64 != record_count {
+        return Err(ProllyError::InvalidFileFormat(format!(
+            "Patch header announces {} records but {} were found", record_count, records.len()
+        )));
+    }
+
+    Ok(Patch {
+        base_root: (flags & FLAG_HAS_BASE_ROOT != 0).then_some(base_root),
+        target_root: (flags & FLAG_HAS_TARGET_ROOT != 0).then_some(target_root),
+        target_digest,
+        records,
+    })
+}
@@ -0,0 +1,452 @@
+// prolly-rust/src/store/ref_counted_store.rs
+
+//! A `ChunkStore` wrapper that reference-counts chunks so garbage can be freed as soon as
+//! it is orphaned, without a mark phase over every live chunk.
+//!
+//! A chunk's count is the number of references to it from stored nodes (child nodes of
+//! internal nodes, value chunks of leaves) plus the number of times it was retained as a
+//! root. Counts are updated when a chunk is first put. Releasing a root whose count drops
+//! to zero deletes it and releases everything it references in turn.
+//!
+//! A tree write stores several nodes that never become part of a root (intermediate
+//! versions of the path being rewritten); these stay at zero and are freed by
+//! `free_unreferenced`. Like `GarbageCollector`, nodes are told apart from value chunks by
+//! whether they decode as a `Node`.
+//!
+//! The store is not behind `PTree`: the JS tree's store type is fixed to `InMemoryStore`
+//! throughout the bindings, and counting decodes every new node on put, a cost the default
+//! tree should not pay. Rust users wrap their store and build a `ProllyTree` over it.
+
+use async_trait::async_trait;
+use log::trace;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::common::Hash;
+use crate::chunk::hash_bytes;
+use crate::error::{Result, ProllyError};
+use crate::gc::GarbageCollector;
+use crate::node::definition::{Node, ValueRepr};
+use super::chunk_store::{check_batch_len, ChunkStore, HashPager};
+
+/// Number of hashes requested per `ChunkStore::hashes_page` call while recounting.
+const RECOUNT_PAGE_SIZE: usize = 1024;
+
+#[derive(Debug, Default)]
+struct RefCounts {
+    /// Non-zero reference counts. A chunk may be counted before it is stored, when a node
+    /// referencing it was put first.
+    counts: HashMap<Hash, u64>,
+    /// How many times each root was retained; included in `counts`.
+    retained_roots: HashMap<Hash, u64>,
+    /// Stored chunks with a count of zero.
+    unreferenced: HashSet<Hash>,
+}
+
+impl RefCounts {
+    fn count(&self, hash: &Hash) -> u64 {
+        self.counts.get(hash).copied().unwrap_or(0)
+    }
+
+    fn increment(&mut self, hash: Hash) {
+        *self.counts.entry(hash).or_insert(0) += 1;
+        self.unreferenced.remove(&hash);
+    }
+
+    /// Decrements the count of `hash` and returns whether it dropped to zero.
+    fn decrement(&mut self, hash: &Hash) -> bool {
+        match self.counts.get_mut(hash) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                false
+            }
+            Some(_) => {
+                self.counts.remove(hash);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The outcome of `RefCountedStore::check_consistency`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RefCountCheck {
+    /// Chunks in the store.
+    pub chunks: u64,
+    /// Chunks reachable from the retained roots, according to a mark phase.
+    pub reachable_chunks: u64,
+    /// Chunks whose tracked count differs from a recount over the stored nodes.
+    pub miscounted: Vec<Hash>,
+    /// Chunks a mark-and-sweep collection would delete but reference counting has not freed
+    /// yet. Empty right after `free_unreferenced`.
+    pub unfreed: Vec<Hash>,
+}
+
+impl RefCountCheck {
+    /// Whether every tracked count matches the recount.
+    pub fn is_consistent(&self) -> bool {
+        self.miscounted.is_empty()
+    }
+}
+
+/// A `ChunkStore` that keeps reference counts of the chunks in an inner store, so that
+/// chunks orphaned by replacing a root can be freed immediately with `release_root`.
+///
+/// The wrapper must see every write to the inner store: wrap an empty store with `new`, or
+/// an existing one with `rebuild`, and do not write to the inner store directly. The inner
+/// store must address chunks by `hash_bytes` of their bytes, as `InMemoryStore` does.
+#[derive(Debug)]
+pub struct RefCountedStore<S: ChunkStore> {
+    inner: Arc<S>,
+    refs: Mutex<RefCounts>,
+}
+
+impl<S: ChunkStore> RefCountedStore<S> {
+    /// Wraps an empty `inner` store.
+    pub fn new(inner: Arc<S>) -> Self {
+        Self { inner, refs: Mutex::new(RefCounts::default()) }
+    }
+
+    /// Wraps `inner`, counting the references between the chunks it already holds and
+    /// retaining `roots`.
+    pub async fn rebuild(inner: Arc<S>, roots: &[Hash]) -> Result<Self> {
+        let store = Self::new(inner);
+        {
+            let mut refs = store.refs.lock().await;
+            *refs = store.recount().await?;
+            for root in roots {
+                Self::retain_locked(&mut refs, store.inner.as_ref(), *root).await?;
+            }
+        }
+        Ok(store)
+    }
+
+    /// The wrapped store.
+    pub fn inner(&self) -> &Arc<S> {
+        &self.inner
+    }
+
+    /// The current reference count of `hash`.
+    pub async fn ref_count(&self, hash: &Hash) -> u64 {
+        self.refs.lock().await.count(hash)
+    }
+
+    /// The retained roots, without duplicates.
+    pub async fn retained_roots(&self) -> Vec<Hash> {
+        self.refs.lock().await.retained_roots.keys().copied().collect()
+    }
+
+    /// Keeps `root` and everything it references alive until a matching `release_root`.
+    pub async fn retain_root(&self, root: Hash) -> Result<()> {
+        let mut refs = self.refs.lock().await;
+        Self::retain_locked(&mut refs, self.inner.as_ref(), root).await
+    }
+
+    /// Releases one retain of `root`, freeing it and every chunk only it kept alive once no
+    /// retain or stored node references it anymore. Returns the number of chunks freed.
+    pub async fn release_root(&self, root: &Hash) -> Result<usize> {
+        let mut refs = self.refs.lock().await;
+        match refs.retained_roots.get_mut(root) {
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                refs.retained_roots.remove(root);
+            }
+            None => return Err(ProllyError::InvalidOperation(format!("Root {:?} is not retained", root))),
+        }
+        if refs.decrement(root) {
+            self.free_locked(&mut refs, vec![*root]).await
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Retains `new_root` and releases `old_root`, as after a commit that replaced one with
+    /// the other, then frees the unreferenced chunks the writes left behind. Returns the
+    /// number of chunks freed.
+    pub async fn replace_root(&self, old_root: Option<Hash>, new_root: Option<Hash>) -> Result<usize> {
+        if old_root == new_root {
+            return self.free_unreferenced().await;
+        }
+        if let Some(new_root) = new_root {
+            self.retain_root(new_root).await?;
+        }
+        let mut freed = 0;
+        if let Some(old_root) = old_root {
+            freed += self.release_root(&old_root).await?;
+        }
+        Ok(freed + self.free_unreferenced().await?)
+    }
+
+    /// Frees every stored chunk that no retained root or stored node references, such as
+    /// nodes superseded within a tree write, along with what only they referenced. Must not
+    /// run while a write is in progress whose new nodes are not referenced yet. Returns the
+    /// number of chunks freed.
+    pub async fn free_unreferenced(&self) -> Result<usize> {
+        let mut refs = self.refs.lock().await;
+        let unreferenced: Vec<Hash> = refs.unreferenced.drain().collect();
+        self.free_locked(&mut refs, unreferenced).await
+    }
+
+    /// Recounts references from the stored nodes and compares the result with the tracked
+    /// counts, and runs a mark phase from the retained roots to find chunks a mark-and-sweep
+    /// collection would delete.
+    pub async fn check_consistency(&self) -> Result<RefCountCheck> {
+        let refs = self.refs.lock().await;
+        let mut expected = self.recount().await?;
+        for (root, retains) in &refs.retained_roots {
+            *expected.counts.entry(*root).or_insert(0) += retains;
+        }
+        let mut miscounted: Vec<Hash> = refs.counts.keys().chain(expected.counts.keys())
+            .filter(|hash| refs.count(hash) != expected.count(hash))
+            .copied()
+            .collect::<HashSet<Hash>>()
+            .into_iter()
+            .collect();
+        miscounted.sort();
+
+        let roots: Vec<Hash> = refs.retained_roots.keys().copied().collect();
+        let plan = GarbageCollector::new(Arc::clone(&self.inner)).plan(&roots).await?;
+        Ok(RefCountCheck {
+            chunks: plan.live_chunks + plan.dead_chunks.len() as u64,
+            reachable_chunks: plan.live_chunks,
+            miscounted,
+            unfreed: plan.dead_chunks.iter().map(|chunk| chunk.hash).collect(),
+        })
+    }
+
+    async fn retain_locked(refs: &mut RefCounts, inner: &S, root: Hash) -> Result<()> {
+        if !inner.exists(&root).await? {
+            return Err(ProllyError::ChunkNotFound(root));
+        }
+        *refs.retained_roots.entry(root).or_insert(0) += 1;
+        refs.increment(root);
+        Ok(())
+    }
+
+    /// Deletes `zero_count` chunks, whose counts already dropped to zero, and releases the
+    /// references they held, transitively.
+    async fn free_locked(&self, refs: &mut RefCounts, zero_count: Vec<Hash>) -> Result<usize> {
+        let mut pending = zero_count;
+        let mut freed = Vec::new();
+        while let Some(hash) = pending.pop() {
+            refs.unreferenced.remove(&hash);
+            let Some(bytes) = self.inner.get(&hash).await? else { continue };
+            for reference in chunk_references(&bytes) {
+                if refs.decrement(&reference) {
+                    pending.push(reference);
+                }
+            }
+            freed.push(hash);
+        }
+        if !freed.is_empty() {
+            trace!("RefCountedStore: freeing {} chunks.", freed.len());
+            self.inner.delete_batch(&freed).await?;
+        }
+        Ok(freed.len())
+    }
+
+    /// Counts the references between the chunks of the inner store, without root retains.
+    async fn recount(&self) -> Result<RefCounts> {
+        let mut recounted = RefCounts::default();
+        let mut pager = HashPager::new();
+        while let Some(page) = pager.next_page(self.inner.as_ref(), RECOUNT_PAGE_SIZE).await? {
+            for hash in &page {
+                if let Some(bytes) = self.inner.get(hash).await? {
+                    for reference in chunk_references(&bytes) {
+                        *recounted.counts.entry(reference).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+        // Unreferenced chunks are only known once every reference has been counted.
+        let mut pager = HashPager::new();
+        while let Some(page) = pager.next_page(self.inner.as_ref(), RECOUNT_PAGE_SIZE).await? {
+            let unreferenced: Vec<Hash> = page.into_iter().filter(|hash| recounted.count(hash) == 0).collect();
+            recounted.unreferenced.extend(unreferenced);
+        }
+        Ok(recounted)
+    }
+
+    /// Releases the references held by deleted chunks, given with their bytes (`None` for
+    /// chunks that were not stored). Chunks whose counts drop to zero become unreferenced.
+    fn release_deleted<'a>(refs: &mut RefCounts, deleted: impl Iterator<Item = (&'a Hash, Option<Vec<u8>>)>) {
+        for (hash, bytes) in deleted {
+            if let Some(bytes) = bytes {
+                for reference in chunk_references(&bytes) {
+                    if refs.decrement(&reference) {
+                        refs.unreferenced.insert(reference);
+                    }
+                }
+            }
+            refs.unreferenced.remove(hash);
+        }
+    }
+
+    /// Updates the counts for a chunk that was just stored and was not present before.
+    fn count_new_chunk(refs: &mut RefCounts, hash: Hash, stored_hash: Hash, bytes: &[u8]) -> Result<()> {
+        if stored_hash != hash {
+            return Err(ProllyError::ChecksumMismatch {
+                context: format!("Inner store stored chunk {:?} under hash {:?}", hash, stored_hash),
+            });
+        }
+        for reference in chunk_references(bytes) {
+            refs.increment(reference);
+        }
+        if refs.count(&hash) == 0 {
+            refs.unreferenced.insert(hash);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: ChunkStore> ChunkStore for RefCountedStore<S> {
+    async fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>> {
+        self.inner.get(hash).await
+    }
+
+    /// Chunks already stored are put again without being recounted, so that a write
+    /// barrier of the inner store sees the put.
+    async fn put(&self, bytes: Vec<u8>) -> Result<Hash> {
+        let mut refs = self.refs.lock().await;
+        let hash = hash_bytes(&bytes);
+        if self.inner.exists(&hash).await? {
+            return self.inner.put(bytes).await;
+        }
+        let stored_hash = self.inner.put(bytes.clone()).await?;
+        Self::count_new_chunk(&mut refs, hash, stored_hash, &bytes)?;
+        Ok(stored_hash)
+    }
+
+    async fn exists(&self, hash: &Hash) -> Result<bool> {
+        self.inner.exists(hash).await
+    }
+
+    async fn get_many(&self, hashes: &[Hash]) -> Result<Vec<Option<Vec<u8>>>> {
+        self.inner.get_many(hashes).await
+    }
+
+    /// Puts every chunk with one inner `put_many` call and counts those the inner store did
+    /// not hold yet, counting a chunk repeated within the batch once.
+    async fn put_many(&self, chunks: Vec<Vec<u8>>) -> Result<Vec<Hash>> {
+        let mut refs = self.refs.lock().await;
+        let hashes: Vec<Hash> = chunks.iter().map(|bytes| hash_bytes(bytes)).collect();
+        let present = check_batch_len("exists_many", hashes.len(), self.inner.exists_many(&hashes).await?)?;
+        let mut seen = HashSet::new();
+        let new_chunks: Vec<usize> = present
+            .iter()
+            .enumerate()
+            .filter(|(index, present)| !**present && seen.insert(hashes[*index]))
+            .map(|(index, _)| index)
+            .collect();
+        let new_bytes: Vec<Vec<u8>> = new_chunks.iter().map(|index| chunks[*index].clone()).collect();
+        let stored_hashes = check_batch_len("put_many", hashes.len(), self.inner.put_many(chunks).await?)?;
+        for (index, bytes) in new_chunks.into_iter().zip(&new_bytes) {
+            Self::count_new_chunk(&mut refs, hashes[index], stored_hashes[index], bytes)?;
+        }
+        Ok(stored_hashes)
+    }
+
+    async fn exists_many(&self, hashes: &[Hash]) -> Result<Vec<bool>> {
+        self.inner.exists_many(hashes).await
+    }
+
+    /// Deletes the chunks and releases the references they held, without freeing chunks
+    /// whose counts drop to zero (they become unreferenced instead).
+    async fn delete_batch(&self, hashes: &[Hash]) -> Result<()> {
+        let mut refs = self.refs.lock().await;
+        let chunks = check_batch_len("get_many", hashes.len(), self.inner.get_many(hashes).await?)?;
+        self.inner.delete_batch(hashes).await?;
+        Self::release_deleted(&mut refs, hashes.iter().zip(chunks));
+        Ok(())
+    }
+
+    async fn all_hashes(&self) -> Result<Vec<Hash>> {
+        self.inner.all_hashes().await
+    }
+
+    async fn chunk_count(&self) -> Result<u64> {
+        self.inner.chunk_count().await
+    }
+
+    async fn hashes_page(&self, after: Option<&Hash>, limit: usize) -> Result<Vec<Hash>> {
+        self.inner.hashes_page(after, limit).await
+    }
+
+    async fn write_generation(&self) -> Result<Option<u64>> {
+        self.inner.write_generation().await
+    }
+
+    fn begin_write(&self) -> Result<Option<u64>> {
+        self.inner.begin_write()
+    }
+
+    fn end_write(&self, write_id: u64) -> Result<()> {
+        self.inner.end_write(write_id)
+    }
+
+    /// Like `delete_batch`, for the chunks the inner store actually deletes. Holding the
+    /// count lock keeps puts through this store out until the counts are updated.
+    async fn delete_batch_unless_put_since(&self, hashes: &[Hash], generation: u64) -> Result<usize> {
+        let mut refs = self.refs.lock().await;
+        let chunks = check_batch_len("get_many", hashes.len(), self.inner.get_many(hashes).await?)?;
+        let deleted = self.inner.delete_batch_unless_put_since(hashes, generation).await?;
+        if deleted > 0 {
+            let remaining = check_batch_len("exists_many", hashes.len(), self.inner.exists_many(hashes).await?)?;
+            let deleted_chunks = hashes
+                .iter()
+                .zip(chunks)
+                .zip(remaining)
+                .filter(|(_, remaining)| !remaining)
+                .map(|(chunk, _)| chunk);
+            Self::release_deleted(&mut refs, deleted_chunks);
+        }
+        Ok(deleted)
+    }
+
+    fn get_sync(&self, hash: &Hash) -> Result<Option<Vec<u8>>> {
+        self.inner.get_sync(hash)
+    }
+
+    fn put_sync(&self, bytes: Vec<u8>) -> Result<Hash> {
+        let mut refs = self.refs.try_lock().map_err(|_| {
+            ProllyError::StorageError("Failed to acquire synchronous lock on reference counts. An async operation is likely in progress.".to_string())
+        })?;
+        let hash = hash_bytes(&bytes);
+        if self.inner.get_sync(&hash)?.is_some() {
+            return self.inner.put_sync(bytes);
+        }
+        let stored_hash = self.inner.put_sync(bytes.clone())?;
+        Self::count_new_chunk(&mut refs, hash, stored_hash, &bytes)?;
+        Ok(stored_hash)
+    }
+
+    fn delete_batch_sync(&self, hashes: &[Hash]) -> Result<()> {
+        let mut refs = self.refs.try_lock().map_err(|_| {
+            ProllyError::StorageError("Failed to acquire synchronous lock on reference counts. An async operation is likely in progress.".to_string())
+        })?;
+        let chunks = hashes.iter().map(|hash| self.inner.get_sync(hash)).collect::<Result<Vec<_>>>()?;
+        self.inner.delete_batch_sync(hashes)?;
+        Self::release_deleted(&mut refs, hashes.iter().zip(chunks));
+        Ok(())
+    }
+}
+
+/// The chunks a chunk references: children of an internal node or value chunks of a leaf,
+/// once per reference. Chunks that do not decode as nodes reference nothing.
+fn chunk_references(bytes: &[u8]) -> Vec<Hash> {
+    match Node::decode(bytes) {
+        Ok(Node::Internal { children, .. }) => children.iter().map(|child| child.child_hash).collect(),
+        Ok(Node::Leaf { entries, .. }) => entries
+            .iter()
+            .flat_map(|entry| match &entry.value {
+                ValueRepr::Inline(_) => Vec::new(),
+                ValueRepr::Chunked(data_hash) => vec![*data_hash],
+                ValueRepr::ChunkedSequence { chunk_hashes, .. } => chunk_hashes.clone(),
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
@@ -0,0 +1,308 @@
+// packages/prolly-rust/src/sync.rs
+
+//! Replicates the chunks reachable from a root from one `ChunkStore` to another.
+//!
+//! The source walks the tree top-down and offers chunk hashes to the destination in `Have`
+//! messages; the destination answers with the `Want`ed subset it is missing. Subtrees the
+//! destination already holds are pruned without being read. Once the walk is over, the
+//! wanted chunks are sent in `Chunks` messages, deepest level first, so the destination
+//! never holds a node whose children are missing: an interrupted sync leaves it in a state
+//! where pruning by hash stays correct, and simply running again completes it.
+//!
+//! Messages go through a `Transport`, so the same walk can run over sockets or JS
+//! `postMessage` by serializing them with `SyncMessage::encode`.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use async_trait::async_trait;
+use log::{debug, warn};
+use serde::{Serialize, Deserialize};
+
+use crate::common::Hash;
+use crate::error::{Result, ProllyError};
+use crate::node::definition::{Node, ValueRepr};
+use crate::store::ChunkStore;
+use crate::store::chunk_store::check_batch_len;
+
+/// A message of the sync protocol. The source sends `Have`, `Chunks` and `Done`; the
+/// destination answers `Have` with `Want` and the others with `Ack`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncMessage {
+    /// Chunks reachable from the root that the source can send.
+    Have { hashes: Vec<Hash> },
+    /// The hashes of the last `Have` that the destination does not hold, in the same order.
+    Want { hashes: Vec<Hash> },
+    /// Wanted chunks, as `(hash, bytes)` pairs.
+    Chunks { chunks: Vec<(Hash, Vec<u8>)> },
+    /// The sync of `root` is complete.
+    Done { root: Option<Hash> },
+    Ack,
+}
+
+impl SyncMessage {
+    /// Serializes the message for transports that carry bytes.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        bincode::serialize(self).map_err(ProllyError::from)
+    }
+
+    /// Parses a message produced by `encode`.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        bincode::deserialize(bytes).map_err(ProllyError::from)
+    }
+}
+
+/// Carries messages from the sync source to the destination and returns its replies.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Sends `message` to the destination and waits for its reply.
+    async fn exchange(&self, message: SyncMessage) -> Result<SyncMessage>;
+}
+
+/// The destination side of the protocol: answers messages against a `ChunkStore`.
+#[derive(Debug)]
+pub struct SyncDestination<D: ChunkStore + ?Sized> {
+    store: Arc<D>,
+    /// The store's id for the write from the first `Chunks` message to `Done`, so that a
+    /// garbage collection of the destination spares chunks whose parents are still to come.
+    /// It is also ended when a `Chunks` message fails and when the destination is dropped.
+    write_id: Mutex<Option<u64>>,
+}
+
+impl<D: ChunkStore + ?Sized> SyncDestination<D> {
+    pub fn new(store: Arc<D>) -> Self {
+        Self { store, write_id: Mutex::new(None) }
+    }
+
+    fn write_id(&self) -> MutexGuard<'_, Option<u64>> {
+        self.write_id.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Handles one message from the source and returns the reply.
+    pub async fn handle(&self, message: SyncMessage) -> Result<SyncMessage> {
+        match message {
+            SyncMessage::Have { hashes } => {
+                let found = check_batch_len("exists_many", hashes.len(), self.store.exists_many(&hashes).await?)?;
+                let wanted = hashes.into_iter().zip(found).filter(|(_, found)| !found).map(|(hash, _)| hash).collect();
+                Ok(SyncMessage::Want { hashes: wanted })
+            }
+            SyncMessage::Chunks { chunks } => {
+                {
+                    let mut write_id = self.write_id();
+                    if write_id.is_none() {
+                        *write_id = self.store.begin_write()?;
+                    }
+                }
+                if let Err(e) = self.put_chunks(chunks).await {
+                    // The source will not finish this sync; a rerun registers a new write.
+                    self.end_write()?;
+                    return Err(e);
+                }
+                Ok(SyncMessage::Ack)
+            }
+            SyncMessage::Done { root } => {
+                self.end_write()?;
+                if let Some(root_hash) = root && !self.store.exists(&root_hash).await? {
+                    return Err(ProllyError::ChunkNotFound(root_hash));
+                }
+                Ok(SyncMessage::Ack)
+            }
+            SyncMessage::Want { .. } | SyncMessage::Ack => Err(ProllyError::InvalidOperation(
+                "Sync destination received a message only the destination sends".to_string(),
+            )),
+        }
+    }
+
+    async fn put_chunks(&self, chunks: Vec<(Hash, Vec<u8>)>) -> Result<()> {
+        for (hash, bytes) in chunks {
+            let stored_hash = self.store.put(bytes).await?;
+            if stored_hash != hash {
+                return Err(ProllyError::ChecksumMismatch {
+                    context: format!("Synced chunk {:?} was stored under hash {:?}", hash, stored_hash),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the write registered by the first `Chunks` message, if it is still pending.
+    fn end_write(&self) -> Result<()> {
+        let write_id = self.write_id().take();
+        match write_id {
+            Some(write_id) => self.store.end_write(write_id),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Releases the registration of a sync that never reached `Done`, e.g. because the source
+/// failed or the transport was dropped, so the store's collections are not held back by it.
+impl<D: ChunkStore + ?Sized> Drop for SyncDestination<D> {
+    fn drop(&mut self) {
+        if let Err(e) = self.end_write() {
+            warn!("Sync destination failed to end its pending write: {}", e);
+        }
+    }
+}
+
+/// A `Transport` that hands messages directly to a `SyncDestination` in the same process.
+#[derive(Debug)]
+pub struct InProcessTransport<D: ChunkStore + ?Sized> {
+    destination: SyncDestination<D>,
+}
+
+impl<D: ChunkStore + ?Sized> InProcessTransport<D> {
+    pub fn new(destination_store: Arc<D>) -> Self {
+        Self { destination: SyncDestination::new(destination_store) }
+    }
+}
+
+#[async_trait]
+impl<D: ChunkStore + ?Sized> Transport for InProcessTransport<D> {
+    async fn exchange(&self, message: SyncMessage) -> Result<SyncMessage> {
+        self.destination.handle(message).await
+    }
+}
+
+/// Options for `sync_chunks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct SyncOptions {
+    /// Maximum number of hashes in a `Have` message, and of chunks in a `Chunks` message.
+    pub batch_size: usize,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions { batch_size: 256 }
+    }
+}
+
+/// What a sync transferred.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStats {
+    /// Chunks the destination was missing, all of which were sent.
+    pub chunks_sent: u64,
+    /// Total size of the chunks sent.
+    pub bytes_sent: u64,
+    /// Offered chunks the destination already held. Subtrees below them were not walked.
+    pub chunks_skipped: u64,
+    /// Number of messages exchanged.
+    pub round_trips: u64,
+}
+
+/// Transfers to the destination behind `transport` every chunk reachable from `root_hash`
+/// in `source` that the destination is missing, assuming that the destination holds the
+/// whole subtree of any node it holds. Each missing node is read once, and its bytes are
+/// kept from the walk until they are sent, so memory grows with the missing nodes (value
+/// chunks are only read when sent).
+pub async fn sync_chunks<S: ChunkStore + ?Sized, T: Transport + ?Sized>(
+    root_hash: Option<Hash>,
+    source: &S,
+    transport: &T,
+    options: SyncOptions,
+) -> Result<SyncStats> {
+    let batch_size = options.batch_size.max(1);
+    let mut stats = SyncStats::default();
+    // The wanted chunks of each level of the walk. Node children are always one level below
+    // their parent, and value chunks one level below their leaf.
+    let mut wanted_levels: Vec<Vec<Hash>> = Vec::new();
+    // The bytes of the wanted nodes, read once to walk them and kept until they are sent.
+    let mut walked_nodes: HashMap<Hash, Vec<u8>> = HashMap::new();
+    let mut offered: HashSet<Hash> = HashSet::new();
+    // (hash, whether the chunk is a node whose references must be followed)
+    let mut level: Vec<(Hash, bool)> = root_hash.into_iter().map(|hash| (hash, true)).collect();
+    offered.extend(root_hash);
+
+    while !level.is_empty() {
+        let mut wanted_here = Vec::new();
+        let mut next_level = Vec::new();
+        for batch in level.chunks(batch_size) {
+            let hashes: Vec<Hash> = batch.iter().map(|(hash, _)| *hash).collect();
+            let wanted = match exchange(transport, SyncMessage::Have { hashes: hashes.clone() }, &mut stats).await? {
+                SyncMessage::Want { hashes: wanted } => wanted,
+                other => return Err(unexpected_reply("Have", &other)),
+            };
+            let wanted_set: HashSet<Hash> = wanted.iter().copied().collect();
+            if wanted_set.len() != wanted.len() || !wanted.iter().all(|hash| hashes.contains(hash)) {
+                return Err(ProllyError::InvalidOperation("Sync destination wanted chunks that were not offered".to_string()));
+            }
+            stats.chunks_skipped += (hashes.len() - wanted.len()) as u64;
+            let wanted_nodes: Vec<Hash> = batch.iter().filter(|(hash, is_node)| *is_node && wanted_set.contains(hash)).map(|(hash, _)| *hash).collect();
+            let node_bytes = check_batch_len("get_many", wanted_nodes.len(), source.get_many(&wanted_nodes).await?)?;
+            for (hash, bytes) in wanted_nodes.into_iter().zip(node_bytes) {
+                let bytes = bytes.ok_or(ProllyError::ChunkNotFound(hash))?;
+                for reference in node_references(&Node::decode(&bytes)?) {
+                    if offered.insert(reference.0) {
+                        next_level.push(reference);
+                    }
+                }
+                walked_nodes.insert(hash, bytes);
+            }
+            wanted_here.extend(wanted);
+        }
+        wanted_levels.push(wanted_here);
+        level = next_level;
+    }
+
+    let missing: Vec<Hash> = wanted_levels.into_iter().rev().flatten().collect();
+    debug!("Sync: sending {} missing chunks", missing.len());
+    for batch in missing.chunks(batch_size) {
+        let unread: Vec<Hash> = batch.iter().filter(|hash| !walked_nodes.contains_key(*hash)).copied().collect();
+        let mut read = check_batch_len("get_many", unread.len(), source.get_many(&unread).await?)?.into_iter();
+        let mut chunks = Vec::with_capacity(batch.len());
+        for hash in batch {
+            let bytes = match walked_nodes.remove(hash) {
+                Some(bytes) => bytes,
+                None => read.next().flatten().ok_or(ProllyError::ChunkNotFound(*hash))?,
+            };
+            stats.bytes_sent += bytes.len() as u64;
+            chunks.push((*hash, bytes));
+        }
+        stats.chunks_sent += chunks.len() as u64;
+        match exchange(transport, SyncMessage::Chunks { chunks }, &mut stats).await? {
+            SyncMessage::Ack => {}
+            other => return Err(unexpected_reply("Chunks", &other)),
+        }
+    }
+    match exchange(transport, SyncMessage::Done { root: root_hash }, &mut stats).await? {
+        SyncMessage::Ack => Ok(stats),
+        other => Err(unexpected_reply("Done", &other)),
+    }
+}
+
+/// Copies the chunks reachable from `root_hash` that `destination` is missing from `source`,
+/// over an `InProcessTransport`.
+pub async fn sync_to_store<S: ChunkStore + ?Sized, D: ChunkStore + ?Sized>(
+    root_hash: Option<Hash>,
+    source: &S,
+    destination: Arc<D>,
+    options: SyncOptions,
+) -> Result<SyncStats> {
+    sync_chunks(root_hash, source, &InProcessTransport::new(destination), options).await
+}
+
+async fn exchange<T: Transport + ?Sized>(transport: &T, message: SyncMessage, stats: &mut SyncStats) -> Result<SyncMessage> {
+    stats.round_trips += 1;
+    transport.exchange(message).await
+}
+
+fn unexpected_reply(sent: &str, reply: &SyncMessage) -> ProllyError {
+    ProllyError::InvalidOperation(format!("Unexpected reply to a sync {} message: {:?}", sent, reply))
+}
+
+/// The chunks a node references, each paired with whether it is itself a node.
+fn node_references(node: &Node) -> Vec<(Hash, bool)> {
+    match node {
+        Node::Internal { children, .. } => children.iter().map(|child| (child.child_hash, true)).collect(),
+        Node::Leaf { entries, .. } => entries
+            .iter()
+            .flat_map(|entry| match &entry.value {
+                ValueRepr::Inline(_) => Vec::new(),
+                ValueRepr::Chunked(data_hash) => vec![(*data_hash, false)],
+                ValueRepr::ChunkedSequence { chunk_hashes, .. } => chunk_hashes.iter().map(|hash| (*hash, false)).collect(),
+            })
+            .collect(),
+    }
+}
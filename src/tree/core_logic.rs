@@ -1,9 +1,10 @@
 use std::pin::Pin;
 use std::future::Future;
+use std::sync::Arc;
 use log::warn;
 
 use crate::common::{Hash, Key, Value};
-use crate::node::definition::{Node, LeafEntry, InternalEntry, ValueRepr};
+use crate::node::definition::{Node, LeafEntry, InternalEntry, ValueRepr, shortest_separator};
 use crate::store::ChunkStore;
 use crate::error::{Result, ProllyError};
 
@@ -12,13 +13,48 @@ use super::prolly_tree::ProllyTree; // Used for type context and methods like lo
 use super::io;
 use super::modification;
 
+/// Writes a child's update back into its parent's entry list.
+///
+/// Entries other than the last keep their existing separator: the update came from routing
+/// through that entry, so the child's keys are still bounded by it and the right sibling's
+/// keys are still above it. The last entry has no right sibling and tracks the boundary the
+/// child reports. On a split, the left half takes the separator produced by the split and
+/// the new right sibling inherits the entry's previous bound.
+fn apply_child_update(children: &mut Vec<InternalEntry>, child_idx: usize, update: ProcessedNodeUpdate) {
+    let is_last_child = child_idx + 1 == children.len();
+    let entry = &mut children[child_idx];
+    entry.child_hash = update.new_hash;
+    entry.num_items_subtree = update.new_item_count;
+
+    match update.split_info {
+        Some((right_boundary_key, right_hash, right_item_count)) => {
+            let right_boundary_key = if is_last_child {
+                right_boundary_key
+            } else {
+                std::mem::take(&mut entry.boundary_key)
+            };
+            entry.boundary_key = update.new_boundary_key;
+            children.insert(child_idx + 1, InternalEntry {
+                boundary_key: right_boundary_key,
+                child_hash: right_hash,
+                num_items_subtree: right_item_count,
+            });
+        }
+        None => {
+            if is_last_child {
+                entry.boundary_key = update.new_boundary_key;
+            }
+        }
+    }
+}
+
 pub(super) fn get_recursive_sync_impl<S: ChunkStore>(
     tree: &ProllyTree<S>,
     node_hash: Hash,
     key: &Key,
 ) -> Result<Option<Value>> {
     let node = tree.load_node_sync(&node_hash)?;
-    match node {
+    match node.as_ref() {
         Node::Leaf { entries, .. } => {
             match entries.binary_search_by(|e| e.key.as_slice().cmp(key.as_slice())) {
                 Ok(index) => {
@@ -52,7 +88,7 @@ pub(super) fn get_recursive_impl<'s, S: ChunkStore + 's>(
 ) -> Pin<Box<dyn Future<Output = Result<Option<Value>>> + Send + 's>> {
     Box::pin(async move {
         let node = tree.load_node(&node_hash).await?;
-        match node {
+        match node.as_ref() {
             Node::Leaf { entries, .. } => {
                 match entries.binary_search_by(|e| e.key.as_slice().cmp(key.as_slice())) {
                     Ok(index) => {
@@ -108,7 +144,7 @@ pub(super) fn insert_recursive_impl<'s, S: ChunkStore + 's>(
     level: u8,
 ) -> Pin<Box<dyn Future<Output = Result<ProcessedNodeUpdate>> + Send + 's>> {
     Box::pin(async move {
-        let mut current_node_obj = tree.load_node(&current_node_hash).await?;
+        let mut current_node_obj = Arc::unwrap_or_clone(tree.load_node(&current_node_hash).await?);
 
         match &mut current_node_obj {
             Node::Leaf { entries, .. } => {
@@ -127,10 +163,13 @@ pub(super) fn insert_recursive_impl<'s, S: ChunkStore + 's>(
                     let right_split_item_count = right_sibling_entries.len() as u64;
 
                     let right_sibling_boundary_key = right_sibling_entries.last().ok_or_else(|| ProllyError::InternalError("Split leaf created empty right sibling".to_string()))?.key.clone();
+                    let right_sibling_first_key = right_sibling_entries[0].key.clone();
                     let right_sibling_node = Node::Leaf { level: 0, entries: right_sibling_entries };
-                    let (_r_b, right_sibling_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &right_sibling_node).await?;
-
-                    let (left_boundary_key, left_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &current_node_obj).await?;
+                    let stored = io::store_nodes_and_get_key_hash_pairs(&tree.store, &tree.node_cache, &[&right_sibling_node, &current_node_obj]).await?;
+                    let right_sibling_hash = stored[0].1;
+                    let (left_max_key, left_hash) = stored[1].clone();
+                    // The parent only needs a key that separates the two halves, not the full largest key.
+                    let left_boundary_key = shortest_separator(&left_max_key, &right_sibling_first_key);
 
                     Ok(ProcessedNodeUpdate {
                         new_hash: left_hash,
@@ -139,7 +178,7 @@ pub(super) fn insert_recursive_impl<'s, S: ChunkStore + 's>(
                         split_info: Some((right_sibling_boundary_key, right_sibling_hash, right_split_item_count)),
                     })
                 } else { // Leaf does not split
-                    let (new_boundary_key, new_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &current_node_obj).await?;
+                    let (new_boundary_key, new_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &tree.node_cache, &current_node_obj).await?;
                     Ok(ProcessedNodeUpdate {
                         new_hash,
                         new_boundary_key,
@@ -162,39 +201,37 @@ pub(super) fn insert_recursive_impl<'s, S: ChunkStore + 's>(
 
                 let child_update_result = insert_recursive_impl(tree, child_to_descend_hash, key, value_repr, child_level).await?;
 
-                children[child_idx_to_descend].child_hash = child_update_result.new_hash;
-                children[child_idx_to_descend].boundary_key = child_update_result.new_boundary_key;
-                children[child_idx_to_descend].num_items_subtree = child_update_result.new_item_count;
-
-                let mut split_to_propagate_upwards: Option<(Key, Hash, u64)> = None;
+                let child_split = child_update_result.split_info.is_some();
+                apply_child_update(children, child_idx_to_descend, child_update_result);
 
-                if let Some((boundary_from_child_split, new_child_sibling_hash, child_sibling_item_count)) = child_update_result.split_info {
-                    let new_internal_entry = InternalEntry {
-                        boundary_key: boundary_from_child_split,
-                        child_hash: new_child_sibling_hash,
-                        num_items_subtree: child_sibling_item_count,
-                    };
+                let mut split_to_propagate_upwards: Option<(Key, Node, u64)> = None;
 
-                    let pos_to_insert_sibling = children.binary_search_by_key(&&new_internal_entry.boundary_key, |e| &e.boundary_key).unwrap_or_else(|e| e);
-                    children.insert(pos_to_insert_sibling, new_internal_entry);
-
-                    if children.len() > tree.config.target_fanout { // Internal node itself splits
-                        let mid_idx = children.len() / 2;
-                        let right_sibling_children_entries = children.split_off(mid_idx);
-                        
-                        let _left_internal_node_item_count: u64 = children.iter().map(|c| c.num_items_subtree).sum();
-                        let right_internal_node_item_count: u64 = right_sibling_children_entries.iter().map(|c| c.num_items_subtree).sum();
+                if child_split && children.len() > tree.config.target_fanout { // Internal node itself splits
+                    let mid_idx = children.len() / 2;
+                    let right_sibling_children_entries = children.split_off(mid_idx);
+                    
+                    let _left_internal_node_item_count: u64 = children.iter().map(|c| c.num_items_subtree).sum();
+                    let right_internal_node_item_count: u64 = right_sibling_children_entries.iter().map(|c| c.num_items_subtree).sum();
 
-                        let right_sibling_boundary_key = right_sibling_children_entries.last().ok_or_else(|| ProllyError::InternalError("Split internal created empty right sibling".to_string()))?.boundary_key.clone();
-                        let right_sibling_node = Node::Internal { level, children: right_sibling_children_entries };
-                        let (_r_b, right_sibling_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &right_sibling_node).await?;
+                    let right_sibling_boundary_key = right_sibling_children_entries.last().ok_or_else(|| ProllyError::InternalError("Split internal created empty right sibling".to_string()))?.boundary_key.clone();
+                    let right_sibling_node = Node::Internal { level, children: right_sibling_children_entries };
 
-                        split_to_propagate_upwards = Some((right_sibling_boundary_key, right_sibling_hash, right_internal_node_item_count));
-                    }
+                    split_to_propagate_upwards = Some((right_sibling_boundary_key, right_sibling_node, right_internal_node_item_count));
                 }
                 
                 let current_node_total_items: u64 = children.iter().map(|c| c.num_items_subtree).sum();
-                let (current_node_new_boundary, current_node_new_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &current_node_obj).await?;
+                // Both halves of a split are stored in one call.
+                let (current_node_new_boundary, current_node_new_hash, split_to_propagate_upwards) = match split_to_propagate_upwards {
+                    Some((right_sibling_boundary_key, right_sibling_node, right_internal_node_item_count)) => {
+                        let stored = io::store_nodes_and_get_key_hash_pairs(&tree.store, &tree.node_cache, &[&right_sibling_node, &current_node_obj]).await?;
+                        let (current_node_new_boundary, current_node_new_hash) = stored[1].clone();
+                        (current_node_new_boundary, current_node_new_hash, Some((right_sibling_boundary_key, stored[0].1, right_internal_node_item_count)))
+                    }
+                    None => {
+                        let (current_node_new_boundary, current_node_new_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &tree.node_cache, &current_node_obj).await?;
+                        (current_node_new_boundary, current_node_new_hash, None)
+                    }
+                };
 
                 Ok(ProcessedNodeUpdate {
                     new_hash: current_node_new_hash,
@@ -215,7 +252,7 @@ pub(super) fn insert_recursive_sync_impl<S: ChunkStore>(
     value_repr: ValueRepr,
     level: u8,
 ) -> Result<ProcessedNodeUpdate> {
-    let mut current_node_obj = tree.load_node_sync(&current_node_hash)?;
+    let mut current_node_obj = Arc::unwrap_or_clone(tree.load_node_sync(&current_node_hash)?);
 
     match &mut current_node_obj {
         Node::Leaf { entries, .. } => {
@@ -231,9 +268,11 @@ pub(super) fn insert_recursive_sync_impl<S: ChunkStore>(
                 let left_split_item_count = entries.len() as u64;
                 let right_split_item_count = right_sibling_entries.len() as u64;
                 let right_sibling_boundary_key = right_sibling_entries.last().ok_or_else(|| ProllyError::InternalError("Split leaf created empty right sibling".to_string()))?.key.clone();
+                let right_sibling_first_key = right_sibling_entries[0].key.clone();
                 let right_sibling_node = Node::Leaf { level: 0, entries: right_sibling_entries };
-                let (_r_b, right_sibling_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &right_sibling_node)?;
-                let (left_boundary_key, left_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &current_node_obj)?;
+                let (_r_b, right_sibling_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &right_sibling_node)?;
+                let (left_max_key, left_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &current_node_obj)?;
+                let left_boundary_key = shortest_separator(&left_max_key, &right_sibling_first_key);
                 Ok(ProcessedNodeUpdate {
                     new_hash: left_hash,
                     new_boundary_key: left_boundary_key,
@@ -241,7 +280,7 @@ pub(super) fn insert_recursive_sync_impl<S: ChunkStore>(
                     split_info: Some((right_sibling_boundary_key, right_sibling_hash, right_split_item_count)),
                 })
             } else {
-                let (new_boundary_key, new_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &current_node_obj)?;
+                let (new_boundary_key, new_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &current_node_obj)?;
                 Ok(ProcessedNodeUpdate {
                     new_hash,
                     new_boundary_key,
@@ -261,30 +300,20 @@ pub(super) fn insert_recursive_sync_impl<S: ChunkStore>(
             let child_to_descend_hash = children[child_idx_to_descend].child_hash;
             let child_level = level - 1;
             let child_update_result = insert_recursive_sync_impl(tree, child_to_descend_hash, key, value_repr, child_level)?;
-            children[child_idx_to_descend].child_hash = child_update_result.new_hash;
-            children[child_idx_to_descend].boundary_key = child_update_result.new_boundary_key;
-            children[child_idx_to_descend].num_items_subtree = child_update_result.new_item_count;
+            let child_split = child_update_result.split_info.is_some();
+            apply_child_update(children, child_idx_to_descend, child_update_result);
             let mut split_to_propagate_upwards: Option<(Key, Hash, u64)> = None;
-            if let Some((boundary_from_child_split, new_child_sibling_hash, child_sibling_item_count)) = child_update_result.split_info {
-                let new_internal_entry = InternalEntry {
-                    boundary_key: boundary_from_child_split,
-                    child_hash: new_child_sibling_hash,
-                    num_items_subtree: child_sibling_item_count,
-                };
-                let pos_to_insert_sibling = children.binary_search_by_key(&&new_internal_entry.boundary_key, |e| &e.boundary_key).unwrap_or_else(|e| e);
-                children.insert(pos_to_insert_sibling, new_internal_entry);
-                if children.len() > tree.config.target_fanout {
-                    let mid_idx = children.len() / 2;
-                    let right_sibling_children_entries = children.split_off(mid_idx);
-                    let right_internal_node_item_count: u64 = right_sibling_children_entries.iter().map(|c| c.num_items_subtree).sum();
-                    let right_sibling_boundary_key = right_sibling_children_entries.last().ok_or_else(|| ProllyError::InternalError("Split internal created empty right sibling".to_string()))?.boundary_key.clone();
-                    let right_sibling_node = Node::Internal { level, children: right_sibling_children_entries };
-                    let (_r_b, right_sibling_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &right_sibling_node)?;
-                    split_to_propagate_upwards = Some((right_sibling_boundary_key, right_sibling_hash, right_internal_node_item_count));
-                }
+            if child_split && children.len() > tree.config.target_fanout {
+                let mid_idx = children.len() / 2;
+                let right_sibling_children_entries = children.split_off(mid_idx);
+                let right_internal_node_item_count: u64 = right_sibling_children_entries.iter().map(|c| c.num_items_subtree).sum();
+                let right_sibling_boundary_key = right_sibling_children_entries.last().ok_or_else(|| ProllyError::InternalError("Split internal created empty right sibling".to_string()))?.boundary_key.clone();
+                let right_sibling_node = Node::Internal { level, children: right_sibling_children_entries };
+                let (_r_b, right_sibling_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &right_sibling_node)?;
+                split_to_propagate_upwards = Some((right_sibling_boundary_key, right_sibling_hash, right_internal_node_item_count));
             }
             let current_node_total_items: u64 = children.iter().map(|c| c.num_items_subtree).sum();
-            let (current_node_new_boundary, current_node_new_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &current_node_obj)?;
+            let (current_node_new_boundary, current_node_new_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &current_node_obj)?;
             Ok(ProcessedNodeUpdate {
                 new_hash: current_node_new_hash,
                 new_boundary_key: current_node_new_boundary,
@@ -303,7 +332,7 @@ pub(super) fn delete_recursive_impl<'s, S: ChunkStore + 's>(
     key_actually_deleted_flag: &'s mut bool,
 ) -> Pin<Box<dyn Future<Output = Result<DeleteRecursionResult>> + Send + 's>> {
     Box::pin(async move {
-        let mut current_node_obj = tree.load_node(&node_hash).await?;
+        let mut current_node_obj = Arc::unwrap_or_clone(tree.load_node(&node_hash).await?);
 
         match &mut current_node_obj {
             Node::Leaf { entries, .. } => {
@@ -315,7 +344,7 @@ pub(super) fn delete_recursive_impl<'s, S: ChunkStore + 's>(
                             return Ok(DeleteRecursionResult::Merged);
                         } else {
                             let new_leaf_item_count = entries.len() as u64;
-                            let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &current_node_obj).await?;
+                            let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &tree.node_cache, &current_node_obj).await?;
                             Ok(DeleteRecursionResult::Updated(ProcessedNodeUpdate {
                                 new_hash,
                                 new_boundary_key: new_boundary,
@@ -361,11 +390,10 @@ pub(super) fn delete_recursive_impl<'s, S: ChunkStore + 's>(
                         })
                     }
                     DeleteRecursionResult::Updated(child_update) => {
-                        children[child_idx_to_descend].child_hash = child_update.new_hash;
-                        children[child_idx_to_descend].boundary_key = child_update.new_boundary_key;
-                        children[child_idx_to_descend].num_items_subtree = child_update.new_item_count;
+                        let updated_child_hash = child_update.new_hash;
+                        apply_child_update(children, child_idx_to_descend, child_update);
 
-                        let child_node_after_update = tree.load_node(&child_update.new_hash).await?;
+                        let child_node_after_update = tree.load_node(&updated_child_hash).await?;
                         if child_node_after_update.is_underflow(&tree.config) {
                             modification::handle_underflow_strategy(tree, children, child_idx_to_descend).await?;
                             if children.is_empty() { // Current internal node itself merged away
@@ -374,7 +402,7 @@ pub(super) fn delete_recursive_impl<'s, S: ChunkStore + 's>(
                         }
                         
                         let current_node_total_items: u64 = children.iter().map(|c| c.num_items_subtree).sum();
-                        let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &current_node_obj).await?;
+                        let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &tree.node_cache, &current_node_obj).await?;
                         Ok(DeleteRecursionResult::Updated(ProcessedNodeUpdate {
                             new_hash,
                             new_boundary_key: new_boundary,
@@ -390,7 +418,7 @@ pub(super) fn delete_recursive_impl<'s, S: ChunkStore + 's>(
                         }
                         
                         let current_node_total_items: u64 = children.iter().map(|c| c.num_items_subtree).sum();
-                        let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &current_node_obj).await?;
+                        let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &tree.node_cache, &current_node_obj).await?;
                         Ok(DeleteRecursionResult::Updated(ProcessedNodeUpdate {
                             new_hash,
                             new_boundary_key: new_boundary,
@@ -412,7 +440,7 @@ pub(super) fn delete_recursive_sync_impl<S: ChunkStore>(
     level: u8,
     key_actually_deleted_flag: &mut bool,
 ) -> Result<DeleteRecursionResult> {
-    let mut current_node_obj = tree.load_node_sync(&node_hash)?;
+    let mut current_node_obj = Arc::unwrap_or_clone(tree.load_node_sync(&node_hash)?);
     match &mut current_node_obj {
         Node::Leaf { entries, .. } => {
             match entries.binary_search_by(|e| e.key.as_slice().cmp(key.as_slice())) {
@@ -423,7 +451,7 @@ pub(super) fn delete_recursive_sync_impl<S: ChunkStore>(
                         return Ok(DeleteRecursionResult::Merged);
                     } else {
                         let new_leaf_item_count = entries.len() as u64;
-                        let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &current_node_obj)?;
+                        let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &current_node_obj)?;
                         Ok(DeleteRecursionResult::Updated(ProcessedNodeUpdate {
                             new_hash,
                             new_boundary_key: new_boundary,
@@ -464,10 +492,9 @@ pub(super) fn delete_recursive_sync_impl<S: ChunkStore>(
                     })
                 }
                 DeleteRecursionResult::Updated(child_update) => {
-                    children[child_idx_to_descend].child_hash = child_update.new_hash;
-                    children[child_idx_to_descend].boundary_key = child_update.new_boundary_key;
-                    children[child_idx_to_descend].num_items_subtree = child_update.new_item_count;
-                    let child_node_after_update = tree.load_node_sync(&child_update.new_hash)?;
+                    let updated_child_hash = child_update.new_hash;
+                    apply_child_update(children, child_idx_to_descend, child_update);
+                    let child_node_after_update = tree.load_node_sync(&updated_child_hash)?;
                     if child_node_after_update.is_underflow(&tree.config) {
                         modification::handle_underflow_strategy_sync(tree, children, child_idx_to_descend)?;
                         if children.is_empty() {
@@ -475,7 +502,7 @@ pub(super) fn delete_recursive_sync_impl<S: ChunkStore>(
                         }
                     }
                     let current_node_total_items: u64 = children.iter().map(|c| c.num_items_subtree).sum();
-                    let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &current_node_obj)?;
+                    let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &current_node_obj)?;
                     Ok(DeleteRecursionResult::Updated(ProcessedNodeUpdate {
                         new_hash,
                         new_boundary_key: new_boundary,
@@ -489,7 +516,7 @@ pub(super) fn delete_recursive_sync_impl<S: ChunkStore>(
                         return Ok(DeleteRecursionResult::Merged);
                     }
                     let current_node_total_items: u64 = children.iter().map(|c| c.num_items_subtree).sum();
-                    let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &current_node_obj)?;
+                    let (new_boundary, new_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &current_node_obj)?;
                     Ok(DeleteRecursionResult::Updated(ProcessedNodeUpdate {
                         new_hash,
                         new_boundary_key: new_boundary,
@@ -2,10 +2,12 @@
 use std::sync::Arc;
 use std::cmp::Ordering;
 use log::warn; 
+use futures::future::join_all;
 
 use crate::common::{Hash, Key, Value, TreeConfig};
 use crate::error::{Result, ProllyError};
-use crate::node::definition::{Node, ValueRepr, LeafEntry};
+use crate::node::definition::{Node, ValueRepr, LeafEntry, InternalEntry};
+use crate::node::cache::NodeCache;
 use crate::store::ChunkStore;
 use crate::tree::ScanArgs;
 use super::ProllyTree; // Access sibling module
@@ -17,15 +19,16 @@ use super::ProllyTree; // Access sibling module
 pub struct Cursor<S: ChunkStore> {
     /// Reference to the store to load nodes.
     store: Arc<S>,
-    /// Tree configuration (e.g., for max inline size, though less relevant for cursor).
-    #[allow(dead_code)]
-    config: TreeConfig, // Maybe only store Arc<S> is needed? Depends on value reconstruction. Let's keep config for now.
+    /// Decoded-node cache shared with the tree that created this cursor.
+    node_cache: Arc<NodeCache>,
+    /// Tree configuration; the cursor uses its `read_ahead` setting.
+    config: TreeConfig,
     
     /// Stack representing the path from the root to the current leaf.
     /// Each tuple: (node_hash, loaded_node_object, index_in_parent)
     /// The last element is the current leaf node.
     /// usize is the index *within the parent's children list* that points to this node. For root, index is usize::MAX or similar sentinel.
-    path: Vec<(Hash, Node, usize)>,
+    path: Vec<(Hash, Arc<Node>, usize)>,
 
     /// The index of the *next* entry to yield within the current leaf node.
     current_leaf_entry_idx: usize,
@@ -36,6 +39,7 @@ impl<S: ChunkStore> Cursor<S> {
     /// Requires navigating down the leftmost path to the first leaf.
     pub(crate) async fn new_at_start(tree: &ProllyTree<S>) -> Result<Self> {
         let store = Arc::clone(&tree.store);
+        let node_cache = Arc::clone(&tree.node_cache);
         let config = tree.config.clone();
         let mut path = Vec::new();
         let current_leaf_entry_idx = 0;
@@ -46,7 +50,7 @@ impl<S: ChunkStore> Cursor<S> {
 
             loop {
                 let current_node = tree.load_node(&current_hash).await?;
-                let is_leaf = matches!(current_node, Node::Leaf { .. });
+                let is_leaf = matches!(*current_node, Node::Leaf { .. });
                 
                 path.push((current_hash, current_node.clone(), parent_idx)); // Clone node into path
 
@@ -54,7 +58,7 @@ impl<S: ChunkStore> Cursor<S> {
                     break; // Reached the leftmost leaf
                 }
 
-                match current_node {
+                match current_node.as_ref() {
                     Node::Internal { children, .. } => {
                         if children.is_empty() {
                             // Invalid state: internal node with no children reached during descent
@@ -71,13 +75,14 @@ impl<S: ChunkStore> Cursor<S> {
             // Empty tree, path remains empty, index 0
         }
 
-        Ok(Self { store, config, path, current_leaf_entry_idx })
+        Ok(Self { store, node_cache, config, path, current_leaf_entry_idx })
     }
 
     /// Creates a new cursor positioned at or after the given key.
     /// Requires navigating down the tree to find the relevant leaf and position.
     pub(crate) async fn new_at_key(tree: &ProllyTree<S>, start_key: &Key) -> Result<Self> {
          let store = Arc::clone(&tree.store);
+         let node_cache = Arc::clone(&tree.node_cache);
          let config = tree.config.clone();
          let mut path = Vec::new();
          let mut current_leaf_entry_idx = 0; // Will be set precisely later
@@ -91,7 +96,7 @@ impl<S: ChunkStore> Cursor<S> {
                  let current_parent_idx = parent_idx_stack.last().copied().unwrap_or(usize::MAX);
                  path.push((current_hash, current_node.clone(), current_parent_idx));
 
-                match current_node {
+                match current_node.as_ref() {
                     Node::Leaf { entries, .. } => {
                          // Find the index of the first entry >= start_key
                          match entries.binary_search_by(|e| e.key.as_slice().cmp(start_key.as_slice())) {
@@ -121,7 +126,7 @@ impl<S: ChunkStore> Cursor<S> {
         }
          // If tree is empty or key is > all keys, path might be empty or point past last element
 
-         Ok(Self { store, config, path, current_leaf_entry_idx })
+         Ok(Self { store, node_cache, config, path, current_leaf_entry_idx })
     }
 
 
@@ -135,7 +140,7 @@ impl<S: ChunkStore> Cursor<S> {
                 None => return Ok(None), // Path is empty, iteration finished or tree empty
             };
 
-            if let Node::Leaf { entries, .. } = current_leaf_node {
+            if let Node::Leaf { entries, .. } = current_leaf_node.as_ref() {
                 // Try to get the next entry from the current leaf
                 if let Some(entry) = entries.get(self.current_leaf_entry_idx) {
                     // Increment index for next call *before* potentially long value reconstruction
@@ -149,12 +154,7 @@ impl<S: ChunkStore> Cursor<S> {
                                 .ok_or_else(|| ProllyError::ChunkNotFound(*data_hash))?
                         }
                         ValueRepr::ChunkedSequence { chunk_hashes, total_size } => {
-                            let mut reconstructed_value = Vec::with_capacity(*total_size as usize);
-                            for chunk_hash in chunk_hashes {
-                                let chunk_bytes = self.store.get(chunk_hash).await?
-                                    .ok_or_else(|| ProllyError::ChunkNotFound(*chunk_hash))?;
-                                reconstructed_value.extend_from_slice(&chunk_bytes);
-                            }
+                            let reconstructed_value = Self::load_chunk_sequence(&self.store, chunk_hashes, *total_size, self.config.read_ahead).await?;
                              if reconstructed_value.len() as u64 != *total_size {
                                 warn!("Cursor: Reconstructed value size mismatch for key {:?}. Expected {}, got {}.", entry.key, total_size, reconstructed_value.len());
                                 // Consider returning error? Or just potentially bad data?
@@ -186,7 +186,7 @@ impl<S: ChunkStore> Cursor<S> {
     
             let current_leaf_node_cloned = self.path.last().unwrap().1.clone();
     
-            if let Node::Leaf { ref entries, .. } = current_leaf_node_cloned {
+            if let Node::Leaf { entries, .. } = current_leaf_node_cloned.as_ref() {
                 let entry_opt = if !args.reverse {
                     entries.get(self.current_leaf_entry_idx)
                 } else {
@@ -221,15 +221,15 @@ impl<S: ChunkStore> Cursor<S> {
                     return Ok(Some((entry.key.clone(), value)));
                 } else {
                     let advanced = if !args.reverse {
-                        Self::advance_cursor_path_to_next_leaf_static_sync(&mut self.path, &self.store)?
+                        Self::advance_cursor_path_to_next_leaf_static_sync(&mut self.path, &self.store, &self.node_cache)?
                     } else {
-                        Self::advance_cursor_path_to_prev_leaf_static_sync(&mut self.path, &self.store)?
+                        Self::advance_cursor_path_to_prev_leaf_static_sync(&mut self.path, &self.store, &self.node_cache)?
                     };
                     
                     if !advanced { return Ok(None); }
     
                     if let Some((_, new_leaf_node, _)) = self.path.last() {
-                        if let Node::Leaf{entries: new_entries, ..} = new_leaf_node {
+                        if let Node::Leaf{entries: new_entries, ..} = new_leaf_node.as_ref() {
                             self.current_leaf_entry_idx = if !args.reverse { 0 } else { new_entries.len().saturating_sub(1) };
                             if args.reverse && new_entries.is_empty() { self.current_leaf_entry_idx = usize::MAX; }
                         } else { return Err(ProllyError::InternalError("Advanced cursor path did not end in a leaf".to_string())); }
@@ -249,12 +249,7 @@ impl<S: ChunkStore> Cursor<S> {
                     .ok_or_else(|| ProllyError::ChunkNotFound(*data_hash))
             }
             ValueRepr::ChunkedSequence { chunk_hashes, total_size } => {
-                let mut reconstructed_value = Vec::with_capacity(*total_size as usize);
-                for chunk_hash in chunk_hashes {
-                    let chunk_bytes = self.store.get(chunk_hash).await?
-                        .ok_or_else(|| ProllyError::ChunkNotFound(*chunk_hash))?;
-                    reconstructed_value.extend_from_slice(&chunk_bytes);
-                }
+                let reconstructed_value = Self::load_chunk_sequence(&self.store, chunk_hashes, *total_size, self.config.read_ahead).await?;
                 if reconstructed_value.len() as u64 != *total_size {
                      warn!("Cursor/LoadValue: Reconstructed value size mismatch. Expected {}, got {}.", total_size, reconstructed_value.len());
                 }
@@ -280,9 +275,55 @@ impl<S: ChunkStore> Cursor<S> {
         }
     }
 
+    /// Fetches the pieces of a chunked value, requesting up to `read_ahead` pieces beyond
+    /// the current one concurrently.
+    async fn load_chunk_sequence(store: &Arc<S>, chunk_hashes: &[Hash], total_size: u64, read_ahead: usize) -> Result<Value> {
+        let mut reconstructed_value = Vec::with_capacity(total_size as usize);
+        for window in chunk_hashes.chunks(read_ahead.saturating_add(1)) {
+            let pieces = join_all(window.iter().map(|chunk_hash| store.get(chunk_hash))).await;
+            for (chunk_hash, piece) in window.iter().zip(pieces) {
+                let chunk_bytes = piece?.ok_or_else(|| ProllyError::ChunkNotFound(*chunk_hash))?;
+                reconstructed_value.extend_from_slice(&chunk_bytes);
+            }
+        }
+        Ok(reconstructed_value)
+    }
+
+    /// Loads the child at `idx` of an internal node. If it is not cached yet, up to `read_ahead`
+    /// of the siblings that a traversal in the given direction visits next are fetched into the
+    /// node cache concurrently with it, so the following leaf moves do not wait on the store.
+    async fn load_child_with_read_ahead(
+        store: &Arc<S>,
+        node_cache: &NodeCache,
+        siblings: &[InternalEntry],
+        idx: usize,
+        reverse: bool,
+        read_ahead: usize,
+    ) -> Result<Arc<Node>> {
+        let hash = siblings[idx].child_hash;
+        if read_ahead == 0 || node_cache.config().max_nodes == 0 || node_cache.contains(&hash) {
+            return node_cache.load(store.as_ref(), &hash).await;
+        }
+        let upcoming: Vec<Hash> = if !reverse {
+            siblings[idx + 1..].iter().take(read_ahead).map(|entry| entry.child_hash).collect()
+        } else {
+            siblings[..idx].iter().rev().take(read_ahead).map(|entry| entry.child_hash).collect()
+        };
+        let prefetch = join_all(
+            upcoming.iter()
+                .filter(|sibling_hash| !node_cache.contains(sibling_hash))
+                .map(|sibling_hash| node_cache.load(store.as_ref(), sibling_hash)),
+        );
+        // Prefetch failures are ignored; they resurface when the cursor actually reaches that node.
+        let (node, _) = futures::join!(node_cache.load(store.as_ref(), &hash), prefetch);
+        node
+    }
+
     async fn advance_cursor_path_to_next_leaf_static(
-        path: &mut Vec<(Hash, Node, usize)>,
-        store: &Arc<S>
+        path: &mut Vec<(Hash, Arc<Node>, usize)>,
+        store: &Arc<S>,
+        node_cache: &NodeCache,
+        read_ahead: usize,
     ) -> Result<bool> {
         if path.is_empty() { return Ok(false); }
         loop {
@@ -292,49 +333,48 @@ impl<S: ChunkStore> Cursor<S> {
             let (_parent_hash, parent_node_ref, _parent_parent_idx) = match path.last() {
                  Some(parent_info) => parent_info, None => return Ok(false),
             };
-            let parent_node_cloned = parent_node_ref.clone();
-            if let Node::Internal { children, .. } = parent_node_cloned {
+            let mut parent_descend = Arc::clone(parent_node_ref);
+            if let Node::Internal { children, .. } = parent_descend.as_ref() {
                  let next_sibling_idx_in_parent = popped_idx_in_parent + 1;
                  if next_sibling_idx_in_parent < children.len() {
-                     let mut current_hash_descend = children[next_sibling_idx_in_parent].child_hash;
                      let mut current_idx_in_its_parent_descend = next_sibling_idx_in_parent;
                      loop {
-                        let current_node_obj_descend = Node::decode(&store.get(&current_hash_descend).await?.ok_or_else(|| ProllyError::ChunkNotFound(current_hash_descend))?)?;
-                        let is_leaf = matches!(current_node_obj_descend, Node::Leaf { .. });
-                        path.push((current_hash_descend, current_node_obj_descend.clone(), current_idx_in_its_parent_descend));
-                        if is_leaf { return Ok(true); }
-                        match current_node_obj_descend {
+                        let Node::Internal { children: siblings_descend, .. } = parent_descend.as_ref() else { unreachable!() };
+                        let current_hash_descend = siblings_descend[current_idx_in_its_parent_descend].child_hash;
+                        let current_node_obj_descend = Self::load_child_with_read_ahead(store, node_cache, siblings_descend, current_idx_in_its_parent_descend, false, read_ahead).await?;
+                        path.push((current_hash_descend, Arc::clone(&current_node_obj_descend), current_idx_in_its_parent_descend));
+                        match current_node_obj_descend.as_ref() {
+                              Node::Leaf { .. } => return Ok(true),
                               Node::Internal { children: c_descend, .. } => {
                                    if c_descend.is_empty() { return Err(ProllyError::InternalError("Empty internal node during static advance".to_string())); }
                                    current_idx_in_its_parent_descend = 0;
-                                   current_hash_descend = c_descend[0].child_hash;
                               }
-                               Node::Leaf { .. } => unreachable!(),
                          }
+                        parent_descend = current_node_obj_descend;
                      }
                  } else { continue; }
             } else { return Err(ProllyError::InternalError("Cursor path parent not internal during static advance".to_string())); }
         }
     }
 
-    fn advance_cursor_path_to_next_leaf_static_sync(path: &mut Vec<(Hash, Node, usize)>, store: &Arc<S>) -> Result<bool> {
+    fn advance_cursor_path_to_next_leaf_static_sync(path: &mut Vec<(Hash, Arc<Node>, usize)>, store: &Arc<S>, node_cache: &NodeCache) -> Result<bool> {
         if path.is_empty() { return Ok(false); }
         loop {
             let (_popped_hash, _popped_node, popped_idx_in_parent) = path.pop().ok_or(ProllyError::InternalError("Path empty in advance sync".into()))?;
             if path.is_empty() { return Ok(false); }
             let (_parent_hash, parent_node, _parent_idx) = path.last().ok_or(ProllyError::InternalError("Parent not found in advance sync".into()))?;
 
-            if let Node::Internal { children, .. } = parent_node {
+            if let Node::Internal { children, .. } = parent_node.as_ref() {
                 let next_sibling_idx = popped_idx_in_parent + 1;
                 if next_sibling_idx < children.len() {
                     let mut current_hash = children[next_sibling_idx].child_hash;
                     let mut current_idx = next_sibling_idx;
                     loop {
-                        let node = Node::decode(&store.get_sync(&current_hash)?.ok_or(ProllyError::ChunkNotFound(current_hash))?)?;
-                        let is_leaf = matches!(&node, Node::Leaf {..});
+                        let node = node_cache.load_sync(store.as_ref(), &current_hash)?;
+                        let is_leaf = matches!(*node, Node::Leaf {..});
                         path.push((current_hash, node.clone(), current_idx));
                         if is_leaf { return Ok(true); }
-                        if let Node::Internal { children, .. } = node {
+                        if let Node::Internal { children, .. } = node.as_ref() {
                             if children.is_empty() { return Err(ProllyError::InternalError("Empty internal node found".into())); }
                             current_hash = children[0].child_hash;
                             current_idx = 0;
@@ -346,8 +386,10 @@ impl<S: ChunkStore> Cursor<S> {
     }
 
     async fn advance_cursor_path_to_prev_leaf_static(
-        path: &mut Vec<(Hash, Node, usize)>,
-        store: &Arc<S>
+        path: &mut Vec<(Hash, Arc<Node>, usize)>,
+        store: &Arc<S>,
+        node_cache: &NodeCache,
+        read_ahead: usize,
     ) -> Result<bool> {
         if path.is_empty() { return Ok(false); }
         loop {
@@ -357,35 +399,34 @@ impl<S: ChunkStore> Cursor<S> {
             let (_parent_hash, parent_node_ref, _parent_parent_idx) = match path.last() {
                  Some(parent_info) => parent_info, None => return Ok(false),
             };
-            let parent_node_cloned = parent_node_ref.clone();
-            if let Node::Internal { children, .. } = parent_node_cloned {
+            let mut parent_descend = Arc::clone(parent_node_ref);
+            if let Node::Internal { .. } = parent_descend.as_ref() {
                  if popped_idx_in_parent == usize::MAX { return Ok(false); }
                  let prev_sibling_idx_in_parent = popped_idx_in_parent.checked_sub(1);
 
                  if let Some(prev_idx) = prev_sibling_idx_in_parent {
                     // prev_idx < children.len() is inherently true if Some(prev_idx)
-                    let mut current_hash_descend = children[prev_idx].child_hash;
                     let mut current_idx_in_its_parent_descend = prev_idx;
                     loop {
-                        let current_node_obj_descend = Node::decode(&store.get(&current_hash_descend).await?.ok_or_else(|| ProllyError::ChunkNotFound(current_hash_descend))?)?;
-                        let is_leaf = matches!(current_node_obj_descend, Node::Leaf { .. });
-                        path.push((current_hash_descend, current_node_obj_descend.clone(), current_idx_in_its_parent_descend));
-                        if is_leaf { return Ok(true); }
-                        match current_node_obj_descend {
+                        let Node::Internal { children: siblings_descend, .. } = parent_descend.as_ref() else { unreachable!() };
+                        let current_hash_descend = siblings_descend[current_idx_in_its_parent_descend].child_hash;
+                        let current_node_obj_descend = Self::load_child_with_read_ahead(store, node_cache, siblings_descend, current_idx_in_its_parent_descend, true, read_ahead).await?;
+                        path.push((current_hash_descend, Arc::clone(&current_node_obj_descend), current_idx_in_its_parent_descend));
+                        match current_node_obj_descend.as_ref() {
+                            Node::Leaf { .. } => return Ok(true),
                             Node::Internal { children: c_descend, .. } => {
                                 if c_descend.is_empty() { return Err(ProllyError::InternalError("Empty internal node during prev_leaf advance".to_string())); }
                                 current_idx_in_its_parent_descend = c_descend.len() - 1;
-                                current_hash_descend = c_descend[current_idx_in_its_parent_descend].child_hash;
                             }
-                            Node::Leaf { .. } => unreachable!(),
                         }
+                        parent_descend = current_node_obj_descend;
                     }
                  } else { continue; } 
             } else { return Err(ProllyError::InternalError("Cursor path parent not internal during prev_leaf advance".to_string())); }
         }
     }
 
-    fn advance_cursor_path_to_prev_leaf_static_sync(path: &mut Vec<(Hash, Node, usize)>, store: &Arc<S>) -> Result<bool> {
+    fn advance_cursor_path_to_prev_leaf_static_sync(path: &mut Vec<(Hash, Arc<Node>, usize)>, store: &Arc<S>, node_cache: &NodeCache) -> Result<bool> {
         if path.is_empty() { return Ok(false); }
         loop {
             let (_popped_hash, _popped_node, popped_idx_in_parent) = path.pop().ok_or(ProllyError::InternalError("Path empty in advance sync".into()))?;
@@ -393,16 +434,16 @@ impl<S: ChunkStore> Cursor<S> {
             if popped_idx_in_parent == usize::MAX { return Ok(false); }
             let (_parent_hash, parent_node, _parent_idx) = path.last().ok_or(ProllyError::InternalError("Parent not found in advance sync".into()))?;
 
-            if let Node::Internal { children, .. } = parent_node {
+            if let Node::Internal { children, .. } = parent_node.as_ref() {
                 if let Some(prev_sibling_idx) = popped_idx_in_parent.checked_sub(1) {
                     let mut current_hash = children[prev_sibling_idx].child_hash;
                     let mut current_idx = prev_sibling_idx;
                     loop {
-                        let node = Node::decode(&store.get_sync(&current_hash)?.ok_or(ProllyError::ChunkNotFound(current_hash))?)?;
-                        let is_leaf = matches!(&node, Node::Leaf {..});
+                        let node = node_cache.load_sync(store.as_ref(), &current_hash)?;
+                        let is_leaf = matches!(*node, Node::Leaf {..});
                         path.push((current_hash, node.clone(), current_idx));
                         if is_leaf { return Ok(true); }
-                        if let Node::Internal { children, .. } = node {
+                        if let Node::Internal { children, .. } = node.as_ref() {
                             if children.is_empty() { return Err(ProllyError::InternalError("Empty internal node found".into())); }
                             current_idx = children.len() - 1;
                             current_hash = children[current_idx].child_hash;
@@ -419,12 +460,14 @@ impl<S: ChunkStore> Cursor<S> {
     ) -> Result<Self> {
 
         let store = Arc::clone(&tree.store);
+
+        let node_cache = Arc::clone(&tree.node_cache);
         let config = tree.config.clone();
-        let mut path: Vec<(Hash, Node, usize)> = Vec::new();
+        let mut path: Vec<(Hash, Arc<Node>, usize)> = Vec::new();
         let mut current_leaf_entry_idx: usize = if args.reverse { usize::MAX } else { 0 };
 
         if tree.root_hash.is_none() {
-            return Ok(Self { store, config, path, current_leaf_entry_idx });
+            return Ok(Self { store, node_cache, config, path, current_leaf_entry_idx });
         }
 
         let mut current_hash = tree.root_hash.unwrap();
@@ -440,7 +483,7 @@ impl<S: ChunkStore> Cursor<S> {
         };
 
         if let Some(key_to_find_in_descent) = primary_bound_for_initial_descend {
-            while let Node::Internal { children, .. } = &current_node_obj {
+            while let Node::Internal { children, .. } = current_node_obj.as_ref() {
                 if children.is_empty() { break; }
                 let child_idx_to_descend = children
                     .binary_search_by_key(key_to_find_in_descent, |entry| entry.boundary_key.clone())
@@ -448,34 +491,37 @@ impl<S: ChunkStore> Cursor<S> {
                     .min(children.len().saturating_sub(1));
                 
                 current_hash = children[child_idx_to_descend].child_hash;
-                current_node_obj = tree.load_node(&current_hash).await?;
+                current_node_obj = Self::load_child_with_read_ahead(&store, &node_cache, children, child_idx_to_descend, args.reverse, config.read_ahead).await?;
                 path.push((current_hash, current_node_obj.clone(), child_idx_to_descend));
             }
-            if let Node::Leaf { entries, .. } = &current_node_obj {
+            if let Node::Leaf { entries, .. } = current_node_obj.as_ref() {
                 if !args.reverse {
                     match entries.binary_search_by_key(key_to_find_in_descent, |e| e.key.clone()) {
                         Ok(idx) => current_leaf_entry_idx = idx,
                         Err(idx) => current_leaf_entry_idx = idx,
                     }
                 } else { // Reverse scan, initially positioned relative to start_bound (upper)
+                    // Separators may route a bound that sorts before every key in this leaf here;
+                    // in that case start "before the beginning" so the scan steps to the previous leaf.
                     match entries.binary_search_by_key(key_to_find_in_descent, |e| e.key.clone()) {
                         Ok(idx) => current_leaf_entry_idx = idx,
-                        Err(idx) => current_leaf_entry_idx = idx.saturating_sub(1),
+                        Err(0) => current_leaf_entry_idx = usize::MAX,
+                        Err(idx) => current_leaf_entry_idx = idx - 1,
                     }
                     if entries.is_empty() { current_leaf_entry_idx = usize::MAX; }
-                    else if current_leaf_entry_idx >= entries.len() { current_leaf_entry_idx = entries.len().saturating_sub(1); }
+                    else if current_leaf_entry_idx != usize::MAX && current_leaf_entry_idx >= entries.len() { current_leaf_entry_idx = entries.len().saturating_sub(1); }
                 }
             }
         } else { // No primary bound, descend to first/last leaf
-            while let Node::Internal { children, .. } = &current_node_obj {
+            while let Node::Internal { children, .. } = current_node_obj.as_ref() {
                  if children.is_empty() { break; }
                  let child_idx_to_descend = if !args.reverse { 0 } else { children.len() - 1 };
                  current_hash = children[child_idx_to_descend].child_hash;
-                 current_node_obj = tree.load_node(&current_hash).await?;
+                 current_node_obj = Self::load_child_with_read_ahead(&store, &node_cache, children, child_idx_to_descend, args.reverse, config.read_ahead).await?;
                  path.push((current_hash, current_node_obj.clone(), child_idx_to_descend));
             }
             if args.reverse {
-                if let Node::Leaf{ entries, .. } = &current_node_obj {
+                if let Node::Leaf{ entries, .. } = current_node_obj.as_ref() {
                     current_leaf_entry_idx = entries.len().saturating_sub(1);
                     if entries.is_empty() { current_leaf_entry_idx = usize::MAX; }
                 } else { current_leaf_entry_idx = usize::MAX; }
@@ -491,12 +537,12 @@ impl<S: ChunkStore> Cursor<S> {
             // Clone to satisfy borrow checker if advance_..._leaf_static needs mutable self.path inside match
             let current_leaf_node_obj_clone = current_leaf_node_obj_ref.clone(); 
 
-            if let Node::Leaf { entries, .. } = current_leaf_node_obj_clone {
+            if let Node::Leaf { entries, .. } = current_leaf_node_obj_clone.as_ref() {
                 if !args.reverse { // Forward
                     let entries_in_current_leaf = entries.len();
                     // If current_leaf_entry_idx is already at/past the end, we must advance leaf first
                     if current_leaf_entry_idx >= entries_in_current_leaf {
-                        if !Self::advance_cursor_path_to_next_leaf_static(&mut path, &store).await? {
+                        if !Self::advance_cursor_path_to_next_leaf_static(&mut path, &store, &node_cache, config.read_ahead).await? {
                             remaining_offset = 0; // No more leaves, consumed all possible offset
                             current_leaf_entry_idx = entries_in_current_leaf; // Stay at end
                             break;
@@ -514,7 +560,7 @@ impl<S: ChunkStore> Cursor<S> {
                         remaining_offset -= remaining_in_leaf as u64;
                         // Move to end of current leaf, then advance
                         current_leaf_entry_idx = entries_in_current_leaf; 
-                        if !Self::advance_cursor_path_to_next_leaf_static(&mut path, &store).await? {
+                        if !Self::advance_cursor_path_to_next_leaf_static(&mut path, &store, &node_cache, config.read_ahead).await? {
                             remaining_offset = 0; // No more leaves
                             break;
                         }
@@ -523,13 +569,13 @@ impl<S: ChunkStore> Cursor<S> {
                 } else { // Reverse
                     // If current_leaf_entry_idx is usize::MAX (before start), advance leaf first
                     if current_leaf_entry_idx == usize::MAX {
-                        if !Self::advance_cursor_path_to_prev_leaf_static(&mut path, &store).await? {
+                        if !Self::advance_cursor_path_to_prev_leaf_static(&mut path, &store, &node_cache, config.read_ahead).await? {
                             remaining_offset = 0; // No more leaves
                             break;
                         }
                         // Set to last entry of new leaf
                         if let Some((_, new_leaf_ref, _)) = path.last() {
-                           if let Node::Leaf{entries: new_entries, ..} = new_leaf_ref { // Borrow new_leaf_ref
+                           if let Node::Leaf{entries: new_entries, ..} = new_leaf_ref.as_ref() { // Borrow new_leaf_ref
                                current_leaf_entry_idx = new_entries.len().saturating_sub(1);
                                if new_entries.is_empty() { current_leaf_entry_idx = usize::MAX; }
                            } else { break; } // Should be a leaf
@@ -546,13 +592,13 @@ impl<S: ChunkStore> Cursor<S> {
                         remaining_offset -= available_to_move_back_in_leaf as u64;
                         // Move to before start of current leaf, then advance
                         current_leaf_entry_idx = usize::MAX; 
-                        if !Self::advance_cursor_path_to_prev_leaf_static(&mut path, &store).await? {
+                        if !Self::advance_cursor_path_to_prev_leaf_static(&mut path, &store, &node_cache, config.read_ahead).await? {
                             remaining_offset = 0; // No more leaves
                             break;
                         }
                         // Set to last entry of new leaf
                         if let Some((_, new_leaf_ref, _)) = path.last() {
-                           if let Node::Leaf{entries: new_entries, ..} = new_leaf_ref { // Borrow new_leaf_ref
+                           if let Node::Leaf{entries: new_entries, ..} = new_leaf_ref.as_ref() { // Borrow new_leaf_ref
                                current_leaf_entry_idx = new_entries.len().saturating_sub(1);
                                if new_entries.is_empty() { current_leaf_entry_idx = usize::MAX; }
                            } else { break; }
@@ -568,7 +614,7 @@ impl<S: ChunkStore> Cursor<S> {
         if remaining_offset > 0 && !path.is_empty() {
             if !args.reverse {
                 if let Some((_, leaf_node_ref, _)) = path.last() {
-                    if let Node::Leaf{entries, ..} = leaf_node_ref {
+                    if let Node::Leaf{entries, ..} = leaf_node_ref.as_ref() {
                         current_leaf_entry_idx = entries.len(); // Past the end
                     }
                 }
@@ -581,7 +627,7 @@ impl<S: ChunkStore> Cursor<S> {
         // --- Phase 3: Final inclusivity adjustment (relative to bounds) ---
         if path.last().is_some() {
             let (_leaf_hash, leaf_node, _parent_idx) = path.last().unwrap();
-            if let Node::Leaf { entries, .. } = leaf_node {
+            if let Node::Leaf { entries, .. } = leaf_node.as_ref() {
                 if !args.reverse {
                     if let Some(sb_val) = &args.start_bound {
                         if current_leaf_entry_idx < entries.len() &&
@@ -605,7 +651,7 @@ impl<S: ChunkStore> Cursor<S> {
             }
         }
 
-        Ok(Self { store, config, path, current_leaf_entry_idx })
+        Ok(Self { store, node_cache, config, path, current_leaf_entry_idx })
     }
 
     pub(crate) fn new_for_scan_sync(
@@ -613,12 +659,13 @@ impl<S: ChunkStore> Cursor<S> {
         args: &ScanArgs,
     ) -> Result<Self> {
         let store = Arc::clone(&tree.store);
+        let node_cache = Arc::clone(&tree.node_cache);
         let config = tree.config.clone();
-        let mut path: Vec<(Hash, Node, usize)> = Vec::new();
+        let mut path: Vec<(Hash, Arc<Node>, usize)> = Vec::new();
         let mut current_leaf_entry_idx: usize = if args.reverse { usize::MAX } else { 0 };
 
         if tree.root_hash.is_none() {
-            return Ok(Self { store, config, path, current_leaf_entry_idx });
+            return Ok(Self { store, node_cache, config, path, current_leaf_entry_idx });
         }
 
         let mut current_hash = tree.root_hash.unwrap();
@@ -632,7 +679,7 @@ impl<S: ChunkStore> Cursor<S> {
         };
 
         if let Some(key_to_find_in_descent) = primary_bound_for_initial_descend {
-            while let Node::Internal { children, .. } = &current_node_obj {
+            while let Node::Internal { children, .. } = current_node_obj.as_ref() {
                 if children.is_empty() { break; }
                 let child_idx_to_descend = children
                     .binary_search_by_key(key_to_find_in_descent, |entry| entry.boundary_key.clone())
@@ -643,19 +690,19 @@ impl<S: ChunkStore> Cursor<S> {
                 current_node_obj = tree.load_node_sync(&current_hash)?;
                 path.push((current_hash, current_node_obj.clone(), child_idx_to_descend));
             }
-            if let Node::Leaf { entries, .. } = &current_node_obj {
+            if let Node::Leaf { entries, .. } = current_node_obj.as_ref() {
                 if !args.reverse {
                     current_leaf_entry_idx = entries.binary_search_by_key(key_to_find_in_descent, |e| e.key.clone())
                         .map_or_else(|idx| idx, |idx| idx);
                 } else {
                     current_leaf_entry_idx = entries.binary_search_by_key(key_to_find_in_descent, |e| e.key.clone())
-                        .map_or_else(|idx| idx.saturating_sub(1), |idx| idx);
+                        .map_or_else(|idx| idx.checked_sub(1).unwrap_or(usize::MAX), |idx| idx);
                     if entries.is_empty() { current_leaf_entry_idx = usize::MAX; }
-                    else if current_leaf_entry_idx >= entries.len() { current_leaf_entry_idx = entries.len().saturating_sub(1); }
+                    else if current_leaf_entry_idx != usize::MAX && current_leaf_entry_idx >= entries.len() { current_leaf_entry_idx = entries.len().saturating_sub(1); }
                 }
             }
         } else {
-            while let Node::Internal { children, .. } = &current_node_obj {
+            while let Node::Internal { children, .. } = current_node_obj.as_ref() {
                  if children.is_empty() { break; }
                  let child_idx_to_descend = if !args.reverse { 0 } else { children.len() - 1 };
                  current_hash = children[child_idx_to_descend].child_hash;
@@ -663,7 +710,7 @@ impl<S: ChunkStore> Cursor<S> {
                  path.push((current_hash, current_node_obj.clone(), child_idx_to_descend));
             }
             if args.reverse {
-                if let Node::Leaf{ entries, .. } = &current_node_obj {
+                if let Node::Leaf{ entries, .. } = current_node_obj.as_ref() {
                     current_leaf_entry_idx = entries.len().saturating_sub(1);
                     if entries.is_empty() { current_leaf_entry_idx = usize::MAX; }
                 } else { current_leaf_entry_idx = usize::MAX; }
@@ -673,10 +720,10 @@ impl<S: ChunkStore> Cursor<S> {
         let mut remaining_offset = args.offset;
         while remaining_offset > 0 && !path.is_empty() {
              let current_leaf_node_obj_clone = path.last().unwrap().1.clone();
-             if let Node::Leaf { entries, .. } = current_leaf_node_obj_clone {
+             if let Node::Leaf { entries, .. } = current_leaf_node_obj_clone.as_ref() {
                  if !args.reverse {
                      if current_leaf_entry_idx >= entries.len() {
-                         if !Self::advance_cursor_path_to_next_leaf_static_sync(&mut path, &store)? { remaining_offset = 0; break; }
+                         if !Self::advance_cursor_path_to_next_leaf_static_sync(&mut path, &store, &node_cache)? { remaining_offset = 0; break; }
                          current_leaf_entry_idx = 0; continue;
                      }
                      let remaining_in_leaf = entries.len() - current_leaf_entry_idx;
@@ -684,14 +731,14 @@ impl<S: ChunkStore> Cursor<S> {
                          current_leaf_entry_idx += remaining_offset as usize; remaining_offset = 0;
                      } else {
                          remaining_offset -= remaining_in_leaf as u64; current_leaf_entry_idx = entries.len();
-                         if !Self::advance_cursor_path_to_next_leaf_static_sync(&mut path, &store)? { remaining_offset = 0; break; }
+                         if !Self::advance_cursor_path_to_next_leaf_static_sync(&mut path, &store, &node_cache)? { remaining_offset = 0; break; }
                          current_leaf_entry_idx = 0;
                      }
                  } else { // Reverse
                      if current_leaf_entry_idx == usize::MAX {
-                         if !Self::advance_cursor_path_to_prev_leaf_static_sync(&mut path, &store)? { remaining_offset = 0; break; }
+                         if !Self::advance_cursor_path_to_prev_leaf_static_sync(&mut path, &store, &node_cache)? { remaining_offset = 0; break; }
                          if let Some((_, new_leaf, _)) = path.last() {
-                            if let Node::Leaf{entries: new_entries, ..} = new_leaf {
+                            if let Node::Leaf{entries: new_entries, ..} = new_leaf.as_ref() {
                                 current_leaf_entry_idx = new_entries.len().saturating_sub(1);
                                 if new_entries.is_empty() { current_leaf_entry_idx = usize::MAX; }
                             }
@@ -703,9 +750,9 @@ impl<S: ChunkStore> Cursor<S> {
                          current_leaf_entry_idx -= remaining_offset as usize; remaining_offset = 0;
                      } else {
                          remaining_offset -= available_to_move_back as u64; current_leaf_entry_idx = usize::MAX;
-                         if !Self::advance_cursor_path_to_prev_leaf_static_sync(&mut path, &store)? { remaining_offset = 0; break; }
+                         if !Self::advance_cursor_path_to_prev_leaf_static_sync(&mut path, &store, &node_cache)? { remaining_offset = 0; break; }
                          if let Some((_, new_leaf, _)) = path.last() {
-                            if let Node::Leaf{entries: new_entries, ..} = new_leaf {
+                            if let Node::Leaf{entries: new_entries, ..} = new_leaf.as_ref() {
                                 current_leaf_entry_idx = new_entries.len().saturating_sub(1);
                                 if new_entries.is_empty() { current_leaf_entry_idx = usize::MAX; }
                             }
@@ -721,7 +768,7 @@ impl<S: ChunkStore> Cursor<S> {
 
         if path.last().is_some() {
             let leaf_node = &path.last().unwrap().1;
-            if let Node::Leaf { entries, .. } = leaf_node {
+            if let Node::Leaf { entries, .. } = leaf_node.as_ref() {
                 if !args.reverse {
                     if let Some(sb_val) = &args.start_bound {
                         if current_leaf_entry_idx < entries.len() && !args.start_inclusive && &entries[current_leaf_entry_idx].key == sb_val {
@@ -737,7 +784,7 @@ impl<S: ChunkStore> Cursor<S> {
                 }
             }
         }
-        Ok(Self { store, config, path, current_leaf_entry_idx })
+        Ok(Self { store, node_cache, config, path, current_leaf_entry_idx })
     }
 
     pub async fn next_in_scan(&mut self, args: &ScanArgs) -> Result<Option<(Key, Value)>> {
@@ -752,7 +799,7 @@ impl<S: ChunkStore> Cursor<S> {
             // We clone here to avoid borrowing issues if we need to modify self.path later (e.g., in advance_to_next/prev_leaf)
             let (_leaf_hash, current_leaf_node_cloned, _idx_in_parent) = self.path.last().unwrap().clone();
 
-            if let Node::Leaf { ref entries, .. } = current_leaf_node_cloned { // Use 'ref entries'
+            if let Node::Leaf { entries, .. } = current_leaf_node_cloned.as_ref() { // Use 'ref entries'
 
                 let entry_opt: Option<&LeafEntry> = if !args.reverse {
                     if self.current_leaf_entry_idx >= entries.len() {
@@ -825,9 +872,9 @@ impl<S: ChunkStore> Cursor<S> {
                 } else { // entry_opt was None
                     let advanced: bool = if !args.reverse {
                         // Need to pass self.path mutably
-                        Self::advance_cursor_path_to_next_leaf_static(&mut self.path, &self.store).await?
+                        Self::advance_cursor_path_to_next_leaf_static(&mut self.path, &self.store, &self.node_cache, self.config.read_ahead).await?
                     } else {
-                        Self::advance_cursor_path_to_prev_leaf_static(&mut self.path, &self.store).await?
+                        Self::advance_cursor_path_to_prev_leaf_static(&mut self.path, &self.store, &self.node_cache, self.config.read_ahead).await?
                     };
                     
                     if !advanced {
@@ -836,7 +883,7 @@ impl<S: ChunkStore> Cursor<S> {
 
                     // After advancing, set index to start/end of new leaf
                     if let Some((_, new_leaf_node, _)) = self.path.last() {
-                        if let Node::Leaf{entries: new_entries, ..} = new_leaf_node {
+                        if let Node::Leaf{entries: new_entries, ..} = new_leaf_node.as_ref() {
                             self.current_leaf_entry_idx = if !args.reverse { 0 }
                                                         else { new_entries.len().saturating_sub(1) };
                             if args.reverse && new_entries.is_empty() { self.current_leaf_entry_idx = usize::MAX; }
@@ -855,7 +902,7 @@ impl<S: ChunkStore> Cursor<S> {
     }
 
     pub(crate) async fn advance_to_next_leaf(&mut self) -> Result<bool> {
-        let advanced = Self::advance_cursor_path_to_next_leaf_static(&mut self.path, &self.store).await?;
+        let advanced = Self::advance_cursor_path_to_next_leaf_static(&mut self.path, &self.store, &self.node_cache, self.config.read_ahead).await?;
         if advanced {
             self.current_leaf_entry_idx = 0; 
         }
@@ -864,11 +911,11 @@ impl<S: ChunkStore> Cursor<S> {
 
     #[allow(dead_code)] // If not used elsewhere yet
     pub(crate) async fn advance_to_prev_leaf(&mut self) -> Result<bool> {
-        let advanced = Self::advance_cursor_path_to_prev_leaf_static(&mut self.path, &self.store).await?;
+        let advanced = Self::advance_cursor_path_to_prev_leaf_static(&mut self.path, &self.store, &self.node_cache, self.config.read_ahead).await?;
         if advanced {
             // When moving to a previous leaf, set index to its last entry
             if let Some((_, new_leaf_node, _)) = self.path.last() {
-                if let Node::Leaf { entries, .. } = new_leaf_node {
+                if let Node::Leaf { entries, .. } = new_leaf_node.as_ref() {
                     self.current_leaf_entry_idx = entries.len().saturating_sub(1);
                     if entries.is_empty() { self.current_leaf_entry_idx = usize::MAX; }
                 } else {
@@ -6,20 +6,22 @@ use std::sync::Arc;
 use std::collections::VecDeque;
 
 use crate::common::{Hash, TreeConfig};
-use crate::error::{Result, ProllyError};
+use crate::error::Result;
 use crate::node::definition::{Node, ValueRepr};
+use crate::node::cache::NodeCache;
 use crate::store::ChunkStore;
 use crate::tree::{ProllyTree, types::{HierarchyScanArgs, HierarchyItem}};
 
 #[derive(Debug)]
 pub struct HierarchyCursor<S: ChunkStore> {
     store: Arc<S>,
+    node_cache: Arc<NodeCache>,
     #[allow(dead_code)]
     config: TreeConfig,
     args: HierarchyScanArgs, // For max_depth, start_key etc.
 
     traversal_queue: VecDeque<(Hash, usize, Vec<usize>)>,
-    current_node_entries_queue: VecDeque<(Hash, Node, usize, usize)>,
+    current_node_entries_queue: VecDeque<(Hash, Arc<Node>, usize, usize)>,
     // REMOVED: page_limit and items_yielded_count
 }
 
@@ -35,6 +37,7 @@ impl<S: ChunkStore> HierarchyCursor<S> {
 
         Ok(Self {
             store: Arc::clone(&tree.store),
+            node_cache: Arc::clone(&tree.node_cache),
             config: tree.config.clone(),
             args: args.clone(), // args now primarily for max_depth, start_key
             traversal_queue,
@@ -42,10 +45,8 @@ impl<S: ChunkStore> HierarchyCursor<S> {
         })
     }
 
-    async fn load_node(&self, hash: &Hash) -> Result<Node> {
-        let bytes = self.store.get(hash).await?
-            .ok_or_else(|| ProllyError::ChunkNotFound(*hash))?;
-        Node::decode(&bytes)
+    async fn load_node(&self, hash: &Hash) -> Result<Arc<Node>> {
+        self.node_cache.load(self.store.as_ref(), hash).await
     }
 
     pub fn next_item<'s>(&'s mut self) -> Pin<Box<dyn Future<Output = Result<Option<HierarchyItem>>> + Send + 's>>
@@ -57,7 +58,7 @@ impl<S: ChunkStore> HierarchyCursor<S> {
 
             if let Some(&mut (ref mut parent_hash_val, ref node_ref, ref mut entry_idx_ref, ref mut _entry_type_ref)) = self.current_node_entries_queue.front_mut() {
                 let current_parent_hash = *parent_hash_val;
-                let item_to_yield = match *node_ref {
+                let item_to_yield = match **node_ref {
                     Node::Internal { ref children, .. } => {
                         if *entry_idx_ref < children.len() {
                             let internal_entry = &children[*entry_idx_ref];
@@ -107,7 +108,7 @@ impl<S: ChunkStore> HierarchyCursor<S> {
                     return self.next_item().await;
                 }
                 let loaded_node_for_processing = self.load_node(&node_hash).await?;
-                let (is_leaf, num_entries, entry_type_code_val) = match &loaded_node_for_processing {
+                let (is_leaf, num_entries, entry_type_code_val) = match loaded_node_for_processing.as_ref() {
                     Node::Leaf { entries, .. } => (true, entries.len(), 1),
                     Node::Internal { children, .. } => (false, children.len(), 0),
                 };
@@ -118,9 +119,9 @@ impl<S: ChunkStore> HierarchyCursor<S> {
                     path_indices: path_indices.clone(),
                 };
                 if num_entries > 0 {
-                    self.current_node_entries_queue.push_back((node_hash, loaded_node_for_processing.clone(), 0, entry_type_code_val));
+                    self.current_node_entries_queue.push_back((node_hash, Arc::clone(&loaded_node_for_processing), 0, entry_type_code_val));
                 }
-                if let Node::Internal { children, .. } = &loaded_node_for_processing {
+                if let Node::Internal { children, .. } = loaded_node_for_processing.as_ref() {
                     if self.args.max_depth.is_none() || depth < self.args.max_depth.unwrap() {
                         for (i, child_entry) in children.iter().enumerate().rev() {
                             let mut child_path_indices = path_indices.clone();
@@ -5,40 +5,67 @@ use fastcdc::v2020::FastCDC;
 use crate::common::{Hash, Key, Value, TreeConfig};
 use crate::error::{Result, ProllyError};
 use crate::node::definition::{Node, ValueRepr};
+use crate::node::cache::NodeCache;
 use crate::store::ChunkStore;
-use crate::chunk::{chunk_node, hash_bytes};
+use crate::store::chunk_store::check_batch_len;
 
 pub(super) async fn store_node_and_get_key_hash_pair<S: ChunkStore>(
     store: &Arc<S>,
+    node_cache: &NodeCache,
     node: &Node,
 ) -> Result<(Key, Hash)> {
-    let (hash, bytes) = chunk_node(node)?;
-    store.put(bytes).await?;
+    // Use the hash reported by the store: wrapping stores (e.g. encryption) may address
+    // chunks by something other than the plaintext hash.
+    let bytes = node.encode()?;
+    let encoded_size = bytes.len();
+    let hash = store.put(bytes).await?;
+    // Freshly written nodes (the new root and path) are the ones read next.
+    node_cache.insert(hash, Arc::new(node.clone()), encoded_size);
 
-    let boundary_key = match node {
+    Ok((boundary_key(node)?, hash))
+}
+
+/// Stores several nodes with a single `put_many` call, e.g. both halves of a split, and
+/// returns their boundary key and hash pairs in the same order.
+pub(super) async fn store_nodes_and_get_key_hash_pairs<S: ChunkStore>(
+    store: &Arc<S>,
+    node_cache: &NodeCache,
+    nodes: &[&Node],
+) -> Result<Vec<(Key, Hash)>> {
+    let encoded = nodes.iter().map(|node| node.encode()).collect::<Result<Vec<_>>>()?;
+    let encoded_sizes: Vec<usize> = encoded.iter().map(Vec::len).collect();
+    let hashes = check_batch_len("put_many", nodes.len(), store.put_many(encoded).await?)?;
+    nodes
+        .iter()
+        .zip(hashes)
+        .zip(encoded_sizes)
+        .map(|((node, hash), encoded_size)| {
+            node_cache.insert(hash, Arc::new((*node).clone()), encoded_size);
+            Ok((boundary_key(node)?, hash))
+        })
+        .collect()
+}
+
+fn boundary_key(node: &Node) -> Result<Key> {
+    match node {
         Node::Leaf { entries, .. } if !entries.is_empty() => Ok(entries.last().unwrap().key.clone()),
         Node::Internal { children, .. } if !children.is_empty() => Ok(children.last().unwrap().boundary_key.clone()),
         _ => Err(ProllyError::InternalError(
             "Attempted to get boundary key from empty or invalid node".to_string(),
         )),
-    };
-    Ok((boundary_key?, hash))
+    }
 }
 
 pub(super) fn store_node_and_get_key_hash_pair_sync<S: ChunkStore>(
     store: &Arc<S>,
+    node_cache: &NodeCache,
     node: &Node,
 ) -> Result<(Key, Hash)> {
-    let (hash, bytes) = chunk_node(node)?;
-    store.put_sync(bytes)?;
-    let boundary_key = match node {
-        Node::Leaf { entries, .. } if !entries.is_empty() => Ok(entries.last().unwrap().key.clone()),
-        Node::Internal { children, .. } if !children.is_empty() => Ok(children.last().unwrap().boundary_key.clone()),
-        _ => Err(ProllyError::InternalError(
-            "Attempted to get boundary key from empty or invalid node".to_string(),
-        )),
-    };
-    Ok((boundary_key?, hash))
+    let bytes = node.encode()?;
+    let encoded_size = bytes.len();
+    let hash = store.put_sync(bytes)?;
+    node_cache.insert(hash, Arc::new(node.clone()), encoded_size);
+    Ok((boundary_key(node)?, hash))
 }
 
 pub(super) async fn prepare_value_repr<S: ChunkStore>(
@@ -57,15 +84,10 @@ pub(super) async fn prepare_value_repr<S: ChunkStore>(
         config.cdc_max_size as u32,
     );
 
-    let mut chunk_hashes = Vec::new();
     let total_size = value.len() as u64;
-
-    for entry in chunker {
-        let chunk_data = &value[entry.offset..entry.offset + entry.length];
-        let chunk_hash = hash_bytes(chunk_data);
-        store.put(chunk_data.to_vec()).await?;
-        chunk_hashes.push(chunk_hash);
-    }
+    let chunks: Vec<Vec<u8>> = chunker.map(|entry| value[entry.offset..entry.offset + entry.length].to_vec()).collect();
+    let chunk_count = chunks.len();
+    let chunk_hashes = check_batch_len("put_many", chunk_count, store.put_many(chunks).await?)?;
 
     match chunk_hashes.len() {
         0 => {
@@ -98,8 +120,7 @@ pub(super) fn prepare_value_repr_sync<S: ChunkStore>(
     let total_size = value.len() as u64;
     for entry in chunker {
         let chunk_data = &value[entry.offset..entry.offset + entry.length];
-        let chunk_hash = hash_bytes(chunk_data);
-        store.put_sync(chunk_data.to_vec())?;
+        let chunk_hash = store.put_sync(chunk_data.to_vec())?;
         chunk_hashes.push(chunk_hash);
     }
     match chunk_hashes.len() {
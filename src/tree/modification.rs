@@ -1,4 +1,5 @@
-use crate::node::definition::{Node, InternalEntry};
+use std::sync::Arc;
+use crate::node::definition::{Node, InternalEntry, shortest_separator};
 use crate::store::ChunkStore;
 use crate::error::{Result, ProllyError};
 
@@ -57,26 +58,36 @@ async fn rebalance_borrow_from_left<S: ChunkStore>(
     left_sibling_idx_in_parent: usize,
     underflow_node_idx_in_parent: usize,
 ) -> Result<()> {
-    let mut left_node_obj = tree.load_node(&parent_children_vec[left_sibling_idx_in_parent].child_hash).await?;
-    let mut underflow_node_obj = tree.load_node(&parent_children_vec[underflow_node_idx_in_parent].child_hash).await?;
+    let mut left_node_obj = Arc::unwrap_or_clone(tree.load_node(&parent_children_vec[left_sibling_idx_in_parent].child_hash).await?);
+    let mut underflow_node_obj = Arc::unwrap_or_clone(tree.load_node(&parent_children_vec[underflow_node_idx_in_parent].child_hash).await?);
 
-    match (&mut left_node_obj, &mut underflow_node_obj) {
+    // The separator between the two siblings in the parent moves along with the borrowed entry.
+    let new_separator = match (&mut left_node_obj, &mut underflow_node_obj) {
         (Node::Leaf { entries: left_entries, .. }, Node::Leaf { entries: underflow_entries, .. }) => {
             if let Some(borrowed_entry) = left_entries.pop() {
+                let separator = left_entries.last()
+                    .map_or_else(|| borrowed_entry.key.clone(), |last| shortest_separator(&last.key, &borrowed_entry.key));
                 underflow_entries.insert(0, borrowed_entry);
+                separator
             } else {
                 return Err(ProllyError::InternalError("Attempted to borrow from empty left leaf sibling".to_string()));
             }
         }
         (Node::Internal { children: left_children_entries, .. }, Node::Internal { children: underflow_children_entries, .. }) => {
-            if let Some(borrowed_child_internal_entry) = left_children_entries.pop() {
+            if let Some(mut borrowed_child_internal_entry) = left_children_entries.pop() {
+                // The borrowed entry was the left sibling's last one and now has a right sibling,
+                // so it needs a real separator; the parent's old separator bounds it from above.
+                borrowed_child_internal_entry.boundary_key = parent_children_vec[left_sibling_idx_in_parent].boundary_key.clone();
+                let separator = left_children_entries.last()
+                    .map_or_else(|| borrowed_child_internal_entry.boundary_key.clone(), |last| last.boundary_key.clone());
                 underflow_children_entries.insert(0, borrowed_child_internal_entry);
+                separator
             } else {
                 return Err(ProllyError::InternalError("Attempted to borrow from empty left internal sibling".to_string()));
             }
         }
         _ => return Err(ProllyError::InternalError("Mismatched node types during rebalance from left".to_string())),
-    }
+    };
 
     let new_left_node_item_count = match &left_node_obj {
         Node::Leaf { entries, .. } => entries.len() as u64,
@@ -87,14 +98,13 @@ async fn rebalance_borrow_from_left<S: ChunkStore>(
         Node::Internal { children: c, .. } => c.iter().map(|entry| entry.num_items_subtree).sum(),
     };
 
-    let (new_left_boundary, new_left_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &left_node_obj).await?;
-    let (new_underflow_boundary, new_underflow_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &underflow_node_obj).await?;
+    let stored = io::store_nodes_and_get_key_hash_pairs(&tree.store, &tree.node_cache, &[&left_node_obj, &underflow_node_obj]).await?;
+    let (new_left_hash, new_underflow_hash) = (stored[0].1, stored[1].1);
 
-    parent_children_vec[left_sibling_idx_in_parent].boundary_key = new_left_boundary;
+    parent_children_vec[left_sibling_idx_in_parent].boundary_key = new_separator;
     parent_children_vec[left_sibling_idx_in_parent].child_hash = new_left_hash;
     parent_children_vec[left_sibling_idx_in_parent].num_items_subtree = new_left_node_item_count;
 
-    parent_children_vec[underflow_node_idx_in_parent].boundary_key = new_underflow_boundary;
     parent_children_vec[underflow_node_idx_in_parent].child_hash = new_underflow_hash;
     parent_children_vec[underflow_node_idx_in_parent].num_items_subtree = new_underflow_node_item_count;
 
@@ -107,26 +117,36 @@ async fn rebalance_borrow_from_right<S: ChunkStore>(
     underflow_node_idx_in_parent: usize,
     right_sibling_idx_in_parent: usize,
 ) -> Result<()> {
-    let mut underflow_node_obj = tree.load_node(&parent_children_vec[underflow_node_idx_in_parent].child_hash).await?;
-    let mut right_node_obj = tree.load_node(&parent_children_vec[right_sibling_idx_in_parent].child_hash).await?;
+    let mut underflow_node_obj = Arc::unwrap_or_clone(tree.load_node(&parent_children_vec[underflow_node_idx_in_parent].child_hash).await?);
+    let mut right_node_obj = Arc::unwrap_or_clone(tree.load_node(&parent_children_vec[right_sibling_idx_in_parent].child_hash).await?);
 
-    match (&mut underflow_node_obj, &mut right_node_obj) {
+    // The separator between the two siblings in the parent moves along with the borrowed entry.
+    let new_separator = match (&mut underflow_node_obj, &mut right_node_obj) {
         (Node::Leaf { entries: underflow_entries, .. }, Node::Leaf { entries: right_entries, .. }) => {
             if right_entries.is_empty() {
                 return Err(ProllyError::InternalError("Attempted to borrow from empty right leaf sibling".to_string()));
             }
             let borrowed_entry = right_entries.remove(0);
+            let separator = right_entries.first()
+                .map_or_else(|| borrowed_entry.key.clone(), |first| shortest_separator(&borrowed_entry.key, &first.key));
             underflow_entries.push(borrowed_entry);
+            separator
         }
         (Node::Internal { children: underflow_children_entries, .. }, Node::Internal { children: right_children_entries, .. }) => {
             if right_children_entries.is_empty() {
                 return Err(ProllyError::InternalError("Attempted to borrow from empty right internal sibling".to_string()));
             }
             let borrowed_child_internal_entry = right_children_entries.remove(0);
+            // The underflow node's last entry gains a right sibling, so give it the parent's old separator.
+            if let Some(last) = underflow_children_entries.last_mut() {
+                last.boundary_key = parent_children_vec[underflow_node_idx_in_parent].boundary_key.clone();
+            }
+            let separator = borrowed_child_internal_entry.boundary_key.clone();
             underflow_children_entries.push(borrowed_child_internal_entry);
+            separator
         }
         _ => return Err(ProllyError::InternalError("Mismatched node types during rebalance from right".to_string())),
-    }
+    };
 
     let new_underflow_node_item_count = match &underflow_node_obj {
         Node::Leaf { entries, .. } => entries.len() as u64,
@@ -137,14 +157,13 @@ async fn rebalance_borrow_from_right<S: ChunkStore>(
         Node::Internal { children: c, .. } => c.iter().map(|entry| entry.num_items_subtree).sum(),
     };
 
-    let (new_underflow_boundary, new_underflow_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &underflow_node_obj).await?;
-    let (new_right_boundary, new_right_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &right_node_obj).await?;
+    let stored = io::store_nodes_and_get_key_hash_pairs(&tree.store, &tree.node_cache, &[&underflow_node_obj, &right_node_obj]).await?;
+    let (new_underflow_hash, new_right_hash) = (stored[0].1, stored[1].1);
 
-    parent_children_vec[underflow_node_idx_in_parent].boundary_key = new_underflow_boundary;
+    parent_children_vec[underflow_node_idx_in_parent].boundary_key = new_separator;
     parent_children_vec[underflow_node_idx_in_parent].child_hash = new_underflow_hash;
     parent_children_vec[underflow_node_idx_in_parent].num_items_subtree = new_underflow_node_item_count;
 
-    parent_children_vec[right_sibling_idx_in_parent].boundary_key = new_right_boundary;
     parent_children_vec[right_sibling_idx_in_parent].child_hash = new_right_hash;
     parent_children_vec[right_sibling_idx_in_parent].num_items_subtree = new_right_node_item_count;
     
@@ -176,22 +195,27 @@ async fn merge_into_left_sibling_internal<S: ChunkStore>(
     let items_from_left_child_before_merge = parent_children_vec[left_idx_in_parent].num_items_subtree;
     let items_from_right_child_before_merge = parent_children_vec[right_idx_in_parent].num_items_subtree;
 
-    let mut left_node_obj = tree.load_node(&parent_children_vec[left_idx_in_parent].child_hash).await?;
-    let right_node_to_merge_obj = tree.load_node(&parent_children_vec[right_idx_in_parent].child_hash).await?;
+    let mut left_node_obj = Arc::unwrap_or_clone(tree.load_node(&parent_children_vec[left_idx_in_parent].child_hash).await?);
+    let right_node_to_merge_obj = Arc::unwrap_or_clone(tree.load_node(&parent_children_vec[right_idx_in_parent].child_hash).await?);
 
     match (&mut left_node_obj, right_node_to_merge_obj) {
         (Node::Leaf { entries: left_entries, .. }, Node::Leaf { entries: mut right_entries_to_append, .. }) => {
             left_entries.append(&mut right_entries_to_append);
         }
         (Node::Internal { children: left_children_entries, .. }, Node::Internal { children: mut right_children_to_append, .. }) => {
+            // The left node's last entry gains a right sibling, so give it the parent's separator.
+            if let Some(last) = left_children_entries.last_mut() {
+                last.boundary_key = parent_children_vec[left_idx_in_parent].boundary_key.clone();
+            }
             left_children_entries.append(&mut right_children_to_append);
         }
         _ => return Err(ProllyError::InternalError("Mismatched node types during merge".to_string())),
     }
 
-    let (new_merged_node_boundary, new_merged_node_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &left_node_obj).await?;
+    let (_new_merged_node_boundary, new_merged_node_hash) = io::store_node_and_get_key_hash_pair(&tree.store, &tree.node_cache, &left_node_obj).await?;
 
-    parent_children_vec[left_idx_in_parent].boundary_key = new_merged_node_boundary;
+    // The merged node covers the right sibling's range, so it inherits that entry's bound.
+    parent_children_vec[left_idx_in_parent].boundary_key = parent_children_vec[right_idx_in_parent].boundary_key.clone();
     parent_children_vec[left_idx_in_parent].child_hash = new_merged_node_hash;
     parent_children_vec[left_idx_in_parent].num_items_subtree = items_from_left_child_before_merge + items_from_right_child_before_merge;
 
@@ -244,25 +268,35 @@ fn rebalance_borrow_from_left_sync<S: ChunkStore>(
     left_sibling_idx_in_parent: usize,
     underflow_node_idx_in_parent: usize,
 ) -> Result<()> {
-    let mut left_node_obj = tree.load_node_sync(&parent_children_vec[left_sibling_idx_in_parent].child_hash)?;
-    let mut underflow_node_obj = tree.load_node_sync(&parent_children_vec[underflow_node_idx_in_parent].child_hash)?;
-    match (&mut left_node_obj, &mut underflow_node_obj) {
+    let mut left_node_obj = Arc::unwrap_or_clone(tree.load_node_sync(&parent_children_vec[left_sibling_idx_in_parent].child_hash)?);
+    let mut underflow_node_obj = Arc::unwrap_or_clone(tree.load_node_sync(&parent_children_vec[underflow_node_idx_in_parent].child_hash)?);
+    // The separator between the two siblings in the parent moves along with the borrowed entry.
+    let new_separator = match (&mut left_node_obj, &mut underflow_node_obj) {
         (Node::Leaf { entries: left_entries, .. }, Node::Leaf { entries: underflow_entries, .. }) => {
             if let Some(borrowed_entry) = left_entries.pop() {
+                let separator = left_entries.last()
+                    .map_or_else(|| borrowed_entry.key.clone(), |last| shortest_separator(&last.key, &borrowed_entry.key));
                 underflow_entries.insert(0, borrowed_entry);
+                separator
             } else {
                 return Err(ProllyError::InternalError("Attempted to borrow from empty left leaf sibling".to_string()));
             }
         }
         (Node::Internal { children: left_children_entries, .. }, Node::Internal { children: underflow_children_entries, .. }) => {
-            if let Some(borrowed_child_internal_entry) = left_children_entries.pop() {
+            if let Some(mut borrowed_child_internal_entry) = left_children_entries.pop() {
+                // The borrowed entry was the left sibling's last one and now has a right sibling,
+                // so it needs a real separator; the parent's old separator bounds it from above.
+                borrowed_child_internal_entry.boundary_key = parent_children_vec[left_sibling_idx_in_parent].boundary_key.clone();
+                let separator = left_children_entries.last()
+                    .map_or_else(|| borrowed_child_internal_entry.boundary_key.clone(), |last| last.boundary_key.clone());
                 underflow_children_entries.insert(0, borrowed_child_internal_entry);
+                separator
             } else {
                 return Err(ProllyError::InternalError("Attempted to borrow from empty left internal sibling".to_string()));
             }
         }
         _ => return Err(ProllyError::InternalError("Mismatched node types during rebalance from left".to_string())),
-    }
+    };
     let new_left_node_item_count = match &left_node_obj {
         Node::Leaf { entries, .. } => entries.len() as u64,
         Node::Internal { children: c, .. } => c.iter().map(|entry| entry.num_items_subtree).sum(),
@@ -271,12 +305,11 @@ fn rebalance_borrow_from_left_sync<S: ChunkStore>(
         Node::Leaf { entries, .. } => entries.len() as u64,
         Node::Internal { children: c, .. } => c.iter().map(|entry| entry.num_items_subtree).sum(),
     };
-    let (new_left_boundary, new_left_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &left_node_obj)?;
-    let (new_underflow_boundary, new_underflow_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &underflow_node_obj)?;
-    parent_children_vec[left_sibling_idx_in_parent].boundary_key = new_left_boundary;
+    let (_new_left_boundary, new_left_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &left_node_obj)?;
+    let (_new_underflow_boundary, new_underflow_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &underflow_node_obj)?;
+    parent_children_vec[left_sibling_idx_in_parent].boundary_key = new_separator;
     parent_children_vec[left_sibling_idx_in_parent].child_hash = new_left_hash;
     parent_children_vec[left_sibling_idx_in_parent].num_items_subtree = new_left_node_item_count;
-    parent_children_vec[underflow_node_idx_in_parent].boundary_key = new_underflow_boundary;
     parent_children_vec[underflow_node_idx_in_parent].child_hash = new_underflow_hash;
     parent_children_vec[underflow_node_idx_in_parent].num_items_subtree = new_underflow_node_item_count;
     Ok(())
@@ -288,25 +321,35 @@ fn rebalance_borrow_from_right_sync<S: ChunkStore>(
     underflow_node_idx_in_parent: usize,
     right_sibling_idx_in_parent: usize,
 ) -> Result<()> {
-    let mut underflow_node_obj = tree.load_node_sync(&parent_children_vec[underflow_node_idx_in_parent].child_hash)?;
-    let mut right_node_obj = tree.load_node_sync(&parent_children_vec[right_sibling_idx_in_parent].child_hash)?;
-    match (&mut underflow_node_obj, &mut right_node_obj) {
+    let mut underflow_node_obj = Arc::unwrap_or_clone(tree.load_node_sync(&parent_children_vec[underflow_node_idx_in_parent].child_hash)?);
+    let mut right_node_obj = Arc::unwrap_or_clone(tree.load_node_sync(&parent_children_vec[right_sibling_idx_in_parent].child_hash)?);
+    // The separator between the two siblings in the parent moves along with the borrowed entry.
+    let new_separator = match (&mut underflow_node_obj, &mut right_node_obj) {
         (Node::Leaf { entries: underflow_entries, .. }, Node::Leaf { entries: right_entries, .. }) => {
             if right_entries.is_empty() {
                 return Err(ProllyError::InternalError("Attempted to borrow from empty right leaf sibling".to_string()));
             }
             let borrowed_entry = right_entries.remove(0);
+            let separator = right_entries.first()
+                .map_or_else(|| borrowed_entry.key.clone(), |first| shortest_separator(&borrowed_entry.key, &first.key));
             underflow_entries.push(borrowed_entry);
+            separator
         }
         (Node::Internal { children: underflow_children_entries, .. }, Node::Internal { children: right_children_entries, .. }) => {
             if right_children_entries.is_empty() {
                 return Err(ProllyError::InternalError("Attempted to borrow from empty right internal sibling".to_string()));
             }
             let borrowed_child_internal_entry = right_children_entries.remove(0);
+            // The underflow node's last entry gains a right sibling, so give it the parent's old separator.
+            if let Some(last) = underflow_children_entries.last_mut() {
+                last.boundary_key = parent_children_vec[underflow_node_idx_in_parent].boundary_key.clone();
+            }
+            let separator = borrowed_child_internal_entry.boundary_key.clone();
             underflow_children_entries.push(borrowed_child_internal_entry);
+            separator
         }
         _ => return Err(ProllyError::InternalError("Mismatched node types during rebalance from right".to_string())),
-    }
+    };
     let new_underflow_node_item_count = match &underflow_node_obj {
         Node::Leaf { entries, .. } => entries.len() as u64,
         Node::Internal { children: c, .. } => c.iter().map(|entry| entry.num_items_subtree).sum(),
@@ -315,12 +358,11 @@ fn rebalance_borrow_from_right_sync<S: ChunkStore>(
         Node::Leaf { entries, .. } => entries.len() as u64,
         Node::Internal { children: c, .. } => c.iter().map(|entry| entry.num_items_subtree).sum(),
     };
-    let (new_underflow_boundary, new_underflow_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &underflow_node_obj)?;
-    let (new_right_boundary, new_right_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &right_node_obj)?;
-    parent_children_vec[underflow_node_idx_in_parent].boundary_key = new_underflow_boundary;
+    let (_new_underflow_boundary, new_underflow_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &underflow_node_obj)?;
+    let (_new_right_boundary, new_right_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &right_node_obj)?;
+    parent_children_vec[underflow_node_idx_in_parent].boundary_key = new_separator;
     parent_children_vec[underflow_node_idx_in_parent].child_hash = new_underflow_hash;
     parent_children_vec[underflow_node_idx_in_parent].num_items_subtree = new_underflow_node_item_count;
-    parent_children_vec[right_sibling_idx_in_parent].boundary_key = new_right_boundary;
     parent_children_vec[right_sibling_idx_in_parent].child_hash = new_right_hash;
     parent_children_vec[right_sibling_idx_in_parent].num_items_subtree = new_right_node_item_count;
     Ok(())
@@ -346,19 +388,23 @@ fn merge_into_left_sibling_internal_sync<S: ChunkStore>(
     }
     let items_from_left_child_before_merge = parent_children_vec[left_idx_in_parent].num_items_subtree;
     let items_from_right_child_before_merge = parent_children_vec[right_idx_in_parent].num_items_subtree;
-    let mut left_node_obj = tree.load_node_sync(&parent_children_vec[left_idx_in_parent].child_hash)?;
-    let right_node_to_merge_obj = tree.load_node_sync(&parent_children_vec[right_idx_in_parent].child_hash)?;
+    let mut left_node_obj = Arc::unwrap_or_clone(tree.load_node_sync(&parent_children_vec[left_idx_in_parent].child_hash)?);
+    let right_node_to_merge_obj = Arc::unwrap_or_clone(tree.load_node_sync(&parent_children_vec[right_idx_in_parent].child_hash)?);
     match (&mut left_node_obj, right_node_to_merge_obj) {
         (Node::Leaf { entries: left_entries, .. }, Node::Leaf { entries: mut right_entries_to_append, .. }) => {
             left_entries.append(&mut right_entries_to_append);
         }
         (Node::Internal { children: left_children_entries, .. }, Node::Internal { children: mut right_children_to_append, .. }) => {
+            // The left node's last entry gains a right sibling, so give it the parent's separator.
+            if let Some(last) = left_children_entries.last_mut() {
+                last.boundary_key = parent_children_vec[left_idx_in_parent].boundary_key.clone();
+            }
             left_children_entries.append(&mut right_children_to_append);
         }
         _ => return Err(ProllyError::InternalError("Mismatched node types during merge".to_string())),
     }
-    let (new_merged_node_boundary, new_merged_node_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &left_node_obj)?;
-    parent_children_vec[left_idx_in_parent].boundary_key = new_merged_node_boundary;
+    let (_new_merged_node_boundary, new_merged_node_hash) = io::store_node_and_get_key_hash_pair_sync(&tree.store, &tree.node_cache, &left_node_obj)?;
+    parent_children_vec[left_idx_in_parent].boundary_key = parent_children_vec[right_idx_in_parent].boundary_key.clone();
     parent_children_vec[left_idx_in_parent].child_hash = new_merged_node_hash;
     parent_children_vec[left_idx_in_parent].num_items_subtree = items_from_left_child_before_merge + items_from_right_child_before_merge;
     parent_children_vec.remove(right_idx_in_parent);
@@ -1,13 +1,19 @@
 use std::sync::Arc;
 use std::pin::Pin;
 use std::future::Future;
+use futures::{Stream, TryStreamExt};
 
 use crate::common::{Hash, Key, Value, TreeConfig};
 use crate::error::{Result, ProllyError};
 use crate::node::definition::{Node, LeafEntry, InternalEntry, ValueRepr};
+use crate::node::cache::{NodeCache, NodeCacheStats};
 use crate::store::ChunkStore;
-use crate::diff::{diff_trees, DiffEntry};
-use crate::gc::GarbageCollector;
+use crate::store::chunk_store::check_batch_len;
+use crate::store::patch_format::{Patch, PatchValue};
+use crate::store::file_io_v2::DeltaBundleV2;
+use crate::diff::{diff_trees_with_options, first_difference_in_range, changed_ranges_with_cache, diff_stream_with_options, diff_stream_across_stores, diff_summary_with_options, diff_to_patch, entries_digest, ApplyMode, ChangedRange, DiffConflict, DiffEntry, DiffKind, DiffOptions, DiffSummary, DiffSummaryOptions};
+use crate::gc::{GarbageCollector, GcPhase, GcPlan, GcProgress, IncrementalGc, RootPins, compact_into, delta_chunks, reachable_chunks};
+use crate::sync::{sync_to_store, SyncOptions, SyncStats};
 
 use super::cursor::Cursor;
 use super::types::{ScanArgs, ScanPage, ProcessedNodeUpdate, DeleteRecursionResult}; 
@@ -21,6 +27,16 @@ pub struct ProllyTree<S: ChunkStore> {
     pub root_hash: Option<Hash>,
     pub store: Arc<S>,
     pub config: TreeConfig,
+    /// Decoded-node cache shared with the cursors and diffs this tree creates.
+    pub(crate) node_cache: Arc<NodeCache>,
+    /// Roots kept alive by garbage collection in addition to the current root.
+    pub(crate) root_pins: RootPins,
+    /// The incremental collection in progress, if any.
+    pub(crate) incremental_gc: Option<IncrementalGc<S>>,
+    /// The store's id for the write in progress, see `ChunkStore::begin_write`.
+    write_id: Option<u64>,
+    /// Nesting depth of the write in progress; nested writes share its registration.
+    write_depth: usize,
 }
 
 impl<S: ChunkStore> ProllyTree<S> {
@@ -32,19 +48,55 @@ impl<S: ChunkStore> ProllyTree<S> {
             root_hash: None,
             store,
             config,
+            node_cache: Arc::new(NodeCache::default()),
+            root_pins: RootPins::default(),
+            incremental_gc: None,
+            write_id: None,
+            write_depth: 0,
         }
     }
 
-    pub(crate) async fn load_node(&self, hash: &Hash) -> Result<Node> {
-        let bytes = self.store.get(hash).await?
-            .ok_or_else(|| ProllyError::ChunkNotFound(*hash))?;
-        Node::decode(&bytes)
+    /// Replaces the tree's decoded-node cache, e.g. to change its limits or to share one
+    /// cache between several trees over the same store.
+    pub fn set_node_cache(&mut self, node_cache: Arc<NodeCache>) {
+        self.node_cache = node_cache;
     }
 
-    pub(crate) fn load_node_sync(&self, hash: &Hash) -> Result<Node> {
-        let bytes = self.store.get_sync(hash)?
-            .ok_or_else(|| ProllyError::ChunkNotFound(*hash))?;
-        Node::decode(&bytes)
+    pub fn node_cache(&self) -> &Arc<NodeCache> {
+        &self.node_cache
+    }
+
+    pub fn node_cache_stats(&self) -> NodeCacheStats {
+        self.node_cache.stats()
+    }
+
+    /// Replaces the tree's root pins, e.g. to share them between several trees writing to
+    /// the same store so that a collection run by any of them keeps every pinned root.
+    pub fn set_root_pins(&mut self, root_pins: RootPins) {
+        self.root_pins = root_pins;
+    }
+
+    pub fn root_pins(&self) -> &RootPins {
+        &self.root_pins
+    }
+
+    /// Keeps `root` and everything reachable from it alive through garbage collection until
+    /// a matching `unpin_root`.
+    pub fn pin_root(&self, root: Hash) {
+        self.root_pins.pin(root);
+    }
+
+    /// Releases one pin taken by `pin_root`.
+    pub fn unpin_root(&self, root: &Hash) -> Result<()> {
+        self.root_pins.unpin(root)
+    }
+
+    pub(crate) async fn load_node(&self, hash: &Hash) -> Result<Arc<Node>> {
+        self.node_cache.load(self.store.as_ref(), hash).await
+    }
+
+    pub(crate) fn load_node_sync(&self, hash: &Hash) -> Result<Arc<Node>> {
+        self.node_cache.load_sync(self.store.as_ref(), hash)
     }
 
     pub(crate) fn load_value_repr_sync(&self, value_repr: &ValueRepr) -> Result<Option<Value>> {
@@ -87,12 +139,43 @@ impl<S: ChunkStore> ProllyTree<S> {
                     root_hash: Some(root_hash),
                     store,
                     config,
+                    node_cache: Arc::new(NodeCache::default()),
+                    root_pins: RootPins::default(),
+                    incremental_gc: None,
+                    write_id: None,
+                    write_depth: 0,
                 })
             }
             None => Err(ProllyError::ChunkNotFound(root_hash)),
         }
     }
 
+    /// Registers the write about to start with the store, so that a garbage collection
+    /// started meanwhile from another tree over the same store spares the chunks it puts
+    /// before publishing its root. Writes nested in another share its registration.
+    fn begin_write(&mut self) -> Result<()> {
+        if self.write_depth == 0 {
+            self.write_id = self.store.begin_write()?;
+        }
+        self.write_depth += 1;
+        Ok(())
+    }
+
+    /// Ends the write started by the matching `begin_write`, passing its result through.
+    fn end_write<T>(&mut self, result: Result<T>) -> Result<T> {
+        self.write_depth -= 1;
+        let ended = match self.write_id {
+            Some(write_id) if self.write_depth == 0 => {
+                self.write_id = None;
+                self.store.end_write(write_id)
+            }
+            _ => Ok(()),
+        };
+        let value = result?;
+        ended?;
+        Ok(value)
+    }
+
     pub fn get_root_hash(&self) -> Option<Hash> {
         self.root_hash
     }
@@ -126,6 +209,12 @@ impl<S: ChunkStore> ProllyTree<S> {
 
     // (Add these new methods to the ProllyTree impl block in the existing file)
     pub fn insert_sync(&mut self, key: Key, value: Value) -> Result<bool> {
+        self.begin_write()?;
+        let result = self.insert_sync_in_write(key, value);
+        self.end_write(result)
+    }
+
+    fn insert_sync_in_write(&mut self, key: Key, value: Value) -> Result<bool> {
         let old_root_hash = self.root_hash;
         let value_repr = io::prepare_value_repr_sync(&self.store, &self.config, value)?;
         let current_root_hash = match self.root_hash {
@@ -135,7 +224,7 @@ impl<S: ChunkStore> ProllyTree<S> {
                     level: 0,
                     entries: vec![LeafEntry { key, value: value_repr }],
                 };
-                let (_boundary_key, new_root_hash_val) = io::store_node_and_get_key_hash_pair_sync(&self.store, &new_leaf_node)?;
+                let (_boundary_key, new_root_hash_val) = io::store_node_and_get_key_hash_pair_sync(&self.store, &self.node_cache, &new_leaf_node)?;
                 self.root_hash = Some(new_root_hash_val);
                 return Ok(true);
             }
@@ -160,13 +249,19 @@ impl<S: ChunkStore> ProllyTree<S> {
             ];
             let new_root_level = root_node.level() + 1;
             let new_root_node_obj = Node::new_internal(new_root_children, new_root_level)?;
-            let (_final_boundary, final_root_hash) = io::store_node_and_get_key_hash_pair_sync(&self.store, &new_root_node_obj)?;
+            let (_final_boundary, final_root_hash) = io::store_node_and_get_key_hash_pair_sync(&self.store, &self.node_cache, &new_root_node_obj)?;
             self.root_hash = Some(final_root_hash);
         }
         Ok(old_root_hash != self.root_hash)
     }
 
     pub async fn insert(&mut self, key: Key, value: Value) -> Result<bool> {
+        self.begin_write()?;
+        let result = self.insert_in_write(key, value).await;
+        self.end_write(result)
+    }
+
+    async fn insert_in_write(&mut self, key: Key, value: Value) -> Result<bool> {
         let old_root_hash = self.root_hash;
         let value_repr = io::prepare_value_repr(&self.store, &self.config, value).await?;
 
@@ -179,7 +274,7 @@ impl<S: ChunkStore> ProllyTree<S> {
                     entries: vec![LeafEntry { key, value: value_repr }],
                 };
                 // Use io module to store it
-                let (_boundary_key, new_root_hash_val) = io::store_node_and_get_key_hash_pair(&self.store, &new_leaf_node).await?;
+                let (_boundary_key, new_root_hash_val) = io::store_node_and_get_key_hash_pair(&self.store, &self.node_cache, &new_leaf_node).await?;
                 self.root_hash = Some(new_root_hash_val);
                 return Ok(true);
             }
@@ -210,7 +305,7 @@ impl<S: ChunkStore> ProllyTree<S> {
 
             let new_root_level = root_node.level() + 1;
             let new_root_node_obj = Node::new_internal(new_root_children, new_root_level)?;
-            let (_final_boundary, final_root_hash) = io::store_node_and_get_key_hash_pair(&self.store, &new_root_node_obj).await?;
+            let (_final_boundary, final_root_hash) = io::store_node_and_get_key_hash_pair(&self.store, &self.node_cache, &new_root_node_obj).await?;
             self.root_hash = Some(final_root_hash);
         }
         Ok(old_root_hash != self.root_hash)
@@ -230,6 +325,12 @@ impl<S: ChunkStore> ProllyTree<S> {
 
 
     pub async fn insert_batch(&mut self, items: Vec<(Key, Value)>) -> Result<bool> {
+        self.begin_write()?;
+        let result = self.insert_batch_in_write(items).await;
+        self.end_write(result)
+    }
+
+    async fn insert_batch_in_write(&mut self, items: Vec<(Key, Value)>) -> Result<bool> {
         let old_root_hash = self.root_hash;
         for (key, value) in items {
             self.insert(key, value).await?;
@@ -238,6 +339,12 @@ impl<S: ChunkStore> ProllyTree<S> {
     }
 
     pub fn delete_sync(&mut self, key: &Key) -> Result<bool> {
+        self.begin_write()?;
+        let result = self.delete_sync_in_write(key);
+        self.end_write(result)
+    }
+
+    fn delete_sync_in_write(&mut self, key: &Key) -> Result<bool> {
         let current_root_hash = match self.root_hash {
             Some(h) => h,
             None => return Ok(false),
@@ -251,7 +358,7 @@ impl<S: ChunkStore> ProllyTree<S> {
             DeleteRecursionResult::Updated(update_info) => {
                 self.root_hash = Some(update_info.new_hash);
                 let potentially_new_root_node = self.load_node_sync(&self.root_hash.unwrap())?;
-                if let Node::Internal { ref children, .. } = potentially_new_root_node {
+                if let Node::Internal { children, .. } = potentially_new_root_node.as_ref() {
                     if children.len() == 1 {
                         self.root_hash = Some(children[0].child_hash);
                     }
@@ -266,6 +373,12 @@ impl<S: ChunkStore> ProllyTree<S> {
     }
 
     pub async fn delete(&mut self, key: &Key) -> Result<bool> {
+        self.begin_write()?;
+        let result = self.delete_in_write(key).await;
+        self.end_write(result)
+    }
+
+    async fn delete_in_write(&mut self, key: &Key) -> Result<bool> {
         let current_root_hash = match self.root_hash {
             Some(h) => h,
             None => return Ok(false),
@@ -284,7 +397,7 @@ impl<S: ChunkStore> ProllyTree<S> {
                 self.root_hash = Some(update_info.new_hash);
                 // Check if root became an internal node with a single child, then collapse
                 let potentially_new_root_node = self.load_node(&self.root_hash.unwrap()).await?;
-                if let Node::Internal { ref children, .. } = potentially_new_root_node {
+                if let Node::Internal { children, .. } = potentially_new_root_node.as_ref() {
                     if children.len() == 1 {
                         self.root_hash = Some(children[0].child_hash);
                     }
@@ -330,7 +443,7 @@ impl<S: ChunkStore> ProllyTree<S> {
         let root_node_hash = self.root_hash.unwrap();
         let root_node = self.load_node(&root_node_hash).await?;
 
-        match root_node {
+        match root_node.as_ref() {
             Node::Leaf { entries, .. } => Ok(entries.len() as u64),
             Node::Internal { children, .. } => {
                 Ok(children.iter().map(|c| c.num_items_subtree).sum())
@@ -347,24 +460,450 @@ impl<S: ChunkStore> ProllyTree<S> {
         Cursor::new_at_key(self, key).await
     }
 
-    pub async fn diff(&self, other_root_hash: Option<Hash>) -> Result<Vec<DiffEntry>> {
-        diff_trees(
+    /// Computes the differences between this tree and `other_root_hash`. Set
+    /// `options.start` / `options.end` to restrict the diff to a key range.
+    pub async fn diff(&self, other_root_hash: Option<Hash>, options: DiffOptions) -> Result<Vec<DiffEntry>> {
+        diff_trees_with_options(
             self.root_hash,
             other_root_hash,
             Arc::clone(&self.store),
             self.config.clone(),
+            Arc::clone(&self.node_cache),
+            options,
+        )
+        .await
+    }
+
+    /// Streams the differences between this tree and `other_root_hash` in key order,
+    /// loading nodes and values only as the stream is consumed.
+    pub fn diff_stream(&self, other_root_hash: Option<Hash>, options: DiffOptions) -> impl Stream<Item = Result<DiffEntry>> + Send {
+        diff_stream_with_options(
+            self.root_hash,
+            other_root_hash,
+            Arc::clone(&self.store),
+            self.config.clone(),
+            Arc::clone(&self.node_cache),
+            options,
+        )
+    }
+
+    /// Returns the smallest key whose value differs between this tree and `other_root_hash`
+    /// within `[start, end)`, or `None` if that range is equal. Much cheaper than `diff`:
+    /// only nodes along differing hashes are loaded, and the walk stops at the first change.
+    pub async fn first_difference(&self, other_root_hash: Option<Hash>, start: Option<Key>, end: Option<Key>) -> Result<Option<Key>> {
+        first_difference_in_range(
+            self.root_hash,
+            other_root_hash,
+            Arc::clone(&self.store),
+            self.config.clone(),
+            Arc::clone(&self.node_cache),
+            start,
+            end,
+        )
+        .await
+    }
+
+    /// Whether this tree and `other_root_hash` hold the same entries for every key in `[start, end)`.
+    pub async fn range_equal(&self, other_root_hash: Option<Hash>, start: Option<Key>, end: Option<Key>) -> Result<bool> {
+        self.first_difference(other_root_hash, start, end).await.map(|key| key.is_none())
+    }
+
+    /// Returns the key ranges in which this tree and `other_root_hash` differ, with the subtree
+    /// hashes and item counts covering them on each side, without loading any leaf node.
+    pub async fn changed_ranges(&self, other_root_hash: Option<Hash>) -> Result<Vec<ChangedRange>> {
+        changed_ranges_with_cache(
+            self.root_hash,
+            other_root_hash,
+            Arc::clone(&self.store),
+            self.config.clone(),
+            Arc::clone(&self.node_cache),
+        )
+        .await
+    }
+
+    /// Computes the differences between this tree and a tree held in another store, such as
+    /// one loaded from a file or another replica. Nothing is copied between the stores.
+    pub async fn diff_with_store<R: ChunkStore + ?Sized>(
+        &self,
+        other_root_hash: Option<Hash>,
+        other_store: Arc<R>,
+        options: DiffOptions,
+    ) -> Result<Vec<DiffEntry>> {
+        diff_stream_across_stores(
+            self.root_hash,
+            Arc::clone(&self.store),
+            other_root_hash,
+            other_store,
+            self.config.clone(),
+            // A separate cache keeps nodes that only exist in the other store out of ours.
+            Arc::new(NodeCache::default()),
+            options,
+        )
+        .try_collect()
+        .await
+    }
+
+    /// Counts the keys added, removed and modified between this tree and `other_root_hash`
+    /// without materializing the change set.
+    pub async fn diff_summary(&self, other_root_hash: Option<Hash>, options: DiffSummaryOptions) -> Result<DiffSummary> {
+        diff_summary_with_options(
+            self.root_hash,
+            other_root_hash,
+            Arc::clone(&self.store),
+            self.config.clone(),
+            Arc::clone(&self.node_cache),
+            options,
+        )
+        .await
+    }
+
+    /// Applies `entries` (typically computed by `diff` on another replica) to this tree:
+    /// additions and modifications become inserts and removals become deletes. Entries must
+    /// carry their values (`DiffMode::Values`), so the chunks of the tree the diff was
+    /// computed from are not needed. Returns whether the root hash changed.
+    ///
+    /// In `ApplyMode::Strict`, all entries are checked before anything is written, and the
+    /// call fails with `ProllyError::DiffConflict` listing every key whose current value is
+    /// not the entry's `left_value`; a key appearing in several entries is rejected too, since
+    /// only the first could be checked against the tree. If a write fails part way, the tree
+    /// is reset to its root before the call.
+    pub async fn apply_diff(&mut self, entries: &[DiffEntry], mode: ApplyMode) -> Result<bool> {
+        self.begin_write()?;
+        let result = self.apply_diff_in_write(entries, mode).await;
+        self.end_write(result)
+    }
+
+    async fn apply_diff_in_write(&mut self, entries: &[DiffEntry], mode: ApplyMode) -> Result<bool> {
+        for entry in entries {
+            let has_values = match entry.kind {
+                DiffKind::Added => entry.right_value.is_some(),
+                DiffKind::Removed => entry.left_value.is_some() || mode == ApplyMode::Force,
+                DiffKind::Modified => entry.right_value.is_some() && (entry.left_value.is_some() || mode == ApplyMode::Force),
+            };
+            if !has_values {
+                return Err(ProllyError::InvalidOperation(format!(
+                    "Diff entry for key {:?} is missing its values; apply_diff needs entries computed with DiffMode::Values",
+                    entry.key
+                )));
+            }
+        }
+
+        if mode == ApplyMode::Strict {
+            let mut keys = std::collections::HashSet::new();
+            if let Some(entry) = entries.iter().find(|entry| !keys.insert(&entry.key)) {
+                return Err(ProllyError::InvalidOperation(format!(
+                    "Diff has several entries for key {:?}; ApplyMode::Strict needs one entry per key", entry.key
+                )));
+            }
+            let mut conflicts = Vec::new();
+            for entry in entries {
+                let expected = if entry.kind == DiffKind::Added { None } else { entry.left_value.clone() };
+                let actual = self.get(&entry.key).await?;
+                if actual != expected {
+                    conflicts.push(DiffConflict { key: entry.key.clone(), expected, actual });
+                }
+            }
+            if !conflicts.is_empty() {
+                return Err(ProllyError::DiffConflict { conflicts });
+            }
+        }
+
+        let old_root_hash = self.root_hash;
+        if let Err(e) = self.apply_diff_entries(entries).await {
+            self.root_hash = old_root_hash;
+            return Err(e);
+        }
+        Ok(old_root_hash != self.root_hash)
+    }
+
+    async fn apply_diff_entries(&mut self, entries: &[DiffEntry]) -> Result<()> {
+        for entry in entries {
+            match (&entry.kind, &entry.right_value) {
+                (DiffKind::Added | DiffKind::Modified, Some(value)) => { self.insert(entry.key.clone(), value.clone()).await?; }
+                _ => { self.delete(&entry.key).await?; }
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds a patch from `base_root_hash` to `target_root_hash`, both of which must be
+    /// readable from this tree's store. See `diff_to_patch` for `embed_values`.
+    pub async fn export_patch(&self, base_root_hash: Option<Hash>, target_root_hash: Option<Hash>, embed_values: bool) -> Result<Patch> {
+        diff_to_patch(
+            base_root_hash,
+            target_root_hash,
+            Arc::clone(&self.store),
+            self.config.clone(),
+            Arc::clone(&self.node_cache),
+            embed_values,
         )
         .await
     }
 
+    /// Applies `patch` to this tree, which must currently be at the patch's base root.
+    /// Chunk references are resolved from this tree's store. The tree's shape depends on
+    /// the order of past writes, so the result may hold the target's entries under a
+    /// different root hash; it is then checked against the patch's target digest, and on a
+    /// mismatch the tree is reset to the base root and the call fails. Returns whether the
+    /// root changed.
+    pub async fn import_patch(&mut self, patch: &Patch) -> Result<bool> {
+        self.begin_write()?;
+        let result = self.import_patch_in_write(patch).await;
+        self.end_write(result)
+    }
+
+    async fn import_patch_in_write(&mut self, patch: &Patch) -> Result<bool> {
+        let base_root_hash = self.root_hash;
+        if base_root_hash != patch.base_root {
+            return Err(ProllyError::InvalidOperation(format!(
+                "Patch base root {:?} does not match the tree's current root {:?}",
+                patch.base_root, base_root_hash
+            )));
+        }
+        if let Err(e) = self.apply_patch_records(patch).await {
+            self.root_hash = base_root_hash;
+            return Err(e);
+        }
+        if self.root_hash != patch.target_root {
+            let reached_digest = match entries_digest(self.root_hash, &self.store, &self.node_cache).await {
+                Ok(digest) => digest,
+                Err(e) => {
+                    self.root_hash = base_root_hash;
+                    return Err(e);
+                }
+            };
+            if reached_digest != patch.target_digest {
+                let reached = self.root_hash;
+                self.root_hash = base_root_hash;
+                return Err(ProllyError::ChecksumMismatch {
+                    context: format!("Patch for target root {:?} produced different entries in {:?}", patch.target_root, reached),
+                });
+            }
+        }
+        Ok(base_root_hash != self.root_hash)
+    }
+
+    async fn apply_patch_records(&mut self, patch: &Patch) -> Result<()> {
+        for record in &patch.records {
+            match &record.value {
+                None => { self.delete(&record.key).await?; }
+                Some(PatchValue::Inline(value)) => { self.insert(record.key.clone(), value.clone()).await?; }
+                Some(PatchValue::ChunkRefs(chunk_hashes)) => {
+                    let mut value = Vec::new();
+                    for chunk_hash in chunk_hashes {
+                        let chunk_bytes = self.store.get(chunk_hash).await?
+                            .ok_or(ProllyError::ChunkNotFound(*chunk_hash))?;
+                        value.extend_from_slice(&chunk_bytes);
+                    }
+                    self.insert(record.key.clone(), value).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects the chunks reachable from the current root, the pinned roots and `extra_roots`,
+    /// e.g. older versions the application keeps, leaving out garbage from intermediate writes.
+    /// Returns the retained roots (the current root first, without duplicates) and the chunks.
+    pub async fn live_chunks(&self, extra_roots: &[Hash]) -> Result<(Vec<Hash>, std::collections::HashMap<Hash, Vec<u8>>)> {
+        let retained_roots = self.retained_roots(extra_roots);
+        let live_hashes = reachable_chunks(self.store.as_ref(), &retained_roots, None).await?;
+        Ok((retained_roots, self.read_chunks(live_hashes).await?))
+    }
+
+    /// Copies the chunks reachable from the current root, the pinned roots and `extra_roots`
+    /// into `destination` and returns a tree over it with the same root and configuration.
+    /// Those roots can be checked out from the new tree; nothing else is copied.
+    pub async fn compact_into<D: ChunkStore>(&self, destination: D, extra_roots: &[Hash]) -> Result<ProllyTree<D>> {
+        let destination = compact_into(self.store.as_ref(), destination, &self.retained_roots(extra_roots)).await?;
+        let mut tree = ProllyTree::new(Arc::new(destination), self.config.clone());
+        tree.root_hash = self.root_hash;
+        Ok(tree)
+    }
+
+    /// The current root followed by the pinned roots and `extra_roots`, without duplicates.
+    fn retained_roots(&self, extra_roots: &[Hash]) -> Vec<Hash> {
+        let mut retained_roots: Vec<Hash> = Vec::from_iter(self.root_hash);
+        let mut pinned_roots = self.root_pins.pinned_roots();
+        pinned_roots.sort_unstable();
+        for root in pinned_roots.iter().chain(extra_roots) {
+            if !retained_roots.contains(root) {
+                retained_roots.push(*root);
+            }
+        }
+        retained_roots
+    }
+
+    async fn read_chunks(&self, hashes: impl IntoIterator<Item = Hash>) -> Result<std::collections::HashMap<Hash, Vec<u8>>> {
+        let mut chunks = std::collections::HashMap::new();
+        for hash in hashes {
+            let bytes = self.store.get(&hash).await?.ok_or(ProllyError::ChunkNotFound(hash))?;
+            chunks.insert(hash, bytes);
+        }
+        Ok(chunks)
+    }
+
+    /// Collects the chunks reachable from `target_root_hash` that a store holding
+    /// `base_root_hash` is missing (see `delta_chunks`). Both roots must be readable from this
+    /// tree's store, but only the parts of the two trees that differ are walked.
+    pub async fn export_delta(&self, base_root_hash: Option<Hash>, target_root_hash: Option<Hash>) -> Result<DeltaBundleV2> {
+        let delta_hashes = delta_chunks(self.store.as_ref(), base_root_hash, target_root_hash).await?;
+        let chunks = self.read_chunks(delta_hashes).await?;
+        Ok(DeltaBundleV2 { base_root: base_root_hash, target_root: target_root_hash, chunks })
+    }
+
+    /// Writes the chunks of `bundle` into this tree's store and checks out its target root.
+    ///
+    /// Before anything is written, the bundle must be self-complete relative to its base: the
+    /// store has to hold the base root, and every chunk reachable from the target must be
+    /// either in the bundle or in the store. The store must address chunks by the hash of
+    /// their bytes: each chunk has to be stored under its hash in the bundle, otherwise the
+    /// call fails without checking out the target, and the chunks already written are left
+    /// to garbage collection. Returns whether the root changed.
+    pub async fn import_delta(&mut self, bundle: &DeltaBundleV2) -> Result<bool> {
+        self.begin_write()?;
+        let result = self.import_delta_in_write(bundle).await;
+        self.end_write(result)
+    }
+
+    async fn import_delta_in_write(&mut self, bundle: &DeltaBundleV2) -> Result<bool> {
+        // Chunks outside the bundle come from the base, whose subtrees are complete, and are
+        // checked in one `exists_many` call once the bundle has been walked.
+        let mut outside: Vec<Hash> = bundle.base_root.into_iter().collect();
+        // (hash, whether the chunk is a node whose references must be followed)
+        let mut queue: Vec<(Hash, bool)> = bundle.target_root.into_iter().map(|hash| (hash, true)).collect();
+        let mut visited = std::collections::HashSet::new();
+        while let Some((hash, is_node)) = queue.pop() {
+            if !visited.insert(hash) {
+                continue;
+            }
+            let Some(bytes) = bundle.chunks.get(&hash) else {
+                outside.push(hash);
+                continue;
+            };
+            if !is_node {
+                continue;
+            }
+            match Node::decode(bytes)? {
+                Node::Internal { children, .. } => queue.extend(children.iter().map(|child| (child.child_hash, true))),
+                Node::Leaf { entries, .. } => {
+                    for entry in &entries {
+                        match &entry.value {
+                            ValueRepr::Inline(_) => {}
+                            ValueRepr::Chunked(data_hash) => queue.push((*data_hash, false)),
+                            ValueRepr::ChunkedSequence { chunk_hashes, .. } => queue.extend(chunk_hashes.iter().map(|data_hash| (*data_hash, false))),
+                        }
+                    }
+                }
+            }
+        }
+        let found = check_batch_len("exists_many", outside.len(), self.store.exists_many(&outside).await?)?;
+        if let Some(missing) = outside.iter().zip(found).find_map(|(hash, found)| (!found).then_some(*hash)) {
+            return Err(if Some(missing) == bundle.base_root {
+                ProllyError::InvalidOperation(format!("The store does not hold the delta's base root {:?}", missing))
+            } else {
+                ProllyError::InvalidOperation(format!(
+                    "Delta is incomplete: chunk {:?} is neither in the bundle nor in the store", missing
+                ))
+            });
+        }
+
+        let (hashes, chunks): (Vec<Hash>, Vec<Vec<u8>>) = bundle.chunks.iter().map(|(hash, bytes)| (*hash, bytes.clone())).unzip();
+        let stored_hashes = check_batch_len("put_many", hashes.len(), self.store.put_many(chunks).await?)?;
+        if let Some((hash, stored_hash)) = hashes.iter().zip(&stored_hashes).find(|(hash, stored_hash)| hash != stored_hash) {
+            return Err(ProllyError::ChecksumMismatch {
+                context: format!("Delta chunk {:?} was stored under hash {:?}", hash, stored_hash),
+            });
+        }
+        self.checkout(bundle.target_root).await
+    }
+
+    /// Copies the chunks of the current root that `destination` is missing into it, skipping
+    /// every subtree it already holds. A tree over `destination` can then check out the root.
+    pub async fn sync_to<D: ChunkStore + ?Sized>(&self, destination: Arc<D>, options: SyncOptions) -> Result<SyncStats> {
+        sync_to_store(self.root_hash, self.store.as_ref(), destination, options).await
+    }
+
+    /// Deletes the chunks not reachable from `app_provided_live_root_hashes`, the current
+    /// root or a pinned root. See `GarbageCollector::collect` for what the store must support.
     pub async fn gc(&self, app_provided_live_root_hashes: &[Hash]) -> Result<usize> {
         let collector = GarbageCollector::new(Arc::clone(&self.store));
+        let collected = collector.collect(&self.gc_live_roots(app_provided_live_root_hashes)).await?;
+        // Collected nodes must not keep being served from the cache.
+        if collected > 0 {
+            self.node_cache.clear();
+        }
+        Ok(collected)
+    }
+
+    /// Dry run of `gc`: reports what a collection keeping `app_provided_live_root_hashes`,
+    /// the current root and the pinned roots would delete, without deleting anything.
+    pub async fn plan_gc(&self, app_provided_live_root_hashes: &[Hash]) -> Result<GcPlan> {
+        let collector = GarbageCollector::new(Arc::clone(&self.store));
+        collector.plan(&self.gc_live_roots(app_provided_live_root_hashes)).await
+    }
+
+    /// Executes a plan made by `plan_gc`. Fails if the current root is not among the plan's
+    /// live roots, since chunks of a tree written after planning could then be deleted.
+    ///
+    /// Plans may come back edited, e.g. from JS, so the planned chunks still reachable from
+    /// the plan's live roots, the current root or a pinned root are spared. This marks the
+    /// live chunks again, as a full collection would.
+    pub async fn collect_gc_plan(&self, plan: &GcPlan) -> Result<usize> {
+        if let Some(current_root) = self.root_hash && !plan.live_roots.contains(&current_root) {
+            return Err(ProllyError::InvalidOperation(
+                "The tree's root changed since the GC plan was made; plan again".to_string(),
+            ));
+        }
+        let collector = GarbageCollector::new(Arc::clone(&self.store));
+        let live_chunks = collector.mark(&self.gc_live_roots(&plan.live_roots)).await?;
+        let mut checked_plan = plan.clone();
+        checked_plan.dead_chunks.retain(|chunk| !live_chunks.contains(&chunk.hash));
+        let collected = collector.collect_with(&checked_plan).await?;
+        if collected > 0 {
+            self.node_cache.clear();
+        }
+        Ok(collected)
+    }
+
+    /// Starts an incremental collection keeping `app_provided_live_root_hashes`, the current
+    /// root and the pinned roots, replacing any collection in progress. Drive it with
+    /// `gc_step`.
+    pub async fn start_incremental_gc(&mut self, app_provided_live_root_hashes: &[Hash]) -> Result<GcProgress> {
+        let live_roots = self.gc_live_roots(app_provided_live_root_hashes);
+        let gc = IncrementalGc::start(Arc::clone(&self.store), &live_roots).await?;
+        let progress = gc.progress();
+        self.incremental_gc = Some(gc);
+        Ok(progress)
+    }
+
+    /// Runs up to `max_chunks` chunks of the incremental collection started by
+    /// `start_incremental_gc`. The current root and pinned roots at the time of each step
+    /// are kept as well. Once the returned phase is `Done`, the collection is over.
+    pub async fn gc_step(&mut self, max_chunks: usize) -> Result<GcProgress> {
+        let live_roots = self.gc_live_roots(&[]);
+        let gc = self.incremental_gc.as_mut().ok_or_else(|| ProllyError::InvalidOperation(
+            "No incremental garbage collection in progress".to_string(),
+        ))?;
+        gc.add_roots(&live_roots);
+        let collected_before = gc.progress().collected_chunks;
+        let progress = gc.step(max_chunks).await?;
+        if progress.collected_chunks > collected_before {
+            self.node_cache.clear();
+        }
+        if progress.phase == GcPhase::Done {
+            self.incremental_gc = None;
+        }
+        Ok(progress)
+    }
+
+    fn gc_live_roots(&self, app_provided_live_root_hashes: &[Hash]) -> Vec<Hash> {
         let mut all_live_roots_set = app_provided_live_root_hashes.iter().cloned().collect::<std::collections::HashSet<Hash>>();
+        all_live_roots_set.extend(self.root_pins.pinned_roots());
         if let Some(current_root) = self.root_hash {
             all_live_roots_set.insert(current_root);
         }
-        let all_live_roots_vec = all_live_roots_set.into_iter().collect::<Vec<Hash>>();
-        collector.collect(&all_live_roots_vec).await
+        all_live_roots_set.into_iter().collect::<Vec<Hash>>()
     }
 
     pub async fn scan(&self, args: ScanArgs) -> Result<ScanPage> {
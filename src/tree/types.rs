@@ -108,6 +108,7 @@ pub enum HierarchyItem {
     InternalEntryItem {
         parent_hash: Hash,
         entry_index: usize,
+        /// Separator bounding the child's keys from above; not necessarily a stored key.
         boundary_key: Key,
         child_hash: Hash,
         num_items_subtree: u64,
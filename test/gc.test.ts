@@ -275,4 +275,123 @@ describe("PTree Garbage Collection (GC)", () => {
       createLargeTestData(2300)
     );
   });
+
+  it("GC: should plan a collection without deleting and then execute the plan", async () => {
+    const tree = new PTree();
+    await tree.insert(toU8("k1"), toU8("v1"));
+    await tree.insert(toU8("big"), createLargeTestData(2300));
+    const oldRoot = await getRootHash(tree);
+    await tree.delete(toU8("big"));
+    await tree.insert(toU8("k2"), toU8("v2"));
+    const currentRoot = await getRootHash(tree);
+
+    const chunksBefore = await countChunks(tree);
+    const plan = await tree.planGc([]);
+    expect(await countChunks(tree)).toBe(chunksBefore);
+
+    expect(plan.liveRoots.length).toBe(1);
+    expectU8Eq(plan.liveRoots[0], currentRoot);
+    expect(plan.liveChunks + plan.deadChunks.length).toBe(chunksBefore);
+    expect(plan.nodeChunks + plan.valueChunks).toBe(plan.deadChunks.length);
+    expect(plan.valueChunks).toBeGreaterThanOrEqual(1);
+    expect(plan.deadChunks.some((c) => c.kind === "node" && Array.from(c.hash).join() === Array.from(oldRoot!).join())).toBe(true);
+    const sum = (kind: string) => plan.deadChunks.filter((c) => c.kind === kind).reduce((n, c) => n + c.size, 0);
+    expect(sum("node")).toBe(plan.nodeBytes);
+    expect(sum("value")).toBe(plan.valueBytes);
+
+    // Keeping the old root alive leaves its chunks out of the plan.
+    const keepOld = await tree.planGc([oldRoot!]);
+    expect(keepOld.deadChunks.length).toBeLessThan(plan.deadChunks.length);
+
+    expect(await tree.executeGcPlan(plan)).toBe(plan.deadChunks.length);
+    expect(await countChunks(tree)).toBe(plan.liveChunks);
+    expectU8Eq(await tree.get(toU8("k2")), toU8("v2"));
+    expect(await tree.get(toU8("big"))).toBeNull();
+
+    // A plan made before the root changed is rejected.
+    const stale = await tree.planGc([]);
+    await tree.insert(toU8("k3"), toU8("v3"));
+    await expect(tree.executeGcPlan(stale)).rejects.toThrow(/root changed/);
+  });
+
+  it("GC: should keep pinned roots until they are unpinned", async () => {
+    const tree = new PTree();
+    await tree.insert(toU8("a"), toU8("1"));
+    await tree.insert(toU8("big"), createLargeTestData(2300));
+    const pinned = await getRootHash(tree);
+    await tree.delete(toU8("big"));
+
+    await tree.pinRoot(pinned!);
+    await tree.pinRoot(pinned!);
+    expect(await tree.triggerGc([])).toBe(0);
+    await tree.unpinRoot(pinned!);
+    const plan = await tree.planGc([]);
+    expect(plan.deadChunks.length).toBe(0);
+    expect(plan.liveRoots.length).toBe(2);
+    expect(plan.generation).not.toBeNull();
+
+    await tree.unpinRoot(pinned!);
+    await expect(tree.unpinRoot(pinned!)).rejects.toThrow(/not pinned/);
+    expect(await tree.triggerGc([])).toBe(2);
+    await expect(tree.checkout(pinned!)).rejects.toThrow();
+    expectU8Eq(await tree.get(toU8("a")), toU8("1"));
+  });
+
+  it("GC: should collect incrementally in bounded steps while writes continue", async () => {
+    const tree = new PTree();
+    for (let i = 0; i < 200; i++) {
+      await tree.insert(toU8(`key${String(i).padStart(3, "0")}`), toU8(`v${i}`));
+    }
+    for (let i = 0; i < 200; i += 2) {
+      await tree.insert(toU8(`key${String(i).padStart(3, "0")}`), toU8(`w${i}`));
+    }
+
+    let progress = await tree.startIncrementalGc([]);
+    expect(progress.phase).toBe("mark");
+    let steps = 0;
+    let swept = 0;
+    while (progress.phase !== "done") {
+      await tree.insert(toU8(`extra${steps}`), toU8(`e${steps}`));
+      progress = await tree.gcStep(5);
+      expect(progress.sweptChunks - swept).toBeLessThanOrEqual(5);
+      swept = progress.sweptChunks;
+      steps++;
+    }
+    expect(steps).toBeGreaterThan(1);
+    expect(progress.collectedChunks).toBeGreaterThan(0);
+    expect(progress.sweptChunks).toBe(progress.sweepTotal);
+    await expect(tree.gcStep(5)).rejects.toThrow(/No incremental/);
+
+    for (let i = 0; i < 200; i++) {
+      const expected = i % 2 === 0 ? `w${i}` : `v${i}`;
+      expectU8Eq(await tree.get(toU8(`key${String(i).padStart(3, "0")}`)), toU8(expected));
+    }
+    for (let i = 0; i < steps; i++) {
+      expectU8Eq(await tree.get(toU8(`extra${i}`)), toU8(`e${i}`));
+    }
+  });
+
+  it("GC: should compact live chunks into a new tree", async () => {
+    const tree = new PTree();
+    await tree.insert(toU8("a"), toU8("1"));
+    const retained = await getRootHash(tree);
+    await tree.insert(toU8("big"), createLargeTestData(2300));
+    const dropped = await getRootHash(tree);
+    await tree.delete(toU8("big"));
+    await tree.insert(toU8("b"), toU8("2"));
+    const current = await getRootHash(tree);
+    const chunksBefore = await countChunks(tree);
+
+    const compacted = await tree.compact([retained!]);
+    expect(await countChunks(tree)).toBe(chunksBefore);
+    expect(await countChunks(compacted)).toBeLessThan(chunksBefore);
+    expectU8Eq(await getRootHash(compacted), current);
+    expectU8Eq(await compacted.get(toU8("a")), toU8("1"));
+    expectU8Eq(await compacted.get(toU8("b")), toU8("2"));
+
+    await expect(compacted.checkout(dropped!)).rejects.toThrow();
+    await compacted.checkout(retained!);
+    expect(await compacted.get(toU8("b"))).toBeNull();
+    expectU8Eq(await tree.get(toU8("b")), toU8("2"));
+  });
 });
@@ -183,4 +183,33 @@ describe("ProllyTree Hierarchy Scan", () => {
       expect(result.hasNextPage).toBe(false);
     }
   });
+
+  it("should store shortened separators as internal boundary keys", async () => {
+    const treeWithLongKeys = new PTree({ targetFanout: 4, minFanout: 2 });
+    const prefix = "x".repeat(200);
+    const keys: string[] = [];
+    for (let i = 0; i < 40; i++) {
+      const key = `${String.fromCharCode(97 + (i % 26))}${prefix}${i.toString().padStart(3, "0")}`;
+      keys.push(key);
+      await treeWithLongKeys.insert(toU8(key), toU8(`v${i}`));
+    }
+
+    const result = await treeWithLongKeys.hierarchyScan({});
+    const internalEntries = result.items.filter(
+      (item) => item.type === "InternalEntry"
+    ) as Extract<HierarchyItem, { type: "InternalEntry" }>[];
+    expect(internalEntries.length).toBeGreaterThan(2);
+    // Every entry except the last one in each node is a separator, which for these keys is far shorter.
+    const shortened = internalEntries.filter(
+      (entry) => entry.boundaryKey.length < prefix.length
+    );
+    expect(shortened.length).toBeGreaterThan(0);
+
+    for (const key of keys) {
+      expect(
+        u8ToString((await treeWithLongKeys.get(toU8(key))) as Uint8Array),
+        `get ${key.slice(0, 4)}...`
+      ).toBe(`v${keys.indexOf(key)}`);
+    }
+  });
 });
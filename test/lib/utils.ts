@@ -11,8 +11,11 @@ export function u8ToString(u8: Uint8Array): string {
 // Define DiffEntry type for clarity in tests
 export type JsDiffEntry = {
   key: Uint8Array;
+  kind?: "added" | "removed" | "modified";
   leftValue?: Uint8Array;
   rightValue?: Uint8Array;
+  leftDigest?: Uint8Array;
+  rightDigest?: Uint8Array;
 };
 
 export const expectU8Eq = (
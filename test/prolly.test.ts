@@ -193,6 +193,95 @@ describe("PTree", () => {
     expectU8Eq(rootHash2, rootHash); // Root hashes should match
   });
 
+  it("should sync only missing chunks to another tree's store", async () => {
+    const source = new PTree({ targetFanout: 4, minFanout: 2 });
+    for (let i = 0; i < 100; i++) {
+      await source.insert(toU8(`key${String(i).padStart(3, "0")}`), toU8(`value${i}`));
+    }
+    const replica = new PTree({ targetFanout: 4, minFanout: 2 });
+
+    const first = await source.syncTo(replica);
+    expect(first.chunksSent).toBeGreaterThan(0);
+    expect(first.chunksSkipped).toBe(0);
+    const rootHash = (await source.getRootHash()) as Uint8Array;
+    await replica.checkout(rootHash);
+    expectU8Eq((await replica.get(toU8("key042"))) as Uint8Array | null, toU8("value42"));
+
+    // Nothing is missing any more: the root is offered and skipped.
+    const again = await source.syncTo(replica);
+    expect(again.chunksSent).toBe(0);
+    expect(again.chunksSkipped).toBe(1);
+
+    // After one edit, only the changed path is transferred.
+    await source.insert(toU8("key042"), toU8("changed"));
+    const delta = await source.syncTo(replica, { batchSize: 2 });
+    expect(delta.chunksSent).toBeGreaterThan(0);
+    expect(delta.chunksSent).toBeLessThan(first.chunksSent);
+    await replica.checkout((await source.getRootHash()) as Uint8Array);
+    expectU8Eq((await replica.get(toU8("key042"))) as Uint8Array | null, toU8("changed"));
+    expectU8Eq((await replica.get(toU8("key099"))) as Uint8Array | null, toU8("value99"));
+  });
+
+  it("should export and import deltas between two roots", async () => {
+    const tree = new PTree({ targetFanout: 4, minFanout: 2 });
+    for (let i = 0; i < 100; i++) {
+      await tree.insert(toU8(`key${String(i).padStart(3, "0")}`), toU8(`value${i}`));
+    }
+    const baseRoot = (await tree.getRootHash()) as Uint8Array;
+    const replica = await PTree.loadTreeFromFileBytes(await tree.saveTreeToFileBytes());
+
+    await tree.insert(toU8("key042"), toU8("changed"));
+    await tree.delete(toU8("key077"));
+    const targetRoot = (await tree.getRootHash()) as Uint8Array;
+
+    const delta = await tree.exportDelta(baseRoot, targetRoot);
+    const full = await tree.exportDelta(null, targetRoot);
+    expect(delta.length).toBeLessThan(full.length);
+    expect(new TextDecoder().decode(delta.subarray(0, 8))).toBe("PRLYDLV2");
+    await expect(PTree.loadTreeFromFileBytes(delta)).rejects.toThrow(/delta bundle/);
+
+    // A tree without the base cannot take the delta.
+    await expect(new PTree().importDelta(delta)).rejects.toThrow(/base root/);
+
+    expect(await replica.importDelta(delta)).toBe(true);
+    expectU8Eq((await replica.getRootHash()) as Uint8Array | null, targetRoot);
+    expectU8Eq((await replica.get(toU8("key042"))) as Uint8Array | null, toU8("changed"));
+    expect(await replica.get(toU8("key077"))).toBeNull();
+  });
+
+  it("should save only live chunks and keep retained roots loadable", async () => {
+    const tree = new PTree({ targetFanout: 4, minFanout: 2 });
+    for (let i = 0; i < 100; i++) {
+      await tree.insert(toU8(`key${String(i).padStart(3, "0")}`), toU8(`value${i}`));
+    }
+    const oldRoot = (await tree.getRootHash()) as Uint8Array;
+    for (let i = 0; i < 100; i += 10) {
+      await tree.insert(toU8(`key${String(i).padStart(3, "0")}`), toU8(`updated${i}`));
+    }
+    const allChunks = (await tree.exportChunks()) as Map<Uint8Array, Uint8Array>;
+
+    const liveOnly = await tree.saveTreeToFileBytes();
+    let totalChunkBytes = 0;
+    allChunks.forEach((chunk) => (totalChunkBytes += chunk.length));
+    expect(liveOnly.length).toBeLessThan(totalChunkBytes);
+
+    const loaded = await PTree.loadTreeFromFileBytes(liveOnly);
+    expectU8Eq((await loaded.get(toU8("key010"))) as Uint8Array | null, toU8("updated10"));
+    await expect(loaded.checkout(oldRoot)).rejects.toThrow();
+
+    const withHistory = await tree.saveTreeToFileBytes("with history", [oldRoot]);
+    expect(withHistory.length).toBeGreaterThan(liveOnly.length);
+    const loadedWithHistory = await PTree.loadTreeFromFileBytes(withHistory);
+    const pinned = await loadedWithHistory.getPinnedRoots();
+    expect(pinned).toHaveLength(2);
+    expect(pinned.some((root) => Buffer.from(root).equals(Buffer.from(oldRoot)))).toBe(true);
+    // The retained roots are pinned on load, so a collection keeps the old version.
+    await loadedWithHistory.insert(toU8("key200"), toU8("after load"));
+    await loadedWithHistory.triggerGc([]);
+    await loadedWithHistory.checkout(oldRoot);
+    expectU8Eq((await loadedWithHistory.get(toU8("key010"))) as Uint8Array | null, toU8("value10"));
+  });
+
   it("should overwrite existing values on insert with the same key", async () => {
     const tree = new PTree();
     const key = toU8("overwrite_key");
@@ -969,6 +1058,51 @@ describe("PTree", () => {
       // We don't have the hash from *before* the k02 delete easily, but we know state changed.
     });
   });
+
+  it("should serve repeated reads of upper levels from the node cache", async () => {
+    const tree = new PTree({ targetFanout: 4, minFanout: 2 });
+    for (let i = 0; i < 50; i++) {
+      const key = `key${String(i).padStart(3, "0")}`;
+      await tree.insert(toU8(key), toU8(`value${i}`));
+    }
+
+    const before = await tree.getNodeCacheStats();
+    expect(before.entries).toBeGreaterThan(0);
+
+    for (let i = 0; i < 50; i++) {
+      const key = `key${String(i).padStart(3, "0")}`;
+      expectU8Eq(
+        (await tree.get(toU8(key))) as Uint8Array | null,
+        toU8(`value${i}`)
+      );
+    }
+
+    const after = await tree.getNodeCacheStats();
+    expect(after.hits).toBeGreaterThan(before.hits);
+    expect(after.bytes).toBeGreaterThan(0);
+  });
+
+  it("should bound the node cache by the limits in the tree options", async () => {
+    const small = new PTree({ targetFanout: 4, minFanout: 2, nodeCacheMaxNodes: 3 });
+    const disabled = new PTree({ targetFanout: 4, minFanout: 2, nodeCacheMaxBytes: 0 });
+    for (let i = 0; i < 50; i++) {
+      const key = `key${String(i).padStart(3, "0")}`;
+      await small.insert(toU8(key), toU8(`value${i}`));
+      await disabled.insert(toU8(key), toU8(`value${i}`));
+    }
+    for (const tree of [small, disabled]) {
+      expectU8Eq((await tree.get(toU8("key025"))) as Uint8Array | null, toU8("value25"));
+    }
+
+    const smallStats = await small.getNodeCacheStats();
+    expect(smallStats.entries).toBeLessThanOrEqual(3);
+    expect(smallStats.evictions).toBeGreaterThan(0);
+    const disabledStats = await disabled.getNodeCacheStats();
+    expect(disabledStats.entries).toBe(0);
+    expect(disabledStats.hits).toBe(0);
+
+    expect(() => new PTree({ nodeCacheMaxNodes: -1 })).toThrow(/non-negative integer/);
+  });
 });
 
 describe("PTree Sync Operations", () => {
@@ -1676,6 +1810,336 @@ describe("PTree Diff", () => {
     ];
     expectDiffsToMatch(diffs, expected, "CDC diff");
   });
+
+  it("should report only keys and kinds, or digests, without loading values", async () => {
+    const tree = new PTree();
+    const largeVal1 = createLargeTestData(2000, 1);
+    const largeVal2 = createLargeTestData(2500, 2);
+
+    await tree.insert(toU8("a"), toU8("val_a"));
+    await tree.insert(toU8("large"), largeVal1);
+    const hash1 = (await tree.getRootHash()) as Uint8Array | null;
+
+    await tree.delete(toU8("a"));
+    await tree.insert(toU8("large"), largeVal2);
+    await tree.insert(toU8("z"), toU8("val_z"));
+    const hash2 = (await tree.getRootHash()) as Uint8Array | null;
+
+    const keyDiffs = (await tree.diffRoots(hash1, hash2, {
+      mode: "keys",
+    })) as JsDiffEntry[];
+    expect(
+      keyDiffs.map((d) => [Buffer.from(d.key).toString(), d.kind])
+    ).toEqual([
+      ["a", "removed"],
+      ["large", "modified"],
+      ["z", "added"],
+    ]);
+    for (const d of keyDiffs) {
+      expect(d.leftValue).toBeUndefined();
+      expect(d.rightValue).toBeUndefined();
+      expect(d.leftDigest).toBeUndefined();
+    }
+
+    const digestDiffs = (await tree.diffRoots(hash1, hash2, {
+      mode: "digests",
+    })) as JsDiffEntry[];
+    expect(digestDiffs.length).toBe(3);
+    const large = digestDiffs[1];
+    expect(large.leftDigest?.length).toBe(32);
+    expect(large.rightDigest?.length).toBe(32);
+    expect(Array.from(large.leftDigest!)).not.toEqual(
+      Array.from(large.rightDigest!)
+    );
+    expect(large.leftValue).toBeUndefined();
+    expect(digestDiffs[0].rightDigest).toBeUndefined();
+  });
+
+  it("should stream diffs lazily in key order with next and nextPage", async () => {
+    const tree = new PTree({ targetFanout: 4, minFanout: 2 });
+    for (let i = 0; i < 60; i++) {
+      await tree.insert(toU8(`k${String(i).padStart(3, "0")}`), toU8(`v${i}`));
+    }
+    const hash1 = (await tree.getRootHash()) as Uint8Array | null;
+    for (let i = 0; i < 60; i += 3) {
+      await tree.insert(toU8(`k${String(i).padStart(3, "0")}`), toU8(`v${i}_mod`));
+    }
+    const hash2 = (await tree.getRootHash()) as Uint8Array | null;
+
+    const fullDiff = (await tree.diffRoots(hash1, hash2)) as JsDiffEntry[];
+    expect(fullDiff.length).toBe(20);
+
+    const stream = await tree.diffStream(hash1, hash2);
+    const first = await stream.next();
+    expect(first.done).toBe(false);
+    expectU8Eq(first.value!.key, toU8("k000"));
+    expectU8Eq(first.value!.rightValue, toU8("v0_mod"));
+
+    const page = (await stream.nextPage(5)) as JsDiffEntry[];
+    expect(page.map((d) => Buffer.from(d.key).toString())).toEqual(
+      ["k003", "k006", "k009", "k012", "k015"]
+    );
+
+    const rest = (await stream.nextPage(100)) as JsDiffEntry[];
+    expect(rest.length).toBe(14);
+    expect(await stream.nextPage(100)).toEqual([]);
+    expect((await stream.next()).done).toBe(true);
+  });
+
+  it("should diff roots of different heights without reporting unchanged keys", async () => {
+    const tree = new PTree({ targetFanout: 4, minFanout: 2 });
+    for (let i = 0; i < 3; i++) {
+      await tree.insert(toU8(`k${String(i * 20).padStart(3, "0")}`), toU8(`v${i * 20}`));
+    }
+    const shallow = (await tree.getRootHash()) as Uint8Array | null;
+    for (let i = 0; i < 60; i++) {
+      if (i % 20 !== 0) {
+        await tree.insert(toU8(`k${String(i).padStart(3, "0")}`), toU8(`v${i}`));
+      }
+    }
+    await tree.insert(toU8("k020"), toU8("v20_mod"));
+    const deep = (await tree.getRootHash()) as Uint8Array | null;
+
+    const forward = (await tree.diffRoots(shallow, deep, {
+      mode: "keys",
+    })) as JsDiffEntry[];
+    expect(forward.length).toBe(58);
+    expect(
+      forward
+        .filter((d) => d.kind !== "added")
+        .map((d) => [Buffer.from(d.key).toString(), d.kind])
+    ).toEqual([["k020", "modified"]]);
+
+    const backward = (await tree.diffRoots(deep, shallow, {
+      mode: "keys",
+    })) as JsDiffEntry[];
+    expect(backward.map((d) => Buffer.from(d.key).toString())).toEqual(
+      forward.map((d) => Buffer.from(d.key).toString())
+    );
+    expect(backward.filter((d) => d.kind === "added")).toEqual([]);
+  });
+  it("should restrict diffs to a key range with start and end", async () => {
+    const tree = new PTree({ targetFanout: 4, minFanout: 2 });
+    for (let i = 0; i < 60; i++) {
+      await tree.insert(toU8(`k${String(i).padStart(3, "0")}`), toU8(`v${i}`));
+    }
+    const hash1 = (await tree.getRootHash()) as Uint8Array | null;
+    for (let i = 0; i < 60; i += 5) {
+      await tree.insert(toU8(`k${String(i).padStart(3, "0")}`), toU8(`v${i}_mod`));
+    }
+    await tree.delete(toU8("k021"));
+    await tree.insert(toU8("k0305"), toU8("new"));
+    const hash2 = (await tree.getRootHash()) as Uint8Array | null;
+
+    const ranged = (await tree.diffRoots(hash1, hash2, {
+      start: toU8("k020"),
+      end: toU8("k035"),
+    })) as JsDiffEntry[];
+    expect(ranged.map((d) => [Buffer.from(d.key).toString(), d.kind])).toEqual([
+      ["k020", "modified"],
+      ["k021", "removed"],
+      ["k025", "modified"],
+      ["k030", "modified"],
+      ["k0305", "added"],
+    ]);
+
+    const full = (await tree.diffRoots(hash1, hash2)) as JsDiffEntry[];
+    const head = (await tree.diffRoots(hash1, hash2, { end: toU8("k020") })) as JsDiffEntry[];
+    const tail = (await tree.diffRoots(hash1, hash2, { start: toU8("k020") })) as JsDiffEntry[];
+    expect(head.length + tail.length).toBe(full.length);
+    expect(head.map((d) => Buffer.from(d.key).toString())).toEqual(
+      ["k000", "k005", "k010", "k015"]
+    );
+  });
+  it("should summarize changes without materializing entries", async () => {
+    const tree = new PTree({ targetFanout: 4, minFanout: 2 });
+    for (let i = 0; i < 40; i++) {
+      await tree.insert(toU8(`k${String(i).padStart(3, "0")}`), toU8(`v${i}`));
+    }
+    const hash1 = (await tree.getRootHash()) as Uint8Array | null;
+    for (let i = 40; i < 100; i++) {
+      await tree.insert(toU8(`k${String(i).padStart(3, "0")}`), toU8(`v${i}`));
+    }
+    await tree.insert(toU8("k005"), toU8("v5_modified"));
+    await tree.delete(toU8("k010"));
+    const hash2 = (await tree.getRootHash()) as Uint8Array | null;
+
+    expect(await tree.diffSummary(hash1, hash2)).toEqual({
+      added: 60,
+      removed: 1,
+      modified: 1,
+    });
+    expect(await tree.diffSummary(hash2, null)).toEqual({
+      added: 0,
+      removed: 99,
+      modified: 0,
+    });
+
+    const withBytes = await tree.diffSummary(hash1, hash2, {
+      includeBytes: true,
+      end: toU8("k040"),
+    });
+    expect(withBytes).toEqual({
+      added: 0,
+      removed: 1,
+      modified: 1,
+      bytesAdded: "v5_modified".length,
+      bytesRemoved: "v5".length + "v10".length,
+    });
+  });
+  it("should apply a diff to another replica, rejecting conflicts in strict mode", async () => {
+    const source = new PTree({ targetFanout: 4, minFanout: 2 });
+    const replica = new PTree({ targetFanout: 4, minFanout: 2 });
+    const largeVal = createLargeTestData(3000, 7);
+    for (let i = 0; i < 20; i++) {
+      const key = toU8(`k${String(i).padStart(3, "0")}`);
+      await source.insert(key, toU8(`v${i}`));
+      await replica.insert(key, toU8(`v${i}`));
+    }
+    const base = (await source.getRootHash()) as Uint8Array | null;
+    await source.insert(toU8("k003"), toU8("v3_mod"));
+    await source.delete(toU8("k007"));
+    await source.insert(toU8("k100"), largeVal);
+    const head = (await source.getRootHash()) as Uint8Array | null;
+    const diffs = (await source.diffRoots(base, head)) as JsDiffEntry[];
+
+    expect(await replica.applyDiff(diffs)).toBe(true);
+    expectU8Eq(await replica.getRootHash(), head);
+    expectU8Eq(await replica.get(toU8("k100")), largeVal);
+    expect(await replica.get(toU8("k007"))).toBeNull();
+    expect(await replica.applyDiff(diffs, "force")).toBe(false);
+
+    // Replaying the same diff again conflicts: the old values are gone.
+    const error = await replica.applyDiff(diffs).catch((e) => e);
+    expect(String(error)).toContain("3 conflicting key(s)");
+    expect(error.conflicts.map((c: any) => Buffer.from(c.key).toString())).toEqual(
+      ["k003", "k007", "k100"]
+    );
+    expect(error.conflicts[1].actual).toBeNull();
+    expectU8Eq(error.conflicts[1].expected, toU8("v7"));
+    expectU8Eq(await replica.getRootHash(), head);
+    await expect(replica.applyDiff([...diffs, diffs[0]])).rejects.toThrow(/several entries/);
+
+    const keysOnly = (await source.diffRoots(base, head, { mode: "keys" })) as JsDiffEntry[];
+    await expect(replica.applyDiff(keysOnly, "force")).rejects.toThrow(/missing its values/);
+  });
+  it("should export a patch and import it to reproduce the target's entries", async () => {
+    const source = new PTree({ targetFanout: 4, minFanout: 2 });
+    const replica = new PTree({ targetFanout: 4, minFanout: 2 });
+    const largeVal = createLargeTestData(5000, 3);
+    for (let i = 0; i < 30; i++) {
+      const key = toU8(`k${String(i).padStart(3, "0")}`);
+      await source.insert(key, toU8(`v${i}`));
+      await replica.insert(key, toU8(`v${i}`));
+    }
+    const base = (await source.getRootHash()) as Uint8Array | null;
+    // The target is written out of key order, so replaying the patch in key order may
+    // produce a differently shaped tree with the same entries.
+    let seed = 11;
+    for (let round = 0; round < 60; round++) {
+      seed = (seed * 1103515245 + 12345) % 2147483648;
+      const key = toU8(`k${String(seed % 80).padStart(3, "0")}`);
+      if (seed % 3 === 0) {
+        await source.delete(key);
+      } else {
+        await source.insert(key, toU8(`r${round}`));
+      }
+    }
+    await source.insert(toU8("k200"), largeVal);
+    const target = (await source.getRootHash()) as Uint8Array | null;
+
+    const patch = await source.exportPatch(base, target);
+    const refPatch = await source.exportPatch(base, target, { embedValues: false });
+    expect(refPatch.length).toBeLessThan(patch.length);
+
+    // The replica's store lacks the large value's chunks, so only the embedded patch applies.
+    await expect(replica.importPatch(refPatch)).rejects.toThrow(/Chunk not found/);
+    expectU8Eq(await replica.getRootHash(), base);
+
+    const corrupted = patch.slice();
+    corrupted[100] ^= 0xff;
+    await expect(replica.importPatch(corrupted)).rejects.toThrow(/Checksum mismatch/);
+
+    expect(await replica.importPatch(patch)).toBe(true);
+    expect(await replica.diffTree(source)).toEqual([]);
+    expectU8Eq(await replica.get(toU8("k200")), largeVal);
+
+    // The replica is no longer at the patch's base root.
+    await expect(replica.importPatch(patch)).rejects.toThrow(/base root/);
+  });
+  it("should diff against a tree held in a different store", async () => {
+    const local = new PTree({ targetFanout: 4, minFanout: 2 });
+    for (let i = 0; i < 50; i++) {
+      await local.insert(toU8(`k${String(i).padStart(3, "0")}`), toU8(`v${i}`));
+    }
+    const remote = await PTree.loadTreeFromFileBytes(await local.saveTreeToFileBytes());
+    await remote.insert(toU8("k010"), toU8("remote"));
+    await remote.delete(toU8("k020"));
+    await local.insert(toU8("k999"), toU8("local"));
+
+    // The local store does not hold the remote tree's new nodes.
+    await expect(
+      local.diffRoots(await local.getRootHash(), await remote.getRootHash())
+    ).rejects.toThrow(/Chunk not found/);
+
+    const diffs = (await local.diffTree(remote)) as JsDiffEntry[];
+    expectDiffsToMatch(diffs, [
+      { key: toU8("k010"), leftValue: toU8("v10"), rightValue: toU8("remote") },
+      { key: toU8("k020"), leftValue: toU8("v20") },
+      { key: toU8("k999"), leftValue: toU8("local") },
+    ], "Cross-store diff");
+    expect(await local.diffTree(local)).toEqual([]);
+  });
+  it("should find the first difference and compare ranges between roots", async () => {
+    const tree = new PTree({ targetFanout: 4, minFanout: 2 });
+    for (let i = 0; i < 80; i++) {
+      await tree.insert(toU8(`k${String(i).padStart(3, "0")}`), toU8(`v${i}`));
+    }
+    const hash1 = (await tree.getRootHash()) as Uint8Array | null;
+    await tree.insert(toU8("k042"), toU8("changed"));
+    await tree.delete(toU8("k061"));
+    const hash2 = (await tree.getRootHash()) as Uint8Array | null;
+
+    expect(await tree.firstDifference(hash1, hash1)).toBeNull();
+    expectU8Eq(await tree.firstDifference(hash1, hash2), toU8("k042"));
+    expectU8Eq(await tree.firstDifference(hash2, hash1, toU8("k043")), toU8("k061"));
+    expectU8Eq(await tree.firstDifference(hash1, null), toU8("k000"));
+
+    expect(await tree.rangeEqual(hash1, hash2)).toBe(false);
+    expect(await tree.rangeEqual(hash1, hash2, null, toU8("k042"))).toBe(true);
+    expect(await tree.rangeEqual(hash1, hash2, toU8("k043"), toU8("k061"))).toBe(true);
+    expect(await tree.rangeEqual(hash1, hash2, toU8("k043"), toU8("k062"))).toBe(false);
+  });
+  it("should report changed key ranges with subtree hashes and counts", async () => {
+    const tree = new PTree({ targetFanout: 4, minFanout: 2 });
+    for (let i = 0; i < 200; i++) {
+      await tree.insert(toU8(`k${String(i).padStart(3, "0")}`), toU8(`v${i}`));
+    }
+    const hash1 = (await tree.getRootHash()) as Uint8Array | null;
+    await tree.insert(toU8("k050"), toU8("changed"));
+    const hash2 = (await tree.getRootHash()) as Uint8Array | null;
+
+    expect(await tree.changedRanges(hash1, hash1)).toEqual([]);
+
+    const ranges = await tree.changedRanges(hash1, hash2);
+    expect(ranges.length).toBe(1);
+    const [range] = ranges;
+    const inRange = (key: Uint8Array) =>
+      (range.low === null || Buffer.compare(Buffer.from(key), Buffer.from(range.low)) > 0) &&
+      (range.high === null || Buffer.compare(Buffer.from(key), Buffer.from(range.high)) <= 0);
+    expect(inRange(toU8("k050"))).toBe(true);
+    expect(inRange(toU8("k000"))).toBe(false);
+    expect(range.left.every((s) => s.level === 0 && s.hash.length === 32)).toBe(true);
+    expect(range.right.every((s) => s.level === 0)).toBe(true);
+    const leftCount = range.left.reduce((sum, s) => sum + s.numItems, 0);
+    const rightCount = range.right.reduce((sum, s) => sum + s.numItems, 0);
+    expect(leftCount).toBe(rightCount);
+
+    // Against an empty root, every subtree is one-sided and counted whole.
+    const removed = await tree.changedRanges(hash1, null);
+    expect(removed.every((r) => r.right.length === 0)).toBe(true);
+    expect(removed.reduce((sum, r) => sum + r.left.reduce((n, s) => n + s.numItems, 0), 0)).toBe(200);
+  });
 }); // End Diff describe block
 
 describe("PTree Events (onChange)", () => {
@@ -536,4 +536,38 @@ describe("PTree Scanning (scanItems)", () => {
     );
     expect(page.hasNextPage).toBe(false); // Assuming endBound is tight
   });
+
+  it("should return the same items in both directions with read-ahead enabled", async () => {
+    const readAheadTree = new PTree({
+      targetFanout: 4,
+      minFanout: 2,
+      readAhead: 3,
+      maxInlineValueSize: 64,
+      cdcMinSize: 64,
+      cdcAvgSize: 256,
+      cdcMaxSize: 1024,
+    });
+    // A large value so that chunked value pieces are fetched with read-ahead as well.
+    const bigValue = toU8("x".repeat(5000));
+    const items = testDataAll.map((item, i) =>
+      i === 7 ? { key: item.key, value: bigValue } : item
+    );
+    for (const item of items) {
+      await readAheadTree.insert(item.key, item.value);
+    }
+
+    const forward = await jsPromiseToScanPageProcessed(
+      readAheadTree.scanItems({ limit: items.length + 5 })
+    );
+    expectKeyValueArrayEq(forward.items, items, "Forward read-ahead scan mismatch");
+
+    const reverse = await jsPromiseToScanPageProcessed(
+      readAheadTree.scanItems({ reverse: true, limit: items.length + 5 })
+    );
+    expectKeyValueArrayEq(
+      reverse.items,
+      [...items].reverse(),
+      "Reverse read-ahead scan mismatch"
+    );
+  });
 });
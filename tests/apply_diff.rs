@@ -0,0 +1,103 @@
+#![cfg(target_arch = "wasm32")]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use async_trait::async_trait;
+use prolly_rust::common::{Hash, TreeConfig};
+use prolly_rust::diff::{ApplyMode, DiffOptions};
+use prolly_rust::error::{ProllyError, Result};
+use prolly_rust::store::{ChunkStore, InMemoryStore};
+use prolly_rust::tree::ProllyTree;
+use wasm_bindgen_test::*;
+
+/// Fails every put once `failing` is set.
+#[derive(Debug, Default)]
+struct FlakyStore {
+    inner: InMemoryStore,
+    failing: AtomicBool,
+}
+
+#[async_trait]
+impl ChunkStore for FlakyStore {
+    async fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>> {
+        self.inner.get(hash).await
+    }
+
+    async fn put(&self, bytes: Vec<u8>) -> Result<Hash> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(ProllyError::StorageError("store is failing".to_string()));
+        }
+        self.inner.put(bytes).await
+    }
+
+    async fn delete_batch(&self, hashes: &[Hash]) -> Result<()> {
+        self.inner.delete_batch(hashes).await
+    }
+
+    async fn all_hashes(&self) -> Result<Vec<Hash>> {
+        self.inner.all_hashes().await
+    }
+}
+
+fn key(i: usize) -> Vec<u8> {
+    format!("key{i:03}").into_bytes()
+}
+
+async fn trees() -> (ProllyTree<InMemoryStore>, ProllyTree<FlakyStore>, Option<Hash>) {
+    let config = TreeConfig { target_fanout: 4, min_fanout: 2, ..TreeConfig::default() };
+    let mut source = ProllyTree::new(Arc::new(InMemoryStore::new()), config.clone());
+    let mut replica = ProllyTree::new(Arc::new(FlakyStore::default()), config);
+    for i in 0..40 {
+        source.insert(key(i), format!("v{i}").into_bytes()).await.unwrap();
+        replica.insert(key(i), format!("v{i}").into_bytes()).await.unwrap();
+    }
+    let base = source.get_root_hash();
+    for i in (0..40).step_by(4) {
+        source.insert(key(i), format!("changed{i}").into_bytes()).await.unwrap();
+    }
+    (source, replica, base)
+}
+
+#[wasm_bindgen_test]
+async fn a_failed_apply_leaves_the_tree_at_its_previous_root() {
+    let (source, mut replica, base) = trees().await;
+    let diff = ProllyTree::from_root_hash(base.unwrap(), Arc::clone(&source.store), source.config.clone())
+        .await
+        .unwrap()
+        .diff(source.get_root_hash(), DiffOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(diff.len(), 10);
+
+    replica.store.failing.store(true, Ordering::SeqCst);
+    for mode in [ApplyMode::Strict, ApplyMode::Force] {
+        assert!(matches!(replica.apply_diff(&diff, mode).await, Err(ProllyError::StorageError(_))));
+        assert_eq!(replica.get_root_hash(), base);
+    }
+    replica.store.failing.store(false, Ordering::SeqCst);
+    assert!(replica.apply_diff(&diff, ApplyMode::Strict).await.unwrap());
+    assert_eq!(replica.get_root_hash(), source.get_root_hash());
+}
+
+#[wasm_bindgen_test]
+async fn strict_mode_rejects_several_entries_for_one_key() {
+    let (source, mut replica, base) = trees().await;
+    let mut diff = ProllyTree::from_root_hash(base.unwrap(), Arc::clone(&source.store), source.config.clone())
+        .await
+        .unwrap()
+        .diff(source.get_root_hash(), DiffOptions::default())
+        .await
+        .unwrap();
+    // The second entry would overwrite the first unchecked.
+    let mut replay = diff[0].clone();
+    replay.left_value = replay.right_value.clone();
+    replay.right_value = Some(b"unchecked".to_vec());
+    diff.push(replay);
+
+    let error = replica.apply_diff(&diff, ApplyMode::Strict).await.unwrap_err();
+    assert!(matches!(error, ProllyError::InvalidOperation(ref message) if message.contains("several entries")));
+    assert_eq!(replica.get_root_hash(), base);
+    assert!(replica.apply_diff(&diff, ApplyMode::Force).await.unwrap());
+    assert_eq!(replica.get(&key(0)).await.unwrap(), Some(b"unchecked".to_vec()));
+}
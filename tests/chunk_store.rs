@@ -0,0 +1,154 @@
+#![cfg(target_arch = "wasm32")]
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use prolly_rust::common::{Hash, TreeConfig};
+use prolly_rust::error::{ProllyError, Result};
+use prolly_rust::gc::GarbageCollector;
+use prolly_rust::node::cache::NodeCache;
+use prolly_rust::store::{ChunkStore, HashPager, InMemoryStore};
+use prolly_rust::tree::ProllyTree;
+use wasm_bindgen_test::*;
+
+async fn store_with_chunks(count: usize) -> (InMemoryStore, Vec<Hash>) {
+    let store = InMemoryStore::new();
+    let mut hashes = Vec::new();
+    for i in 0..count {
+        hashes.push(store.put(format!("chunk {i}").into_bytes()).await.unwrap());
+    }
+    hashes.sort_unstable();
+    (store, hashes)
+}
+
+#[wasm_bindgen_test]
+async fn hashes_page_is_ascending_and_excludes_after() {
+    let (store, hashes) = store_with_chunks(50).await;
+
+    let first = store.hashes_page(None, 10).await.unwrap();
+    assert_eq!(first, hashes[..10]);
+    let second = store.hashes_page(Some(&first[9]), 10).await.unwrap();
+    assert_eq!(second, hashes[10..20]);
+    assert!(!second.contains(&first[9]));
+
+    // `after` need not be a stored hash.
+    let mut between = hashes[24];
+    between[31] = between[31].wrapping_add(1);
+    if between != hashes[25] {
+        assert_eq!(store.hashes_page(Some(&between), 3).await.unwrap(), hashes[25..28]);
+    }
+
+    assert!(store.hashes_page(Some(&hashes[49]), 10).await.unwrap().is_empty());
+    assert_eq!(store.hashes_page(Some(&hashes[45]), 10).await.unwrap(), hashes[46..]);
+}
+
+#[wasm_bindgen_test]
+async fn hash_pager_visits_every_hash_once_for_any_page_size() {
+    let (store, hashes) = store_with_chunks(37).await;
+    for limit in [1, 5, 36, 37, 38, 100] {
+        let mut pager = HashPager::new();
+        let mut seen = Vec::new();
+        while let Some(page) = pager.next_page(&store, limit).await.unwrap() {
+            assert!(page.len() <= limit);
+            seen.extend(page);
+        }
+        assert!(pager.is_done());
+        assert_eq!(seen, hashes, "page size {limit}");
+    }
+}
+
+#[wasm_bindgen_test]
+async fn batch_methods_match_per_item_calls() {
+    let store = InMemoryStore::new();
+    let chunks: Vec<Vec<u8>> = vec![b"a".to_vec(), b"b".to_vec(), b"a".to_vec()];
+    let hashes = store.put_many(chunks.clone()).await.unwrap();
+    assert_eq!(hashes.len(), 3);
+    assert_eq!(hashes[0], hashes[2]);
+    for (hash, bytes) in hashes.iter().zip(&chunks) {
+        assert_eq!(store.get(hash).await.unwrap().as_ref(), Some(bytes));
+    }
+
+    let missing = [7u8; 32];
+    let lookup = [hashes[1], missing, hashes[0]];
+    assert_eq!(store.exists_many(&lookup).await.unwrap(), vec![true, false, true]);
+    assert_eq!(
+        store.get_many(&lookup).await.unwrap(),
+        vec![Some(b"b".to_vec()), None, Some(b"a".to_vec())]
+    );
+    assert!(store.get_many(&[]).await.unwrap().is_empty());
+}
+
+/// A store whose `get_many` drops the last result, breaking the batch contract.
+#[derive(Debug, Default)]
+struct ShortBatchStore {
+    inner: InMemoryStore,
+}
+
+#[async_trait]
+impl ChunkStore for ShortBatchStore {
+    async fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>> {
+        self.inner.get(hash).await
+    }
+
+    async fn put(&self, bytes: Vec<u8>) -> Result<Hash> {
+        self.inner.put(bytes).await
+    }
+
+    async fn get_many(&self, hashes: &[Hash]) -> Result<Vec<Option<Vec<u8>>>> {
+        let mut chunks = self.inner.get_many(hashes).await?;
+        chunks.pop();
+        Ok(chunks)
+    }
+
+    async fn delete_batch(&self, hashes: &[Hash]) -> Result<()> {
+        self.inner.delete_batch(hashes).await
+    }
+
+    async fn all_hashes(&self) -> Result<Vec<Hash>> {
+        self.inner.all_hashes().await
+    }
+
+    async fn hashes_page(&self, after: Option<&Hash>, limit: usize) -> Result<Vec<Hash>> {
+        self.inner.hashes_page(after, limit).await
+    }
+
+    async fn write_generation(&self) -> Result<Option<u64>> {
+        self.inner.write_generation().await
+    }
+
+    async fn delete_batch_unless_put_since(&self, hashes: &[Hash], generation: u64) -> Result<usize> {
+        self.inner.delete_batch_unless_put_since(hashes, generation).await
+    }
+}
+
+#[wasm_bindgen_test]
+async fn short_batch_results_are_storage_errors() {
+    let store = Arc::new(ShortBatchStore::default());
+    let mut tree = ProllyTree::new(Arc::clone(&store), TreeConfig { target_fanout: 4, min_fanout: 2, ..TreeConfig::default() });
+    for i in 0..20 {
+        tree.insert(format!("k{i:02}").into_bytes(), format!("v{i}").into_bytes()).await.unwrap();
+    }
+    let root = tree.get_root_hash().unwrap();
+    let other = store.put(b"not a node".to_vec()).await.unwrap();
+
+    let loaded = NodeCache::default().load_many(store.as_ref(), &[root, other]).await;
+    assert!(matches!(loaded, Err(ProllyError::StorageError(_))));
+    let collected = GarbageCollector::new(Arc::clone(&store)).collect(&[root]).await;
+    assert!(matches!(collected, Err(ProllyError::StorageError(_))));
+    assert!(store.exists(&root).await.unwrap());
+}
+
+#[wasm_bindgen_test]
+async fn cache_hits_share_the_decoded_node() {
+    let store = Arc::new(InMemoryStore::new());
+    let mut tree = ProllyTree::new(Arc::clone(&store), TreeConfig::default());
+    tree.insert(b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    let root = tree.get_root_hash().unwrap();
+
+    let cache = NodeCache::default();
+    let first = cache.load(store.as_ref(), &root).await.unwrap();
+    let second = cache.load(store.as_ref(), &root).await.unwrap();
+    let many = cache.load_many(store.as_ref(), &[root]).await.unwrap();
+    assert!(Arc::ptr_eq(&first, &second) && Arc::ptr_eq(&first, &many[0]));
+    assert_eq!(cache.stats().hits, 2);
+}
@@ -0,0 +1,31 @@
+#![cfg(target_arch = "wasm32")]
+
+use std::sync::Arc;
+
+use prolly_rust::common::TreeConfig;
+use prolly_rust::diff::DiffOptions;
+use prolly_rust::error::ProllyError;
+use prolly_rust::node::definition::{InternalEntry, Node};
+use prolly_rust::store::{ChunkStore, InMemoryStore};
+use prolly_rust::tree::ProllyTree;
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+async fn diffing_an_internal_node_at_level_zero_fails_cleanly() {
+    let config = TreeConfig { target_fanout: 4, min_fanout: 2, ..TreeConfig::default() };
+    let store = Arc::new(InMemoryStore::new());
+    let mut tree = ProllyTree::new(Arc::clone(&store), config);
+    for i in 0..20 {
+        tree.insert(format!("key{i:02}").into_bytes(), b"value".to_vec()).await.unwrap();
+    }
+    let root = tree.get_root_hash().unwrap();
+    // A forged parent of the real root that claims to be at the leaf level.
+    let forged = Node::Internal {
+        level: 0,
+        children: vec![InternalEntry { boundary_key: b"key19".to_vec(), child_hash: root, num_items_subtree: 20 }],
+    };
+    let forged_root = store.put(forged.encode().unwrap()).await.unwrap();
+
+    assert!(matches!(tree.diff(Some(forged_root), DiffOptions::default()).await, Err(ProllyError::InvalidOperation(_))));
+    assert!(matches!(tree.changed_ranges(Some(forged_root)).await, Err(ProllyError::InvalidOperation(_))));
+}
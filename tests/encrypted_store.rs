@@ -0,0 +1,108 @@
+#![cfg(target_arch = "wasm32")]
+
+use std::sync::Arc;
+
+use prolly_rust::common::TreeConfig;
+use prolly_rust::error::ProllyError;
+use prolly_rust::store::{ChunkStore, EncryptedStore, EncryptionMode, InMemoryStore};
+use prolly_rust::tree::ProllyTree;
+use wasm_bindgen_test::*;
+
+const KEY: [u8; 32] = [7; 32];
+
+fn config() -> TreeConfig {
+    TreeConfig { target_fanout: 8, min_fanout: 2, ..TreeConfig::default() }
+}
+
+fn key(i: usize) -> Vec<u8> {
+    format!("key{i:04}").into_bytes()
+}
+
+/// Inline values for most keys, and a value large enough to be chunked every tenth key.
+fn value(i: usize) -> Vec<u8> {
+    if i.is_multiple_of(10) { format!("large value {i} ").repeat(600).into_bytes() } else { format!("value{i}").into_bytes() }
+}
+
+async fn encrypted_tree(mode: EncryptionMode) -> (Arc<InMemoryStore>, ProllyTree<EncryptedStore<InMemoryStore>>) {
+    let inner = Arc::new(InMemoryStore::new());
+    let mut tree = ProllyTree::new(Arc::new(EncryptedStore::new(Arc::clone(&inner), KEY, mode)), config());
+    for i in 0..200 {
+        tree.insert(key(i), value(i)).await.unwrap();
+    }
+    (inner, tree)
+}
+
+#[wasm_bindgen_test]
+async fn trees_round_trip_without_plaintext_reaching_the_inner_store() {
+    for mode in [EncryptionMode::Convergent, EncryptionMode::RandomNonce] {
+        let (inner, tree) = encrypted_tree(mode).await;
+        for i in 0..200 {
+            assert_eq!(tree.get(&key(i)).await.unwrap(), Some(value(i)), "{mode:?}");
+        }
+        for bytes in inner.get_all_chunks_for_test().await.values() {
+            assert!(!bytes.windows(8).any(|window| window == b"value199" || window == b"large va"), "{mode:?}");
+        }
+
+        // Only convergent encryption keeps identical chunks deduplicated.
+        let first = tree.store.put(b"same bytes".to_vec()).await.unwrap();
+        let second = tree.store.put(b"same bytes".to_vec()).await.unwrap();
+        assert_eq!(first == second, mode == EncryptionMode::Convergent);
+        assert_eq!(tree.store.get(&second).await.unwrap(), Some(b"same bytes".to_vec()));
+    }
+}
+
+#[wasm_bindgen_test]
+async fn tampered_chunks_and_wrong_keys_are_rejected() {
+    for mode in [EncryptionMode::Convergent, EncryptionMode::RandomNonce] {
+        let (inner, tree) = encrypted_tree(mode).await;
+        let root = tree.get_root_hash().unwrap();
+
+        let mut envelope = inner.get(&root).await.unwrap().unwrap();
+        let last = envelope.len() - 1;
+        envelope[last] ^= 1;
+        let tampered = inner.put(envelope).await.unwrap();
+        assert!(matches!(tree.store.get(&tampered).await, Err(ProllyError::EncryptionError(_))), "{mode:?}");
+
+        let wrong_key = EncryptedStore::new(Arc::clone(&inner), [8; 32], mode);
+        assert!(matches!(wrong_key.get(&root).await, Err(ProllyError::EncryptionError(_))), "{mode:?}");
+    }
+}
+
+#[wasm_bindgen_test]
+async fn rotated_keys_reencrypt_the_tree_and_gc_reclaims_the_old_chunks() {
+    let (inner, mut tree) = encrypted_tree(EncryptionMode::Convergent).await;
+    let old_root = tree.get_root_hash().unwrap();
+    tree.gc(&[]).await.unwrap();
+    let live_chunks = inner.chunk_count().await.unwrap();
+
+    let new_roots = tree.store.rotate_key([9; 32], &[old_root]).await.unwrap();
+    assert_ne!(new_roots[0], old_root);
+    assert!(tree.store.get(&old_root).await.is_err());
+    tree.checkout(Some(new_roots[0])).await.unwrap();
+    assert_eq!(tree.gc(&[]).await.unwrap() as u64, live_chunks);
+    assert_eq!(inner.chunk_count().await.unwrap(), live_chunks);
+    for i in 0..200 {
+        assert_eq!(tree.get(&key(i)).await.unwrap(), Some(value(i)));
+    }
+
+    let reopened = ProllyTree::from_root_hash(new_roots[0], Arc::new(EncryptedStore::new(inner, [9; 32], EncryptionMode::Convergent)), config())
+        .await
+        .unwrap();
+    assert_eq!(reopened.get(&key(150)).await.unwrap(), Some(value(150)));
+}
+
+#[wasm_bindgen_test]
+async fn copies_by_hash_need_stores_that_address_chunks_alike() {
+    let (_, tree) = encrypted_tree(EncryptionMode::Convergent).await;
+
+    let same_key = Arc::new(EncryptedStore::new(Arc::new(InMemoryStore::new()), KEY, EncryptionMode::Convergent));
+    tree.sync_to(Arc::clone(&same_key), Default::default()).await.unwrap();
+    let replica = ProllyTree::from_root_hash(tree.get_root_hash().unwrap(), same_key, config()).await.unwrap();
+    assert_eq!(replica.get(&key(40)).await.unwrap(), Some(value(40)));
+
+    let random_nonce = Arc::new(EncryptedStore::new(Arc::new(InMemoryStore::new()), KEY, EncryptionMode::RandomNonce));
+    let result = tree.sync_to(random_nonce, Default::default()).await;
+    assert!(matches!(result, Err(ProllyError::ChecksumMismatch { .. })));
+    let result = tree.sync_to(Arc::new(InMemoryStore::new()), Default::default()).await;
+    assert!(matches!(result, Err(ProllyError::ChecksumMismatch { .. })));
+}
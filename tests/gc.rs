@@ -0,0 +1,265 @@
+#![cfg(target_arch = "wasm32")]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use prolly_rust::common::{Hash, TreeConfig};
+use prolly_rust::error::{ProllyError, Result};
+use prolly_rust::gc::{ChunkKind, DeadChunk, GarbageCollector, GcPhase, GcPlan, IncrementalGc};
+use prolly_rust::store::{ChunkStore, InMemoryStore};
+use prolly_rust::store::file_io_v2::{read_prly_delta_v2, read_prly_tree_v2, write_prly_delta_v2};
+use prolly_rust::store::format_v2::DELTA_SIGNATURE_V2;
+use prolly_rust::tree::ProllyTree;
+use wasm_bindgen_test::*;
+
+/// Counts the chunks and hashes each store call touches.
+#[derive(Debug, Default)]
+struct CountingStore {
+    inner: InMemoryStore,
+    touched: AtomicUsize,
+}
+
+impl CountingStore {
+    fn count(&self, items: usize) {
+        self.touched.fetch_add(items, Ordering::SeqCst);
+    }
+
+    fn take_touched(&self) -> usize {
+        self.touched.swap(0, Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl ChunkStore for CountingStore {
+    async fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>> {
+        self.count(1);
+        self.inner.get(hash).await
+    }
+
+    async fn put(&self, bytes: Vec<u8>) -> Result<Hash> {
+        self.inner.put(bytes).await
+    }
+
+    async fn get_many(&self, hashes: &[Hash]) -> Result<Vec<Option<Vec<u8>>>> {
+        self.count(hashes.len());
+        self.inner.get_many(hashes).await
+    }
+
+    async fn delete_batch(&self, hashes: &[Hash]) -> Result<()> {
+        self.inner.delete_batch(hashes).await
+    }
+
+    async fn all_hashes(&self) -> Result<Vec<Hash>> {
+        let hashes = self.inner.all_hashes().await?;
+        self.count(hashes.len());
+        Ok(hashes)
+    }
+
+    async fn chunk_count(&self) -> Result<u64> {
+        self.inner.chunk_count().await
+    }
+
+    async fn hashes_page(&self, after: Option<&Hash>, limit: usize) -> Result<Vec<Hash>> {
+        let page = self.inner.hashes_page(after, limit).await?;
+        self.count(page.len());
+        Ok(page)
+    }
+
+    async fn write_generation(&self) -> Result<Option<u64>> {
+        self.inner.write_generation().await
+    }
+
+    async fn delete_batch_unless_put_since(&self, hashes: &[Hash], generation: u64) -> Result<usize> {
+        self.count(hashes.len());
+        self.inner.delete_batch_unless_put_since(hashes, generation).await
+    }
+}
+
+/// A store that implements only the required methods, so it tracks no write generations.
+#[derive(Debug, Default)]
+struct UntrackedStore {
+    inner: InMemoryStore,
+}
+
+#[async_trait]
+impl ChunkStore for UntrackedStore {
+    async fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>> {
+        self.inner.get(hash).await
+    }
+
+    async fn put(&self, bytes: Vec<u8>) -> Result<Hash> {
+        self.inner.put(bytes).await
+    }
+
+    async fn delete_batch(&self, hashes: &[Hash]) -> Result<()> {
+        self.inner.delete_batch(hashes).await
+    }
+
+    async fn all_hashes(&self) -> Result<Vec<Hash>> {
+        self.inner.all_hashes().await
+    }
+}
+
+fn key(i: usize) -> Vec<u8> {
+    format!("key{i:04}").into_bytes()
+}
+
+#[wasm_bindgen_test]
+async fn incremental_gc_steps_touch_at_most_their_budget() {
+    let config = TreeConfig { target_fanout: 8, min_fanout: 2, ..TreeConfig::default() };
+    let store = Arc::new(CountingStore::default());
+    let mut tree = ProllyTree::new(Arc::clone(&store), config);
+    for i in 0..400 {
+        tree.insert(key(i), format!("v{i}").into_bytes()).await.unwrap();
+    }
+    for i in (0..400).step_by(2) {
+        tree.insert(key(i), format!("w{i}").into_bytes()).await.unwrap();
+    }
+    let root = tree.get_root_hash().unwrap();
+
+    const BUDGET: usize = 3;
+    let mut gc = IncrementalGc::start(Arc::clone(&store), &[root]).await.unwrap();
+    store.take_touched();
+    let mut steps = 0;
+    let mut swept_steps = 0;
+    while !gc.is_done() {
+        let progress = gc.step(BUDGET).await.unwrap();
+        // Each chunk is read once for marking or sweeping, and dead ones once more to delete.
+        let touched = store.take_touched();
+        assert!(touched <= 2 * BUDGET, "step {steps} touched {touched} chunks");
+        if progress.phase == GcPhase::Sweep {
+            assert!(progress.sweep_total >= progress.swept_chunks);
+            swept_steps += 1;
+        }
+        steps += 1;
+    }
+    let progress = gc.progress();
+    assert!(swept_steps > 1);
+    assert!(progress.collected_chunks > 0);
+    assert_eq!(progress.swept_chunks, progress.sweep_total);
+    for i in 0..400 {
+        let expected = if i % 2 == 0 { format!("w{i}") } else { format!("v{i}") };
+        assert_eq!(tree.get(&key(i)).await.unwrap(), Some(expected.into_bytes()));
+    }
+}
+
+#[wasm_bindgen_test]
+async fn chunks_of_a_write_in_progress_survive_a_collection_started_after_them() {
+    let config = TreeConfig { target_fanout: 8, min_fanout: 2, ..TreeConfig::default() };
+    let store = Arc::new(InMemoryStore::new());
+    let mut tree = ProllyTree::new(Arc::clone(&store), config);
+    for i in 0..50 {
+        tree.insert(key(i), format!("v{i}").into_bytes()).await.unwrap();
+    }
+    let root = tree.get_root_hash().unwrap();
+    let collector = GarbageCollector::new(Arc::clone(&store));
+    collector.collect(&[root]).await.unwrap();
+
+    // Another writer puts a leaf, then a collection starts before it publishes the parent.
+    let write_id = store.begin_write().unwrap().unwrap();
+    let leaf = store.put(b"leaf of a write in progress".to_vec()).await.unwrap();
+    assert_eq!(collector.collect(&[root]).await.unwrap(), 0);
+    assert!(store.exists(&leaf).await.unwrap());
+    let mut gc = IncrementalGc::start(Arc::clone(&store), &[root]).await.unwrap();
+    while !gc.is_done() {
+        gc.step(4).await.unwrap();
+    }
+    assert!(store.exists(&leaf).await.unwrap());
+
+    // Once the write has ended, its unpublished chunks are garbage like any other.
+    store.end_write(write_id).unwrap();
+    assert!(store.end_write(write_id).is_err());
+    assert_eq!(collector.collect(&[root]).await.unwrap(), 1);
+    assert!(!store.exists(&leaf).await.unwrap());
+    for i in 0..50 {
+        assert_eq!(tree.get(&key(i)).await.unwrap(), Some(format!("v{i}").into_bytes()));
+    }
+}
+
+#[wasm_bindgen_test]
+async fn executing_an_edited_plan_spares_reachable_chunks() {
+    let config = TreeConfig { target_fanout: 8, min_fanout: 2, ..TreeConfig::default() };
+    let store = Arc::new(InMemoryStore::new());
+    let mut tree = ProllyTree::new(Arc::clone(&store), config);
+    for i in 0..100 {
+        tree.insert(key(i), format!("v{i}").into_bytes()).await.unwrap();
+    }
+    let root = tree.get_root_hash().unwrap();
+    let mut plan = tree.plan_gc(&[]).await.unwrap();
+    let planned = plan.dead_chunks.len();
+    assert!(planned > 0);
+
+    // List every chunk of the live tree as dead, as a tampered or foreign plan could.
+    let live_hashes = tree.live_chunks(&[]).await.unwrap().1;
+    plan.dead_chunks.extend(live_hashes.keys().map(|hash| DeadChunk { hash: *hash, size: 0, kind: ChunkKind::Node }));
+    assert_eq!(tree.collect_gc_plan(&plan).await.unwrap(), planned);
+
+    // A plan built by hand, without a generation to spare new writes, is checked the same way.
+    let foreign = GcPlan {
+        live_roots: vec![root],
+        dead_chunks: live_hashes.keys().map(|hash| DeadChunk { hash: *hash, size: 0, kind: ChunkKind::Value }).collect(),
+        ..GcPlan::default()
+    };
+    assert_eq!(tree.collect_gc_plan(&foreign).await.unwrap(), 0);
+    for i in 0..100 {
+        assert_eq!(tree.get(&key(i)).await.unwrap(), Some(format!("v{i}").into_bytes()));
+    }
+}
+
+#[wasm_bindgen_test]
+async fn delta_export_reads_only_what_differs_from_the_base() {
+    let config = TreeConfig { target_fanout: 8, min_fanout: 2, ..TreeConfig::default() };
+    let store = Arc::new(CountingStore::default());
+    let mut tree = ProllyTree::new(Arc::clone(&store), config.clone());
+    for i in 0..2000 {
+        tree.insert(key(i), format!("v{i}").into_bytes()).await.unwrap();
+    }
+    let base = tree.get_root_hash();
+    let mut replica = ProllyTree::new(Arc::new(InMemoryStore::new()), config);
+    tree.sync_to(Arc::clone(&replica.store), Default::default()).await.unwrap();
+    replica.checkout(base).await.unwrap();
+    tree.insert(key(1234), b"changed".to_vec()).await.unwrap();
+    let target = tree.get_root_hash();
+
+    store.take_touched();
+    let bundle = tree.export_delta(base, target).await.unwrap();
+    let touched = store.take_touched();
+    let live = tree.live_chunks(&[]).await.unwrap().1.len();
+    assert!(touched < live / 10, "read {touched} of {live} chunks");
+    assert!(!bundle.chunks.is_empty() && bundle.chunks.len() <= touched);
+
+    // Bundles carry their own signature, so readers of full tree files reject them.
+    let file_bytes = write_prly_delta_v2(&bundle, &tree.config, None).unwrap();
+    assert_eq!(&file_bytes[..8], DELTA_SIGNATURE_V2);
+    assert!(matches!(read_prly_tree_v2(&file_bytes), Err(ProllyError::InvalidFileFormat(_))));
+    assert_eq!(read_prly_delta_v2(&file_bytes).unwrap().0, bundle);
+
+    // A chunk stored under another hash than the bundle's fails the import.
+    let mut tampered = bundle.clone();
+    let leaf = tampered.chunks.values_mut().find(|bytes| bytes.windows(7).any(|window| window == b"changed")).unwrap();
+    let at = leaf.windows(7).position(|window| window == b"changed").unwrap();
+    leaf[at] = b'C';
+    assert!(matches!(replica.import_delta(&tampered).await, Err(ProllyError::ChecksumMismatch { .. })));
+    assert_eq!(replica.get_root_hash(), base);
+
+    assert!(replica.import_delta(&bundle).await.unwrap());
+    assert_eq!(replica.get_root_hash(), target);
+    assert_eq!(replica.get(&key(1234)).await.unwrap(), Some(b"changed".to_vec()));
+}
+
+#[wasm_bindgen_test]
+async fn stores_without_write_generations_are_not_collected() {
+    let store = Arc::new(UntrackedStore::default());
+    let mut tree = ProllyTree::new(Arc::clone(&store), TreeConfig { target_fanout: 4, min_fanout: 2, ..TreeConfig::default() });
+    for i in 0..20 {
+        tree.insert(key(i), format!("v{i}").into_bytes()).await.unwrap();
+    }
+    let chunk_count = store.chunk_count().await.unwrap();
+
+    assert!(matches!(tree.gc(&[]).await, Err(ProllyError::InvalidOperation(_))));
+    assert!(matches!(tree.plan_gc(&[]).await, Err(ProllyError::InvalidOperation(_))));
+    assert!(matches!(IncrementalGc::start(Arc::clone(&store), &[]).await, Err(ProllyError::InvalidOperation(_))));
+    assert!(matches!(store.hashes_page(None, 10).await, Err(ProllyError::InvalidOperation(_))));
+    assert_eq!(store.chunk_count().await.unwrap(), chunk_count);
+}
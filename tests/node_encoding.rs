@@ -0,0 +1,41 @@
+#![cfg(target_arch = "wasm32")]
+
+use prolly_rust::node::definition::{InternalEntry, LeafEntry, Node, ValueRepr};
+use wasm_bindgen_test::*;
+
+fn leaf() -> Node {
+    let entries = (0..64u32)
+        .map(|i| LeafEntry {
+            key: format!("users/2025/region-eu/account-{i:06}").into_bytes(),
+            value: if i.is_multiple_of(8) { ValueRepr::Chunked([i as u8; 32]) } else { ValueRepr::Inline(format!("v{i}").into_bytes()) },
+        })
+        .collect();
+    Node::Leaf { level: 0, entries }
+}
+
+fn internal() -> Node {
+    let children = (0..32)
+        .map(|i| InternalEntry {
+            boundary_key: format!("users/2025/region-eu/account-{:06}", i * 64 + 63).into_bytes(),
+            child_hash: [i as u8; 32],
+            num_items_subtree: 64,
+        })
+        .collect();
+    Node::Internal { level: 1, children }
+}
+
+#[wasm_bindgen_test]
+fn legacy_nodes_decode_and_reencode_smaller() {
+    for node in [leaf(), internal()] {
+        // Nodes written before prefix compression are plain bincode.
+        let legacy = bincode::serialize(&node).unwrap();
+        assert!(legacy[0] <= 1);
+        assert_eq!(Node::decode(&legacy).unwrap(), node);
+
+        let encoded = node.encode().unwrap();
+        assert_eq!(encoded[0], 0xC1);
+        assert_eq!(Node::decode(&encoded).unwrap(), node);
+        // The shared key prefixes are stored once per key instead of in full.
+        assert!(encoded.len() * 2 < legacy.len(), "{} bytes encoded, {} legacy", encoded.len(), legacy.len());
+    }
+}
@@ -0,0 +1,58 @@
+#![cfg(target_arch = "wasm32")]
+
+use std::sync::Arc;
+
+use prolly_rust::common::{Hash, TreeConfig};
+use prolly_rust::error::ProllyError;
+use prolly_rust::store::InMemoryStore;
+use prolly_rust::store::patch_format::{read_patch_v1, write_patch_v1, Patch, PatchValue};
+use prolly_rust::tree::ProllyTree;
+use wasm_bindgen_test::*;
+
+fn key(i: usize) -> Vec<u8> {
+    format!("key{i:03}").into_bytes()
+}
+
+/// A source that moved past `base` and a replica at `base` holding none of the source's chunks.
+async fn trees() -> (ProllyTree<InMemoryStore>, ProllyTree<InMemoryStore>, Option<Hash>) {
+    let config = TreeConfig { target_fanout: 4, min_fanout: 2, ..TreeConfig::default() };
+    let mut source = ProllyTree::new(Arc::new(InMemoryStore::new()), config.clone());
+    let mut replica = ProllyTree::new(Arc::new(InMemoryStore::new()), config);
+    for i in 0..40 {
+        source.insert(key(i), format!("v{i}").into_bytes()).await.unwrap();
+        replica.insert(key(i), format!("v{i}").into_bytes()).await.unwrap();
+    }
+    let base = source.get_root_hash();
+    for i in (0..40).rev().step_by(3) {
+        source.insert(key(i), format!("changed{i}").into_bytes()).await.unwrap();
+    }
+    source.delete(&key(7)).await.unwrap();
+    (source, replica, base)
+}
+
+async fn export(source: &ProllyTree<InMemoryStore>, base: Option<Hash>) -> Patch {
+    let patch = source.export_patch(base, source.get_root_hash(), true).await.unwrap();
+    read_patch_v1(&write_patch_v1(&patch).unwrap()).unwrap()
+}
+
+#[wasm_bindgen_test]
+async fn patch_imports_on_a_replica_without_the_target_root() {
+    let (source, mut replica, base) = trees().await;
+    let patch = export(&source, base).await;
+
+    assert!(replica.import_patch(&patch).await.unwrap());
+    assert!(replica.diff_with_store(source.get_root_hash(), Arc::clone(&source.store), Default::default()).await.unwrap().is_empty());
+}
+
+#[wasm_bindgen_test]
+async fn forged_records_with_valid_checksums_are_rejected() {
+    let (source, mut replica, base) = trees().await;
+    let mut patch = export(&source, base).await;
+    let record = patch.records.iter_mut().find(|record| record.value.is_some()).unwrap();
+    record.value = Some(PatchValue::Inline(b"forged".to_vec()));
+    // Rewriting the file recomputes its checksums, so only the target digest can catch this.
+    let forged = read_patch_v1(&write_patch_v1(&patch).unwrap()).unwrap();
+
+    assert!(matches!(replica.import_patch(&forged).await, Err(ProllyError::ChecksumMismatch { .. })));
+    assert_eq!(replica.get_root_hash(), base);
+}
@@ -0,0 +1,145 @@
+#![cfg(target_arch = "wasm32")]
+
+use std::sync::Arc;
+
+use prolly_rust::common::TreeConfig;
+use prolly_rust::gc::GcPhase;
+use prolly_rust::store::{ChunkStore, InMemoryStore, RefCountedStore};
+use prolly_rust::tree::ProllyTree;
+use wasm_bindgen_test::*;
+
+fn config() -> TreeConfig {
+    TreeConfig { target_fanout: 8, min_fanout: 2, ..TreeConfig::default() }
+}
+
+/// A small deterministic xorshift generator.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[wasm_bindgen_test]
+async fn counts_match_a_recount_and_mark_and_sweep_across_root_replacements() {
+    let inner = Arc::new(InMemoryStore::new());
+    let store = Arc::new(RefCountedStore::new(Arc::clone(&inner)));
+    let mut tree = ProllyTree::new(Arc::clone(&store), config());
+    let mut rng = Rng(7);
+    let mut snapshot = None;
+    for round in 0..30 {
+        let old_root = tree.get_root_hash();
+        for _ in 0..30 {
+            let k = rng.next() % 400;
+            let key = format!("k{k:04}").into_bytes();
+            match rng.next() % 5 {
+                0 => { tree.delete(&key).await.unwrap(); }
+                // Large values are chunked, so leaves reference value chunks too.
+                1 => { tree.insert(key, vec![(k % 7) as u8; 5000]).await.unwrap(); }
+                _ => { tree.insert(key, format!("v{}", rng.next() % 10).into_bytes()).await.unwrap(); }
+            }
+        }
+        let new_root = tree.get_root_hash();
+        store.replace_root(old_root, new_root).await.unwrap();
+        if round == 10 {
+            snapshot = new_root;
+            store.retain_root(new_root.unwrap()).await.unwrap();
+        }
+        if round == 20 {
+            assert!(store.release_root(&snapshot.unwrap()).await.unwrap() > 0);
+        }
+
+        let check = store.check_consistency().await.unwrap();
+        assert!(check.is_consistent(), "round {round}: miscounted {:?}", check.miscounted);
+        // Reference counting frees exactly what a mark-and-sweep would.
+        assert!(check.unfreed.is_empty(), "round {round}: {} chunks not freed", check.unfreed.len());
+        assert_eq!(check.chunks, check.reachable_chunks);
+        assert_eq!(inner.chunk_count().await.unwrap(), check.reachable_chunks);
+    }
+    assert!(store.release_root(&snapshot.unwrap()).await.is_err());
+}
+
+#[wasm_bindgen_test]
+async fn rebuild_counts_an_existing_store() {
+    let inner = Arc::new(InMemoryStore::new());
+    let mut tree = ProllyTree::new(Arc::clone(&inner), config());
+    for i in 0..200 {
+        tree.insert(format!("k{i:04}").into_bytes(), format!("v{i}").into_bytes()).await.unwrap();
+    }
+    let root = tree.get_root_hash().unwrap();
+
+    let store = RefCountedStore::rebuild(inner, &[root]).await.unwrap();
+    let check = store.check_consistency().await.unwrap();
+    assert!(check.is_consistent());
+    // The intermediate nodes of the inserts are unreferenced until freed.
+    assert!(!check.unfreed.is_empty());
+    assert_eq!(store.free_unreferenced().await.unwrap(), check.unfreed.len());
+    assert!(store.check_consistency().await.unwrap().unfreed.is_empty());
+}
+
+#[wasm_bindgen_test]
+async fn garbage_collection_through_the_wrapper_keeps_counts_and_barrier() {
+    let inner = Arc::new(InMemoryStore::new());
+    let store = Arc::new(RefCountedStore::new(Arc::clone(&inner)));
+    assert!(store.write_generation().await.unwrap().is_some());
+
+    let mut tree = ProllyTree::new(Arc::clone(&store), config());
+    for i in 0..300 {
+        tree.insert(format!("k{i:04}").into_bytes(), format!("v{i}").into_bytes()).await.unwrap();
+    }
+    store.retain_root(tree.get_root_hash().unwrap()).await.unwrap();
+    assert!(tree.gc(&[]).await.unwrap() > 0);
+    let check = store.check_consistency().await.unwrap();
+    assert!(check.is_consistent(), "miscounted {:?}", check.miscounted);
+    assert!(check.unfreed.is_empty());
+
+    // A chunk put again after the barrier is spared even though nothing references it.
+    let orphan = store.put(b"orphan".to_vec()).await.unwrap();
+    let generation = store.write_generation().await.unwrap().unwrap();
+    store.put(b"orphan".to_vec()).await.unwrap();
+    assert_eq!(store.delete_batch_unless_put_since(&[orphan], generation).await.unwrap(), 0);
+    assert!(store.exists(&orphan).await.unwrap());
+    let generation = store.write_generation().await.unwrap().unwrap();
+    assert_eq!(store.delete_batch_unless_put_since(&[orphan], generation).await.unwrap(), 1);
+    assert!(store.check_consistency().await.unwrap().is_consistent());
+
+    let old_root = tree.get_root_hash();
+    for i in (0..300).step_by(3) {
+        tree.delete(&format!("k{i:04}").into_bytes()).await.unwrap();
+    }
+    store.replace_root(old_root, tree.get_root_hash()).await.unwrap();
+    let mut progress = tree.start_incremental_gc(&[]).await.unwrap();
+    while progress.phase != GcPhase::Done {
+        progress = tree.gc_step(16).await.unwrap();
+    }
+    let check = store.check_consistency().await.unwrap();
+    assert!(check.is_consistent(), "miscounted {:?}", check.miscounted);
+    for i in 0..300 {
+        let value = tree.get(&format!("k{i:04}").into_bytes()).await.unwrap();
+        assert_eq!(value.is_some(), i % 3 != 0);
+    }
+}
+
+#[wasm_bindgen_test]
+async fn synchronous_deletes_release_references() {
+    let inner = Arc::new(InMemoryStore::new());
+    let store = Arc::new(RefCountedStore::new(Arc::clone(&inner)));
+    let mut tree = ProllyTree::new(Arc::clone(&store), config());
+    for i in 0..100 {
+        tree.insert(format!("k{i:04}").into_bytes(), format!("v{i}").into_bytes()).await.unwrap();
+    }
+    let root = tree.get_root_hash().unwrap();
+    store.free_unreferenced().await.unwrap();
+
+    // Deleting the root directly releases its children, which become unreferenced.
+    store.delete_batch_sync(&[root]).unwrap();
+    assert!(!store.exists(&root).await.unwrap());
+    let check = store.check_consistency().await.unwrap();
+    assert!(check.is_consistent(), "miscounted {:?}", check.miscounted);
+    assert_eq!(store.free_unreferenced().await.unwrap() as u64, check.chunks);
+    assert_eq!(inner.chunk_count().await.unwrap(), 0);
+}
@@ -0,0 +1,124 @@
+#![cfg(target_arch = "wasm32")]
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use prolly_rust::common::{Hash, Key, TreeConfig};
+use prolly_rust::node::definition::{shortest_separator, Node};
+use prolly_rust::store::{ChunkStore, InMemoryStore};
+use prolly_rust::tree::ProllyTree;
+use wasm_bindgen_test::*;
+
+fn separator(left: &[u8], right: &[u8]) -> Key {
+    let separator = shortest_separator(left, right);
+    assert!(left <= separator.as_slice() && separator.as_slice() < right, "{left:?} {separator:?} {right:?}");
+    separator
+}
+
+#[wasm_bindgen_test]
+fn unordered_inputs_fall_back_to_left() {
+    assert_eq!(shortest_separator(b"b", b"a"), b"b");
+    assert_eq!(shortest_separator(b"same", b"same"), b"same");
+}
+
+#[wasm_bindgen_test]
+fn left_that_prefixes_right_is_its_own_separator() {
+    assert_eq!(separator(b"ab", b"abc"), b"ab");
+    assert_eq!(separator(b"", b"a"), b"");
+}
+
+#[wasm_bindgen_test]
+fn differing_byte_is_bumped_when_there_is_room() {
+    assert_eq!(separator(b"abc", b"abx"), b"abd");
+    assert_eq!(separator(b"account-0001", b"account-0009"), b"account-0002");
+}
+
+#[wasm_bindgen_test]
+fn adjacent_bytes_use_a_proper_prefix_of_right() {
+    assert_eq!(separator(b"apple", b"banana"), b"b");
+    assert_eq!(separator(b"abc", b"abdz"), b"abd");
+}
+
+#[wasm_bindgen_test]
+fn trailing_ff_bytes_carry_to_the_first_byte_that_can_be_bumped() {
+    assert_eq!(separator(&[0x61, 0xFF, 0xFF, 0x10], &[0x62]), [0x61, 0xFF, 0xFF, 0x11]);
+    assert_eq!(separator(&[0x61, 0x20, 0xFF], &[0x62]), [0x61, 0x21]);
+    // Nothing after the differing byte can be bumped, so only `left` separates them.
+    assert_eq!(separator(&[0x61, 0xFF, 0xFF], &[0x62]), [0x61, 0xFF, 0xFF]);
+}
+
+/// Checks that every key under `hash` lies in `(low, high]`, and that each child of an
+/// internal node holds only keys in (previous separator, own separator].
+async fn check_bounds(store: &InMemoryStore, hash: Hash, low: Option<&[u8]>, high: Option<&[u8]>) {
+    let node = Node::decode(&store.get(&hash).await.unwrap().unwrap()).unwrap();
+    match node {
+        Node::Leaf { entries, .. } => {
+            for entry in entries {
+                assert!(low.is_none_or(|low| low < entry.key.as_slice()), "{:?} is not above {low:?}", entry.key);
+                assert!(high.is_none_or(|high| entry.key.as_slice() <= high), "{:?} is above {high:?}", entry.key);
+            }
+        }
+        Node::Internal { children, .. } => {
+            let mut child_low = low;
+            for (idx, child) in children.iter().enumerate() {
+                // The last child inherits the parent's upper bound as well as its own.
+                let child_high = if idx + 1 == children.len() {
+                    Some(high.map_or(child.boundary_key.as_slice(), |high| high.min(child.boundary_key.as_slice())))
+                } else {
+                    Some(child.boundary_key.as_slice())
+                };
+                Box::pin(check_bounds(store, child.child_hash, child_low, child_high)).await;
+                child_low = Some(child.boundary_key.as_slice());
+            }
+        }
+    }
+}
+
+async fn check_tree(tree: &ProllyTree<InMemoryStore>) {
+    if let Some(root) = tree.get_root_hash() {
+        check_bounds(&tree.store, root, None, None).await;
+    }
+}
+
+/// Keys over a small alphabet that includes 0x00 and 0xFF, so that adjacent keys share
+/// prefixes and separators need every branch of `shortest_separator`.
+fn keys(count: usize) -> Vec<Key> {
+    let alphabet = [0x00, 0x41, 0x42, 0xFE, 0xFF];
+    let mut state = 0x2545_F491_u64;
+    let mut seen = HashSet::new();
+    (0..count)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let len = 1 + (state >> 60) as usize % 6;
+            (0..len).map(|i| alphabet[(state >> (8 * i)) as usize % alphabet.len()]).collect::<Key>()
+        })
+        .filter(|key| seen.insert(key.clone()))
+        .collect()
+}
+
+#[wasm_bindgen_test]
+async fn children_stay_within_their_separators_through_inserts_deletes_and_rebalances() {
+    let config = TreeConfig { target_fanout: 4, min_fanout: 2, ..TreeConfig::default() };
+    let mut tree = ProllyTree::new(Arc::new(InMemoryStore::new()), config);
+    let keys = keys(1500);
+    assert!(keys.len() > 400);
+    for (i, key) in keys.iter().enumerate() {
+        tree.insert(key.clone(), format!("v{i}").into_bytes()).await.unwrap();
+        if i % 50 == 0 {
+            check_tree(&tree).await;
+        }
+    }
+    check_tree(&tree).await;
+
+    // Deleting most keys underflows nodes, which then borrow from or merge with siblings.
+    for (i, key) in keys.iter().enumerate().filter(|(i, _)| i % 5 != 0) {
+        tree.delete(key).await.unwrap();
+        if i % 50 == 1 {
+            check_tree(&tree).await;
+        }
+    }
+    check_tree(&tree).await;
+    for key in keys.iter().step_by(5) {
+        assert!(tree.get(key).await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,122 @@
+#![cfg(target_arch = "wasm32")]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use prolly_rust::common::{Hash, TreeConfig};
+use prolly_rust::error::{ProllyError, Result};
+use prolly_rust::gc::GarbageCollector;
+use prolly_rust::store::{ChunkStore, InMemoryStore};
+use prolly_rust::sync::{SyncDestination, SyncMessage, SyncOptions};
+use prolly_rust::tree::ProllyTree;
+use wasm_bindgen_test::*;
+
+/// Records how often each chunk is read and how many existence checks are made.
+#[derive(Debug, Default)]
+struct RecordingStore {
+    inner: InMemoryStore,
+    reads: Mutex<HashMap<Hash, usize>>,
+    exists_calls: AtomicUsize,
+}
+
+impl RecordingStore {
+    fn record_reads(&self, hashes: &[Hash]) {
+        let mut reads = self.reads.lock().unwrap();
+        for hash in hashes {
+            *reads.entry(*hash).or_default() += 1;
+        }
+    }
+}
+
+#[async_trait]
+impl ChunkStore for RecordingStore {
+    async fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>> {
+        self.record_reads(std::slice::from_ref(hash));
+        self.inner.get(hash).await
+    }
+
+    async fn put(&self, bytes: Vec<u8>) -> Result<Hash> {
+        self.inner.put(bytes).await
+    }
+
+    async fn exists(&self, hash: &Hash) -> Result<bool> {
+        self.exists_calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.exists(hash).await
+    }
+
+    async fn get_many(&self, hashes: &[Hash]) -> Result<Vec<Option<Vec<u8>>>> {
+        self.record_reads(hashes);
+        self.inner.get_many(hashes).await
+    }
+
+    async fn exists_many(&self, hashes: &[Hash]) -> Result<Vec<bool>> {
+        self.exists_calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.exists_many(hashes).await
+    }
+
+    async fn delete_batch(&self, hashes: &[Hash]) -> Result<()> {
+        self.inner.delete_batch(hashes).await
+    }
+
+    async fn all_hashes(&self) -> Result<Vec<Hash>> {
+        self.inner.all_hashes().await
+    }
+}
+
+#[wasm_bindgen_test]
+async fn sync_reads_each_chunk_once_and_checks_each_offer_in_one_call() {
+    let config = TreeConfig { target_fanout: 8, min_fanout: 2, ..TreeConfig::default() };
+    let source_store = Arc::new(RecordingStore::default());
+    let mut source = ProllyTree::new(Arc::clone(&source_store), config.clone());
+    for i in 0..500usize {
+        let value = if i.is_multiple_of(50) { vec![i as u8; 6000] } else { format!("v{i}").into_bytes() };
+        source.insert(format!("key{i:04}").into_bytes(), value).await.unwrap();
+    }
+    source_store.reads.lock().unwrap().clear();
+
+    let destination = Arc::new(RecordingStore::default());
+    let stats = source.sync_to(Arc::clone(&destination), SyncOptions { batch_size: 16 }).await.unwrap();
+    assert!(stats.chunks_sent > 16);
+    let reads = source_store.reads.lock().unwrap().clone();
+    assert_eq!(reads.len() as u64, stats.chunks_sent);
+    assert!(reads.values().all(|count| *count == 1), "chunks read more than once: {reads:?}");
+    // One check per `Have` message, plus the check of the root on `Done`.
+    let have_messages = stats.round_trips - stats.chunks_sent.div_ceil(16) - 1;
+    assert_eq!(destination.exists_calls.load(Ordering::SeqCst) as u64, have_messages + 1);
+
+    let replica = ProllyTree::from_root_hash(source.get_root_hash().unwrap(), destination, config).await.unwrap();
+    assert_eq!(replica.get(&b"key0250".to_vec()).await.unwrap(), Some(vec![250u8; 6000]));
+}
+
+#[wasm_bindgen_test]
+async fn failed_syncs_do_not_hold_back_collections_of_the_destination() {
+    let config = TreeConfig { target_fanout: 8, min_fanout: 2, ..TreeConfig::default() };
+    let source_store = Arc::new(InMemoryStore::new());
+    let mut source = ProllyTree::new(Arc::clone(&source_store), config);
+    for i in 0..40u8 {
+        source.insert(format!("key{i:02}").into_bytes(), vec![i; 2000]).await.unwrap();
+    }
+    // Values are sent first and in key order, so the sync fails after some `Chunks` messages.
+    let last_value = InMemoryStore::new().put(vec![39; 2000]).await.unwrap();
+    source_store.delete_batch(&[last_value]).await.unwrap();
+
+    let destination = Arc::new(InMemoryStore::new());
+    let collector = GarbageCollector::new(Arc::clone(&destination));
+    assert!(matches!(
+        source.sync_to(Arc::clone(&destination), SyncOptions { batch_size: 4 }).await,
+        Err(ProllyError::ChunkNotFound(hash)) if hash == last_value
+    ));
+    let garbage = destination.put(b"written after the failed sync".to_vec()).await.unwrap();
+    assert!(collector.collect(&[]).await.unwrap() > 1);
+    assert!(!destination.exists(&garbage).await.unwrap());
+
+    // A `Chunks` message that fails at the destination ends its write as well.
+    let sync_destination = SyncDestination::new(Arc::clone(&destination));
+    let chunks = vec![([0u8; 32], b"not the chunk of this hash".to_vec())];
+    assert!(sync_destination.handle(SyncMessage::Chunks { chunks }).await.is_err());
+    let garbage = destination.put(b"written after the failed message".to_vec()).await.unwrap();
+    assert_eq!(collector.collect(&[]).await.unwrap(), 2);
+    assert!(!destination.exists(&garbage).await.unwrap());
+}